@@ -1,18 +1,23 @@
+use std::collections::HashMap;
 use std::path::Path;
 use std::sync::{Arc, Mutex};
 
 use crate::program_store::ProgramStore;
 use crate::record_store::RecordStore;
 use crate::validator_set::ValidatorSet;
-use anyhow::{bail, ensure, Result};
+use anyhow::{anyhow, bail, ensure, Result};
 use itertools::Itertools;
+use lib::deployment::{self, DeploymentInfo, Edition};
 use lib::validator::GenesisState;
-use lib::{query::AbciQuery, transaction::Transaction, vm};
+use lib::{is_credits_program, query::AbciQuery, transaction::Transaction, vm};
 use tendermint_abci::Application;
 use tendermint_proto::abci;
 
 use tracing::{debug, error, info};
 
+/// Maximum amount of programs returned by a single ListPrograms query.
+const MAX_PROGRAMS_PAGE: usize = 100;
+
 /// An Tendermint ABCI application that works with a SnarkVM backend.
 /// This struct implements the ABCI application hooks, forwarding commands through
 /// a channel for the parts that require knowledge of the application state and the SnarkVM details.
@@ -46,8 +51,25 @@ impl Application for SnarkVMApp {
                 .expect("failure adding genesis records");
         }
 
-        self.validators.lock().unwrap().replace(state.validators);
-        Default::default()
+        let mut validators = self.validators.lock().unwrap();
+        validators.replace(state.validators);
+        validators.set_params(state.staking);
+        validators.set_genesis_supply(state.supply);
+        validators.set_chain_id(request.chain_id.clone());
+
+        // only the validators with the most voting power take part in the consensus
+        let validators = validators
+            .select_validators()
+            .into_iter()
+            .map(|validator| abci::ValidatorUpdate {
+                pub_key: Some(validator.pub_key.into()),
+                power: validator.consensus_power() as i64,
+            })
+            .collect();
+        abci::ResponseInitChain {
+            validators,
+            ..Default::default()
+        }
     }
 
     /// This hook provides information about the ABCI application.
@@ -95,6 +117,39 @@ impl Application for SnarkVMApp {
                     bincode::serialize(&result.map(|(program, _keys)| program)).unwrap()
                 })
             }
+            Ok(AbciQuery::GetProgramEditions { program_id }) => {
+                debug!("Fetching editions of {}", program_id);
+                self.programs
+                    .editions(&program_id)
+                    .map(|result| bincode::serialize(&result).unwrap())
+            }
+            Ok(AbciQuery::GetProgramInfo { program_id }) => {
+                debug!("Fetching deployment info of {}", program_id);
+                self.programs
+                    .info(&program_id)
+                    .map(|result| bincode::serialize(&result).unwrap())
+            }
+            Ok(AbciQuery::ListPrograms { from, limit }) => {
+                debug!("Listing programs");
+                self.programs
+                    .list(from, limit.min(MAX_PROGRAMS_PAGE))
+                    .map(|result| bincode::serialize(&result).unwrap())
+            }
+            Ok(AbciQuery::GetSupply) => {
+                debug!("Fetching credits supply");
+                let supply = self.validators.lock().unwrap().supply();
+                Ok(bincode::serialize(&supply).unwrap())
+            }
+            Ok(AbciQuery::GetTreasury) => {
+                debug!("Fetching treasury");
+                let treasury = self.validators.lock().unwrap().treasury();
+                Ok(bincode::serialize(&treasury).unwrap())
+            }
+            Ok(AbciQuery::GetPendingRewards { address }) => {
+                debug!("Fetching pending rewards of {}", address);
+                let rewards = self.validators.lock().unwrap().pending_rewards(&address);
+                Ok(bincode::serialize(&rewards).unwrap())
+            }
             Err(e) => Err(e.into()),
         };
 
@@ -145,7 +200,7 @@ impl Application for SnarkVMApp {
 
     /// This hook is called before the app starts processing transactions on a block.
     /// Used to store current proposer and the previous block's voters to assign fees and coinbase
-    /// credits when the block is committed.
+    /// credits when the block is committed, and to jail offline validators and slash the ones reported for double signing.
     fn begin_block(&self, request: abci::RequestBeginBlock) -> abci::ResponseBeginBlock {
         // a call to begin block without header doesn't seem to make sense, verify it can happen
         // supporting this case is cumbersome, assuming it won't happen until proven wrong
@@ -157,10 +212,11 @@ impl Application for SnarkVMApp {
         // NOTE: because of how tendermint makes information available to this hook,
         // the block rewards go to this block's porposer and the **previous** block voters.
         // This could be revisited if it's a problem.
-        let votes = request
+        let last_votes = request
             .last_commit_info
             .map(|last_commit| last_commit.votes)
-            .unwrap_or_default()
+            .unwrap_or_default();
+        let votes: HashMap<Vec<u8>, u64> = last_votes
             .iter()
             .filter_map(|vote_info| {
                 if !vote_info.signed_last_block {
@@ -182,13 +238,71 @@ impl Application for SnarkVMApp {
             })
             .collect();
 
-        self.validators.lock().unwrap().begin_block(
-            &header.proposer_address,
-            votes,
-            header.height as u64,
-        );
+        // track the validators that didn't sign the previous block, to jail the ones that are offline
+        let signed: Vec<Vec<u8>> = votes.keys().cloned().collect();
+        let missed: Vec<Vec<u8>> = last_votes
+            .iter()
+            .filter(|vote_info| !vote_info.signed_last_block)
+            .filter_map(|vote_info| Some(vote_info.validator.as_ref()?.address.clone()))
+            .collect();
+
+        let mut validators = self.validators.lock().unwrap();
+        validators.begin_block(&header.proposer_address, votes, header.height as u64);
 
-        Default::default()
+        let attribute = |key: &str, value: String| abci::EventAttribute {
+            key: key.to_string().into_bytes(),
+            value: value.into_bytes(),
+            index: true,
+        };
+        let mut events: Vec<abci::Event> = validators
+            .track_missed_blocks(&signed, &missed)
+            .iter()
+            .map(|address| abci::Event {
+                r#type: "jail".to_string(),
+                attributes: vec![
+                    attribute("address", hex::encode_upper(address)),
+                    attribute("reason", "downtime".to_string()),
+                ],
+            })
+            .collect();
+
+        // punish the validators that tendermint found evidence of misbehavior for, i.e. double signing
+        let slash_events: Vec<abci::Event> = request
+            .byzantine_validators
+            .iter()
+            .filter_map(|evidence| {
+                let address = &evidence.validator.as_ref()?.address;
+                let slashed = match validators.slash(address) {
+                    Some(slashed) => slashed,
+                    None => {
+                        error!(
+                            "received evidence for unknown validator {}",
+                            hex::encode_upper(address)
+                        );
+                        return None;
+                    }
+                };
+
+                let reason = match abci::EvidenceType::from_i32(evidence.r#type) {
+                    Some(abci::EvidenceType::DuplicateVote) => "duplicate_vote",
+                    Some(abci::EvidenceType::LightClientAttack) => "light_client_attack",
+                    _ => "unknown",
+                };
+                Some(abci::Event {
+                    r#type: "slash".to_string(),
+                    attributes: vec![
+                        attribute("address", hex::encode_upper(address)),
+                        attribute("reason", reason.to_string()),
+                        attribute("height", evidence.height.to_string()),
+                        attribute("power", slashed.to_string()),
+                        attribute("jailed", validators.is_jailed(address).to_string()),
+                    ],
+                })
+            })
+            .collect();
+        events.extend(slash_events);
+
+        abci::ResponseBeginBlock { events }
     }
 
     /// This ABCI hook validates a transaction and applies it to the application state,
@@ -208,10 +322,10 @@ impl Application for SnarkVMApp {
             .check_no_duplicate_records(&tx)
             .and_then(|_| self.check_inputs_are_unspent(&tx))
             .and_then(|_| self.validate_transaction(&tx))
-            .map(|_| self.update_validators(&tx))
             .and_then(|_| self.spend_input_records(&tx))
             .and_then(|_| self.add_output_records(&tx))
-            .and_then(|_| self.store_program(&tx));
+            .and_then(|_| self.store_program(&tx))
+            .and_then(|_| self.update_validators(&tx));
 
         match result {
             Ok(_) => {
@@ -239,17 +353,18 @@ impl Application for SnarkVMApp {
         }
     }
 
-    /// Applies validator set updates based on staking transactions included in the block.
+    /// Applies validator set updates based on staking transactions included in the block, keeping only
+    /// the validators with the most voting power in the consensus.
     /// For details about validator set update semantics see:
     /// https://github.com/tendermint/tendermint/blob/v0.34.x/spec/abci/apps.md#endblock
     fn end_block(&self, _request: abci::RequestEndBlock) -> abci::ResponseEndBlock {
         let validator_set = self.validators.lock().unwrap();
         let validator_updates = validator_set
-            .pending_updates()
-            .iter()
-            .map(|validator| abci::ValidatorUpdate {
-                pub_key: Some(validator.pub_key.into()),
-                power: validator.voting_power as i64,
+            .consensus_updates()
+            .into_iter()
+            .map(|(pub_key, power)| abci::ValidatorUpdate {
+                pub_key: Some(pub_key.into()),
+                power: power as i64,
             })
             .collect();
 
@@ -285,9 +400,20 @@ impl Application for SnarkVMApp {
         let height = HeightFile::increment();
 
         let mut validators = self.validators.lock().unwrap();
-        for (commitment, record) in validators.block_rewards() {
+        validators.accrue_rewards();
+        for (commitment, record) in validators.claim_records() {
+            if let Err(err) = self.records.add(commitment, record) {
+                error!("Failed to add claimed rewards record to store {}", err);
+            }
+        }
+        for (commitment, record) in validators.withdrawal_records() {
+            if let Err(err) = self.records.add(commitment, record) {
+                error!("Failed to add withdrawal record to store {}", err);
+            }
+        }
+        for (commitment, record) in validators.treasury_records() {
             if let Err(err) = self.records.add(commitment, record) {
-                error!("Failed to add reward record to store {}", err);
+                error!("Failed to add treasury spend record to store {}", err);
             }
         }
         validators
@@ -366,15 +492,40 @@ impl SnarkVMApp {
             .unwrap_or(Ok(()))
     }
 
-    /// Apply validator set side-effects of the transaction: collecting fees and changing
-    /// the voting power based on staking transactions.
+    /// Apply validator set side-effects of the transaction: collecting fees, changing
+    /// the voting power based on staking transactions, unjailing validators,
+    /// withdrawing unbonded credits, changing validator keys and reward addresses, claiming rewards
+    /// and spending from the treasury.
+    /// This is applied after the record store changes, so it's skipped if any of them fails, and every change is
+    /// extracted from the transaction before applying any of them, so the validator set is never half updated.
     fn update_validators(&self, transaction: &Transaction) -> Result<()> {
+        let stake_updates = transaction.stake_updates()?;
+        let unjail_requests = transaction.staker_requests("unjail")?;
+        let withdraw_requests = transaction.staker_requests("withdraw")?;
+        let validator_changes = transaction.validator_changes()?;
+        let reward_claims = transaction.reward_claims()?;
+        let treasury_spends = transaction.treasury_spends()?;
+
         let mut validator_set = self.validators.lock().unwrap();
         validator_set.collect(transaction.fees() as u64);
-        transaction
-            .stake_updates()?
+        stake_updates
             .into_iter()
             .for_each(|update| validator_set.apply(update));
+        unjail_requests
+            .into_iter()
+            .for_each(|request| validator_set.unjail(request));
+        withdraw_requests
+            .into_iter()
+            .for_each(|request| validator_set.withdraw(request));
+        validator_changes
+            .into_iter()
+            .for_each(|change| validator_set.change(change));
+        reward_claims
+            .into_iter()
+            .for_each(|aleo_address| validator_set.claim(aleo_address));
+        treasury_spends
+            .into_iter()
+            .for_each(|spend| validator_set.spend(spend));
 
         Ok(())
     }
@@ -386,22 +537,49 @@ impl SnarkVMApp {
             Transaction::Deployment {
                 ref program,
                 verifying_keys,
+                certificates,
+                owner,
+                edition,
                 fee,
                 ..
             } => {
+                // a program can be deployed again only by its owner, as the edition following the latest one
+                let expected_edition = match self.programs.editions(program.id())? {
+                    Some(editions) => {
+                        ensure!(
+                            editions.owner == Some(*owner),
+                            format!("Program already exists: {}", program.id())
+                        );
+                        editions.latest() + 1
+                    }
+                    None => 0,
+                };
                 ensure!(
-                    !self.programs.exists(program.id()),
-                    format!("Program already exists: {}", program.id())
+                    *edition == expected_edition,
+                    "Deployment of {} is for edition {edition} but the next edition is {expected_edition}",
+                    program.id()
                 );
 
-                if let Some(transition) = fee {
-                    self.verify_transition(transition)?;
-                }
+                // the fee is checked first, since verifying the deployment can be as expensive as building its keys
+                let minimum_fee = deployment::minimum_fee(program);
+                let transition = fee.as_ref().ok_or_else(|| {
+                    anyhow!("Deployments require a fee of at least {minimum_fee} gates")
+                })?;
+                ensure!(
+                    transaction.fees() >= minimum_fee as i64,
+                    "Deployment fee of {} gates is below the minimum of {minimum_fee} gates",
+                    transaction.fees()
+                );
+                self.verify_transition(transition, 0)?;
 
-                // verify deployment is correct and keys are valid
-                vm::verify_deployment(program, verifying_keys.clone())
+                // verify deployment is correct and keys are certified to match the program
+                vm::verify_deployment(program, verifying_keys.clone(), certificates.clone())
             }
-            Transaction::Execution { transitions, .. } => {
+            Transaction::Execution {
+                transitions,
+                editions,
+                ..
+            } => {
                 ensure!(
                     !transitions.is_empty(),
                     "There are no transitions in the execution"
@@ -411,9 +589,41 @@ impl SnarkVMApp {
                 for update in transaction.stake_updates()? {
                     validator_set.validate(&update)?
                 }
+                for request in transaction.staker_requests("unjail")? {
+                    validator_set.validate_unjail(&request)?
+                }
+                for request in transaction.staker_requests("withdraw")? {
+                    validator_set.validate_withdraw(&request)?
+                }
+                for change in transaction.validator_changes()? {
+                    validator_set.validate_change(&change)?
+                }
+                for aleo_address in transaction.reward_claims()? {
+                    validator_set.validate_claim(&aleo_address)?
+                }
+                // the treasury keys sign each spend for the current amount of spends, so there can only be one per transaction
+                let treasury_spends = transaction.treasury_spends()?;
+                ensure!(
+                    treasury_spends.len() <= 1,
+                    "There can be only one treasury spend per transaction"
+                );
+                for spend in treasury_spends {
+                    validator_set.validate_spend(&spend)?
+                }
 
                 for transition in transitions {
-                    self.verify_transition(transition)?;
+                    // the credits program can't be upgraded, so its transitions (e.g. fees) always use the first edition
+                    let edition = if is_credits_program(transition.program_id()) {
+                        0
+                    } else {
+                        *editions.get(transition.program_id()).ok_or_else(|| {
+                            anyhow!(
+                                "The execution doesn't specify the edition of program {}",
+                                transition.program_id()
+                            )
+                        })?
+                    };
+                    self.verify_transition(transition, edition)?;
                 }
                 Ok(())
             }
@@ -426,17 +636,20 @@ impl SnarkVMApp {
         result
     }
 
-    /// Check the given execution transition with the verifying keys from the program store
-    fn verify_transition(&self, transition: &vm::Transition) -> Result<()> {
-        let stored_keys = self.programs.get(transition.program_id())?;
+    /// Check the given execution transition with the verifying keys of the given program edition from the program store
+    fn verify_transition(&self, transition: &vm::Transition, edition: Edition) -> Result<()> {
+        let stored_keys = self
+            .programs
+            .get_edition(transition.program_id(), edition)?;
 
         // only verify if we have the program available
         if let Some((_program, keys)) = stored_keys {
             vm::verify_execution(transition, &keys)
         } else {
             bail!(format!(
-                "Program {} does not exist",
-                transition.program_id()
+                "Program {} edition {} does not exist",
+                transition.program_id(),
+                edition
             ))
         }
     }
@@ -448,7 +661,12 @@ impl SnarkVMApp {
             ..
         } = transaction
         {
-            self.programs.add(program.id(), program, verifying_keys)?
+            let height = self.validators.lock().unwrap().current_height();
+            let info = DeploymentInfo::from_transaction(transaction, height)?;
+            let edition = self
+                .programs
+                .add(program.id(), program, verifying_keys, info)?;
+            info!("Stored edition {} of program {}", edition, program.id());
         }
         Ok(())
     }
@@ -483,7 +701,9 @@ impl HeightFile {
 // just covering a few special cases here. lower level test are done in record store and program store, higher level in integration tests.
 #[cfg(test)]
 mod tests {
+    use indexmap::IndexMap;
     use lib::{
+        program_file::ProgramFile,
         transaction::Transaction,
         vm::{self, Identifier},
     };
@@ -502,6 +722,10 @@ mod tests {
 
     use super::SnarkVMApp;
 
+    fn records_build() -> ProgramFile {
+        ProgramFile::build(include_str!("../../aleo/records.aleo")).unwrap()
+    }
+
     #[test]
     fn test_abci_hooks() {
         let app = SnarkVMApp {
@@ -518,7 +742,11 @@ mod tests {
 
         // deploy the program to the app
         let deployment_transaction =
-            Transaction::deployment(Path::new("aleo/records.aleo"), &private_key, None).unwrap();
+            Transaction::deployment(&records_build(), &private_key, 0, None).unwrap();
+
+        // deployments without a fee are rejected before their keys are verified
+        let response = app.check_tx(check_request(&deployment_transaction));
+        assert!(response.log.contains("Deployments require a fee"));
 
         let _ = app.store_program(&deployment_transaction);
 
@@ -531,13 +759,26 @@ mod tests {
                 vm::UserInputValueType::from_str(&address.to_string()).unwrap(),
             ],
             &private_key,
+            0,
+            None,
             None,
+            &mut rand::thread_rng(),
         )
         .unwrap();
 
         let check_tx_req = check_request(&transaction);
         assert!(app.check_tx(check_tx_req).code == 0);
 
+        // each transition is verified with the keys of its own program edition, which the execution must include
+        if let Transaction::Execution { transitions, .. } = &transaction {
+            let without_editions =
+                Transaction::from_transitions(transitions.clone(), IndexMap::new()).unwrap();
+            let response = app.check_tx(check_request(&without_editions));
+            assert!(response
+                .log
+                .contains("doesn't specify the edition of program"));
+        }
+
         let transaction_json = json!(transaction);
 
         #[cfg(feature = "lambdavm_backend")]
@@ -564,7 +805,10 @@ mod tests {
             Identifier::from_str("consume_two").unwrap(),
             &[record.clone(), record.clone()],
             &private_key,
+            0,
+            None,
             None,
+            &mut rand::thread_rng(),
         )
         .unwrap();
 
@@ -584,7 +828,10 @@ mod tests {
             Identifier::from_str("consume").unwrap(),
             &[record],
             &private_key,
+            0,
+            None,
             None,
+            &mut rand::thread_rng(),
         )
         .unwrap();
 
@@ -600,6 +847,36 @@ mod tests {
         assert!(app.deliver_tx(deliver_tx_req).code != 0);
     }
 
+    #[test]
+    fn test_deployment_certificates() {
+        let private_key = vm::PrivateKey::new(&mut rand::thread_rng()).unwrap();
+        let transaction = Transaction::deployment(&records_build(), &private_key, 0, None).unwrap();
+
+        if let Transaction::Deployment {
+            program,
+            verifying_keys,
+            certificates,
+            ..
+        } = transaction
+        {
+            // the keys generated by the deployment are certified
+            vm::verify_deployment(&program, verifying_keys.clone(), certificates.clone()).unwrap();
+
+            // swapping the verifying keys of two functions keeps the names but fails the verification
+            let mut tampered_keys = verifying_keys;
+            let mint = Identifier::from_str("mint").unwrap();
+            let consume = Identifier::from_str("consume").unwrap();
+            let mint_key = tampered_keys.map.get(&mint).unwrap().clone();
+            let consume_key = tampered_keys.map.get(&consume).unwrap().clone();
+            tampered_keys.map.insert(mint, consume_key);
+            tampered_keys.map.insert(consume, mint_key);
+
+            assert!(vm::verify_deployment(&program, tampered_keys, certificates).is_err());
+        } else {
+            panic!("expected a deployment transaction");
+        }
+    }
+
     fn check_request(transaction: &Transaction) -> RequestCheckTx {
         RequestCheckTx {
             tx: bincode::serialize(transaction).unwrap(),
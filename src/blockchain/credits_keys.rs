@@ -0,0 +1,32 @@
+/// Binary that builds the credits program keys from the fixed seed and writes the verifying keys and their hash
+/// to the files embedded in the aleo binaries, along with the hash of the lambdavm universal SRS. It needs to be
+/// run for each backend whenever the credits program or the vm backend change, since otherwise nodes will refuse
+/// to start with the stale keys.
+use std::path::PathBuf;
+
+use anyhow::Result;
+use lib::{deployment, vm};
+
+fn main() -> Result<()> {
+    let (_, keys) = vm::build_program_from_seed(lib::CREDITS_SOURCE, lib::CREDITS_SEED)?;
+    let verifying_keys = lib::verifying_keys(&keys);
+    let hash = deployment::verifying_keys_hash(&verifying_keys)?;
+
+    let aleo_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("aleo");
+    let keys_path = aleo_dir.join(format!("credits.{}.keys", vm::BACKEND));
+    let hash_path = aleo_dir.join(format!("credits.{}.sha256", vm::BACKEND));
+    std::fs::write(&keys_path, serde_json::to_string(&verifying_keys)?)?;
+    std::fs::write(&hash_path, format!("{hash}\n"))?;
+
+    println!("wrote {} and {}", keys_path.display(), hash_path.display());
+    println!("credits verifying keys hash: {hash}");
+
+    // lambdavm keys are derived from the universal SRS, so nodes need the same one to synthesize the same keys
+    #[cfg(feature = "lambdavm_backend")]
+    {
+        let srs_hash_path = aleo_dir.join("universal_srs.lambdavm.sha256");
+        std::fs::write(&srs_hash_path, format!("{}\n", vm::universal_srs_hash()?))?;
+        println!("wrote {}", srs_hash_path.display());
+    }
+    Ok(())
+}
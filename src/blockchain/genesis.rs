@@ -3,7 +3,7 @@
 /// expected by our abci app.
 use std::{collections::HashMap, path::PathBuf};
 
-use anyhow::Result;
+use anyhow::{anyhow, ensure, Result};
 use clap::Parser;
 use lib::{validator, vm};
 
@@ -23,6 +23,76 @@ pub struct Cli {
     /// The amount of gates to assign to each validator
     #[clap(long, default_value = "1000")]
     amount: u64,
+
+    /// The percentage of its voting power a validator loses when it double signs. Defaults to 5.
+    #[clap(long)]
+    double_sign_slash_percentage: Option<u64>,
+
+    /// Only slash validators that double sign, instead of also jailing them.
+    #[clap(long)]
+    no_double_sign_jail: bool,
+
+    /// The amount of recent blocks considered to decide whether a validator is offline. Defaults to 100.
+    #[clap(long)]
+    signed_blocks_window: Option<u64>,
+
+    /// Validators that miss more than this amount of blocks within the window are jailed. Defaults to 50.
+    #[clap(long)]
+    max_missed_blocks: Option<u64>,
+
+    /// The amount of blocks a validator jailed for being offline has to wait before unjailing. Defaults to 100.
+    #[clap(long)]
+    downtime_jail_blocks: Option<u64>,
+
+    /// The amount of blocks unstaked credits have to wait before they can be withdrawn. Defaults to 100.
+    #[clap(long)]
+    unbonding_blocks: Option<u64>,
+
+    /// The percentage of the rewards of a validator its operator takes before splitting the rest with
+    /// its delegators. Defaults to 10.
+    #[clap(long)]
+    commission_percentage: Option<u64>,
+
+    /// The maximum amount of validators that take part in the consensus, chosen by voting power.
+    /// Defaults to 100.
+    #[clap(long)]
+    max_validators: Option<u64>,
+
+    /// The gates the operator of a validator needs to have staked on it to take part in the consensus.
+    /// Defaults to 0.
+    #[clap(long)]
+    min_self_stake: Option<u64>,
+
+    /// The baseline credits minted as validator rewards on each block. Defaults to 100, and can't exceed 2^32 - 1.
+    #[clap(long)]
+    block_reward: Option<u64>,
+
+    /// The amount of blocks after which the block reward is cut by the reduction percentage.
+    /// If omitted, the block reward is never reduced.
+    #[clap(long, requires = "reward_reduction_percentage")]
+    reward_reduction_interval: Option<u64>,
+
+    /// The percentage of the block reward cut on each reduction, e.g. 50 to halve it.
+    #[clap(long, requires = "reward_reduction_interval")]
+    reward_reduction_percentage: Option<u64>,
+
+    /// The percentage of the fees of each block that is burned. Defaults to 0.
+    #[clap(long)]
+    fee_burn_percentage: Option<u64>,
+
+    /// The percentage of the fees of each block that accrues to the treasury. Defaults to 0.
+    /// Requires treasury keys and a threshold to spend it.
+    #[clap(long, requires = "treasury_threshold")]
+    treasury_percentage: Option<u64>,
+
+    /// A base64 encoded ed25519 public key that can sign spends from the treasury.
+    /// Can be repeated to set multiple keys. Requires a treasury threshold.
+    #[clap(long = "treasury-key", requires = "treasury_threshold")]
+    treasury_keys: Vec<String>,
+
+    /// The amount of treasury keys that need to sign a spend from the treasury.
+    #[clap(long, requires = "treasury_keys")]
+    treasury_threshold: Option<u64>,
 }
 
 fn main() -> Result<()> {
@@ -85,9 +155,111 @@ fn main() -> Result<()> {
     }
 
     // update the genesis JSON with the calculated app state
+    let mut staking = validator::StakingParams::default();
+    if let Some(percentage) = cli.double_sign_slash_percentage {
+        ensure!(percentage <= 100, "slash percentage can't exceed 100");
+        staking.double_sign_slash_percentage = percentage;
+    }
+    staking.jail_on_double_sign = !cli.no_double_sign_jail;
+    if let Some(window) = cli.signed_blocks_window {
+        staking.signed_blocks_window = window;
+    }
+    if let Some(max_missed_blocks) = cli.max_missed_blocks {
+        staking.max_missed_blocks = max_missed_blocks;
+    }
+    if let Some(jail_blocks) = cli.downtime_jail_blocks {
+        staking.downtime_jail_blocks = jail_blocks;
+    }
+    if let Some(unbonding_blocks) = cli.unbonding_blocks {
+        staking.unbonding_blocks = unbonding_blocks;
+    }
+    if let Some(percentage) = cli.commission_percentage {
+        ensure!(percentage <= 100, "commission percentage can't exceed 100");
+        staking.commission_percentage = percentage;
+    }
+    if let Some(max_validators) = cli.max_validators {
+        ensure!(
+            max_validators > 0,
+            "the max validators should be at least 1"
+        );
+        staking.max_validators = max_validators;
+    }
+    if let Some(min_self_stake) = cli.min_self_stake {
+        staking.min_self_stake = min_self_stake;
+    }
+    if let Some(block_reward) = cli.block_reward {
+        ensure!(
+            block_reward <= validator::MAX_BLOCK_REWARD,
+            "the block reward can't exceed {}",
+            validator::MAX_BLOCK_REWARD
+        );
+        staking.emission.initial_reward = block_reward;
+    }
+    if let Some(interval) = cli.reward_reduction_interval {
+        staking.emission.reduction_interval = interval;
+    }
+    if let Some(percentage) = cli.reward_reduction_percentage {
+        ensure!(
+            percentage <= 100,
+            "reward reduction percentage can't exceed 100"
+        );
+        staking.emission.reduction_percentage = percentage;
+    }
+    if let Some(percentage) = cli.fee_burn_percentage {
+        ensure!(percentage <= 100, "fee burn percentage can't exceed 100");
+        staking.fee_burn_percentage = percentage;
+    }
+    if let Some(percentage) = cli.treasury_percentage {
+        ensure!(percentage <= 100, "treasury percentage can't exceed 100");
+        staking.treasury_percentage = percentage;
+    }
+    ensure!(
+        staking.fee_burn_percentage + staking.treasury_percentage <= 100,
+        "the fee burn and treasury percentages can't exceed 100 combined"
+    );
+    for key in &cli.treasury_keys {
+        base64::decode(key)
+            .ok()
+            .and_then(|bytes| tendermint::PublicKey::from_raw_ed25519(&bytes))
+            .ok_or_else(|| anyhow!("invalid treasury key {key}"))?;
+        ensure!(
+            !staking.treasury_keys.contains(key),
+            "duplicate treasury key {key}"
+        );
+        staking.treasury_keys.push(key.clone());
+    }
+    if let Some(threshold) = cli.treasury_threshold {
+        ensure!(
+            threshold > 0 && threshold <= staking.treasury_keys.len() as u64,
+            "the treasury threshold should be between 1 and the amount of treasury keys"
+        );
+        staking.treasury_threshold = threshold;
+    }
+    ensure!(
+        staking.treasury_keys.is_empty() || staking.treasury_threshold > 0,
+        "the treasury keys need a threshold to authorize spends"
+    );
+    // otherwise the treasury would accrue credits that nobody can spend
+    ensure!(
+        staking.treasury_percentage == 0 || staking.treasury_threshold > 0,
+        "a treasury percentage requires treasury keys and a threshold to spend it"
+    );
+    ensure!(
+        validators
+            .iter()
+            .any(|validator| validator.self_staked() >= staking.min_self_stake),
+        "no genesis validator has the min self stake"
+    );
+    ensure!(
+        staking.max_missed_blocks < staking.signed_blocks_window,
+        "the max missed blocks should be less than the signed blocks window"
+    );
+
     let genesis_state = validator::GenesisState {
+        supply: cli.amount * genesis_records.len() as u64,
         records: genesis_records,
         validators,
+        staking,
     };
     genesis.as_object_mut().unwrap().insert(
         "app_state".to_string(),
@@ -1,15 +1,70 @@
-use anyhow::{anyhow, Result};
-use lib::vm::{self, VerifyingKeyMap};
+use anyhow::{anyhow, ensure, Context, Result};
+use bincode::Options;
+use lib::deployment::{verifying_keys_hash, DeploymentInfo, Edition, ProgramEditions};
+use lib::vm;
 use log::{debug, error};
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
 use std::sync::mpsc::{channel, sync_channel, Receiver, Sender, SyncSender};
 use std::thread;
 
 pub type StoredProgram = (vm::Program, vm::VerifyingKeyMap);
 
+/// A page of programs with their latest edition and its deployment metadata,
+/// plus the id to start the next page from, if there are more programs.
+pub type ProgramPage = (
+    Vec<(vm::ProgramID, Edition, DeploymentInfo)>,
+    Option<vm::ProgramID>,
+);
+
 type Key = vm::ProgramID;
-type Value = StoredProgram;
+type Value = StoredEditions;
+
+/// Every edition deployed for a program id, along with the account allowed to deploy new ones.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct StoredEditions {
+    owner: Option<vm::Address>,
+    editions: Vec<StoredEdition>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct StoredEdition {
+    program: vm::Program,
+    verifying_keys: vm::VerifyingKeyMap,
+    info: DeploymentInfo,
+}
+
+impl StoredEditions {
+    /// Decode a stored value. Stores created before programs had editions hold a single program with its
+    /// verifying keys, which is read as its only edition, without owner so it can't be upgraded.
+    fn decode(program_id: &Key, value: &[u8]) -> Result<Self> {
+        // reject trailing bytes, so a value in one format is never read as the other one
+        let options = || {
+            bincode::DefaultOptions::new()
+                .with_fixint_encoding()
+                .reject_trailing_bytes()
+        };
+        if let Ok(stored) = options().deserialize::<Self>(value) {
+            return Ok(stored);
+        }
+        let (program, verifying_keys) = options()
+            .deserialize::<StoredProgram>(value)
+            .with_context(|| format!("Stored program {program_id} can't be decoded"))?;
+        let info = DeploymentInfo::new(&program, &verifying_keys, None)?;
+        Ok(Self {
+            owner: None,
+            editions: vec![StoredEdition {
+                program,
+                verifying_keys,
+                info,
+            }],
+        })
+    }
+}
 
-/// The program store tracks programs that have been deployed to the OS
+/// The program store tracks programs that have been deployed to the OS.
+/// A program can be deployed more than once by its owner, each deployment registering
+/// a new edition with its own verifying keys.
 #[derive(Clone, Debug)]
 pub struct ProgramStore {
     /// Channel used to send operations to the task that manages the store state.
@@ -18,8 +73,13 @@ pub struct ProgramStore {
 
 #[derive(Debug)]
 enum Command {
-    Add(Key, Box<Value>, SyncSender<Result<()>>),
+    Add(Key, Box<StoredEdition>, SyncSender<Result<Edition>>),
     Get(Key, SyncSender<Result<Option<Value>>>),
+    List {
+        from: Option<Key>,
+        limit: usize,
+        reply_sender: SyncSender<Result<ProgramPage>>,
+    },
     Exists(Key, SyncSender<bool>),
 }
 
@@ -33,22 +93,40 @@ impl ProgramStore {
         thread::spawn(move || {
             while let Ok(command) = command_receiver.recv() {
                 match command {
-                    Command::Add(program_id, program_keys, reply_to) => {
-                        let result = if db_programs
+                    Command::Add(program_id, edition, reply_to) => {
+                        let owner = edition.info.deployer;
+                        let stored = db_programs
                             .get(program_id.to_string().as_bytes())
                             .unwrap_or(None)
-                            .is_some()
-                        {
-                            Err(anyhow!(
-                                "Program {} already exists in the store",
+                            .map(|value| StoredEditions::decode(&program_id, &value))
+                            .transpose();
+
+                        let result = match stored {
+                            Err(e) => Err(e),
+                            // first deployment, the deployer becomes the program owner
+                            Ok(None) => Ok(StoredEditions {
+                                owner,
+                                editions: vec![*edition],
+                            }),
+                            Ok(Some(mut stored))
+                                if stored.owner.is_some() && stored.owner == owner =>
+                            {
+                                stored.editions.push(*edition);
+                                Ok(stored)
+                            }
+                            Ok(Some(_)) => Err(anyhow!(
+                                "Program {} already exists in the store and can only be upgraded by its owner",
                                 &program_id,
-                            ))
-                        } else {
-                            let program_keys = bincode::serialize(&program_keys);
-                            Ok(db_programs
-                                .put(program_id.to_string().as_bytes(), program_keys.unwrap())
-                                .unwrap_or_else(|e| error!("failed to write to db {}", e)))
-                        };
+                            )),
+                        }
+                        .map(|stored| {
+                            let edition = (stored.editions.len() - 1) as Edition;
+                            let stored = bincode::serialize(&stored);
+                            db_programs
+                                .put(program_id.to_string().as_bytes(), stored.unwrap())
+                                .unwrap_or_else(|e| error!("failed to write to db {}", e));
+                            edition
+                        });
 
                         reply_to.send(result).unwrap_or_else(|e| error!("{}", e));
                     }
@@ -56,10 +134,49 @@ impl ProgramStore {
                         let result = db_programs
                             .get(program_id.to_string().as_bytes())
                             .unwrap_or(None)
-                            .map(|value| bincode::deserialize::<Value>(&value).unwrap());
+                            .map(|value| StoredEditions::decode(&program_id, &value))
+                            .transpose();
+
+                        reply_to.send(result).unwrap_or_else(|e| error!("{}", e));
+                    }
+                    Command::List {
+                        from,
+                        limit,
+                        reply_sender,
+                    } => {
+                        let mode = from.as_ref().map_or(rocksdb::IteratorMode::Start, |from| {
+                            rocksdb::IteratorMode::From(
+                                from.to_string().as_bytes(),
+                                rocksdb::Direction::Forward,
+                            )
+                        });
+
+                        let mut iterator = db_programs.iterator(mode).flatten().map(
+                            |(key, value)| -> Result<(Key, Edition, DeploymentInfo)> {
+                                let program_id = Key::from_str(&String::from_utf8_lossy(&key))?;
+                                let mut stored = StoredEditions::decode(&program_id, &value)?;
+                                let info = stored
+                                    .editions
+                                    .pop()
+                                    .ok_or_else(|| anyhow!("Program {program_id} has no editions"))?
+                                    .info;
+                                Ok((program_id, stored.editions.len() as Edition, info))
+                            },
+                        );
+
+                        let mut page = || -> Result<ProgramPage> {
+                            let programs =
+                                iterator.by_ref().take(limit).collect::<Result<Vec<_>>>()?;
+                            let next = iterator
+                                .next()
+                                .transpose()?
+                                .map(|(program_id, _, _)| program_id);
+                            Ok((programs, next))
+                        };
+                        let result = page();
 
-                        reply_to
-                            .send(Ok(result))
+                        reply_sender
+                            .send(result)
                             .unwrap_or_else(|e| error!("{}", e));
                     }
                     Command::Exists(program_id, reply_to) => {
@@ -75,8 +192,68 @@ impl ProgramStore {
         Ok(program_store)
     }
 
-    /// Returns a program
+    /// Returns the latest edition of a program
     pub fn get(&self, program_id: &vm::ProgramID) -> Result<Option<StoredProgram>> {
+        Ok(self
+            .get_stored(program_id)?
+            .and_then(|mut stored| stored.editions.pop())
+            .map(|edition| (edition.program, edition.verifying_keys)))
+    }
+
+    /// Returns a specific edition of a program
+    pub fn get_edition(
+        &self,
+        program_id: &vm::ProgramID,
+        edition: Edition,
+    ) -> Result<Option<StoredProgram>> {
+        Ok(self
+            .get_stored(program_id)?
+            .and_then(|mut stored| {
+                let edition = edition as usize;
+                (edition < stored.editions.len()).then(|| stored.editions.swap_remove(edition))
+            })
+            .map(|edition| (edition.program, edition.verifying_keys)))
+    }
+
+    /// Returns the program owner and the source of each of its deployed editions
+    pub fn editions(&self, program_id: &vm::ProgramID) -> Result<Option<ProgramEditions>> {
+        Ok(self.get_stored(program_id)?.map(|stored| ProgramEditions {
+            owner: stored.owner,
+            editions: stored
+                .editions
+                .into_iter()
+                .map(|edition| edition.program)
+                .collect(),
+        }))
+    }
+
+    /// Returns the deployment metadata of each edition of a program, in deployment order
+    pub fn info(&self, program_id: &vm::ProgramID) -> Result<Option<Vec<DeploymentInfo>>> {
+        Ok(self.get_stored(program_id)?.map(|stored| {
+            stored
+                .editions
+                .into_iter()
+                .map(|edition| edition.info)
+                .collect()
+        }))
+    }
+
+    /// Returns up to `limit` programs ordered by id, starting from the given one, along with their
+    /// latest edition and its deployment metadata. If there are more programs, the id of the next
+    /// one is returned to be used as the start of the following page.
+    pub fn list(&self, from: Option<vm::ProgramID>, limit: usize) -> Result<ProgramPage> {
+        let (reply_sender, reply_receiver) = sync_channel(0);
+
+        self.command_sender.send(Command::List {
+            from,
+            limit,
+            reply_sender,
+        })?;
+
+        reply_receiver.recv()?
+    }
+
+    fn get_stored(&self, program_id: &vm::ProgramID) -> Result<Option<Value>> {
         let (reply_sender, reply_receiver) = sync_channel(0);
 
         self.command_sender
@@ -85,18 +262,25 @@ impl ProgramStore {
         reply_receiver.recv()?
     }
 
-    /// Adds a program to the store
+    /// Adds a program to the store along with its deployment metadata. If the program id already
+    /// exists, the deployer needs to match the owner of the first deployment and the program is stored
+    /// as a new edition. Programs without owner can't be upgraded. Returns the stored edition number.
     pub fn add(
         &self,
         program_id: &vm::ProgramID,
         program: &vm::Program,
         verifying_keys: &vm::VerifyingKeyMap,
-    ) -> Result<()> {
+        info: DeploymentInfo,
+    ) -> Result<Edition> {
         let (reply_sender, reply_receiver) = sync_channel(0);
 
         self.command_sender.send(Command::Add(
             program_id.to_owned(),
-            Box::new((program.clone(), verifying_keys.clone())),
+            Box::new(StoredEdition {
+                program: program.clone(),
+                verifying_keys: verifying_keys.clone(),
+                info,
+            }),
             reply_sender,
         ))?;
 
@@ -114,25 +298,31 @@ impl ProgramStore {
         reply_receiver.recv().unwrap_or(false)
     }
 
+    /// Store the built-in credits program with the verifying keys embedded in the binary. If it was
+    /// already stored, make sure its keys match the pinned hash, otherwise the node would verify
+    /// credits transitions with keys different than the rest of the network.
     fn load_credits(&self) -> Result<()> {
-        let (credits_program, keys) = lib::load_credits();
+        let (credits_program, verifying_keys) = lib::credits_verifying_keys()?;
 
-        if self.exists(credits_program.id()) {
+        if let Some((_, stored_keys)) = self.get(credits_program.id())? {
             debug!("Credits program already exists in program store");
+            let stored_hash = verifying_keys_hash(&stored_keys)?;
+            let expected_hash = verifying_keys_hash(&verifying_keys)?;
+            ensure!(
+                stored_hash == expected_hash,
+                "Stored credits program is invalid: its verifying keys hash {stored_hash} doesn't match the expected {expected_hash}"
+            );
             Ok(())
         } else {
             debug!("Loading credits.aleo as part of Program Store initialization");
 
-            let key_map = keys
-                .map
-                .into_iter()
-                .map(|(i, (_, verifying_key))| (i, verifying_key))
-                .collect();
-
+            // the credits program is built-in, it has no owner so it can't be upgraded
+            let info = DeploymentInfo::new(&credits_program, &verifying_keys, None)?;
             self.add(
                 credits_program.id(),
                 &credits_program,
-                &VerifyingKeyMap { map: key_map },
+                &verifying_keys,
+                info,
             )?;
 
             Ok(())
@@ -175,13 +365,77 @@ mod tests {
 
         assert!(get_program.unwrap().is_none());
 
-        let storage_attempt = store_program(&store, "/aleo/hello.aleo");
+        let storage_attempt = store_program(&store, "/aleo/hello.aleo", None);
         assert!(storage_attempt.is_ok() && store.exists(storage_attempt.unwrap().id()));
 
         // FIXME patching rocksdb weird behavior
         std::mem::forget(store);
     }
 
+    #[test]
+    fn add_program_editions() {
+        let store = ProgramStore::new(&db_path("editions")).unwrap();
+        let owner = account_address();
+        let other = account_address();
+
+        let program = store_program(&store, "/aleo/token.aleo", Some(owner)).unwrap();
+        let editions = store.editions(program.id()).unwrap().unwrap();
+        assert_eq!(Some(owner), editions.owner);
+        assert_eq!(0, editions.latest());
+
+        // other accounts can't deploy new editions
+        let error = store_program(&store, "/aleo/token.aleo", Some(other)).unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("can only be upgraded by its owner"));
+
+        // the owner can
+        store_program(&store, "/aleo/token.aleo", Some(owner)).unwrap();
+        let editions = store.editions(program.id()).unwrap().unwrap();
+        assert_eq!(2, editions.editions.len());
+        assert_eq!(1, editions.latest());
+        assert!(store.get_edition(program.id(), 0).unwrap().is_some());
+        assert!(store.get_edition(program.id(), 1).unwrap().is_some());
+        assert!(store.get_edition(program.id(), 2).unwrap().is_none());
+
+        // programs without owner can't be upgraded at all
+        let program = store_program(&store, "/aleo/hello.aleo", None).unwrap();
+        assert!(store_program(&store, "/aleo/hello.aleo", None).is_err());
+        assert_eq!(0, store.editions(program.id()).unwrap().unwrap().latest());
+
+        // FIXME patching rocksdb weird behavior
+        std::mem::forget(store);
+    }
+
+    #[test]
+    fn list_programs() {
+        let store = ProgramStore::new(&db_path("list")).unwrap();
+        let owner = account_address();
+
+        let hello = store_program(&store, "/aleo/hello.aleo", Some(owner)).unwrap();
+        let token = store_program(&store, "/aleo/token.aleo", Some(owner)).unwrap();
+
+        let info = store.info(hello.id()).unwrap().unwrap();
+        assert_eq!(1, info.len());
+        assert_eq!(Some(owner), info[0].deployer);
+        assert_eq!(hello.functions().len(), info[0].functions.len());
+        assert_eq!(hello.functions().len(), info[0].verifying_key_hashes.len());
+
+        // credits.aleo is always loaded, and programs are listed ordered by id
+        let (programs, next) = store.list(None, 2).unwrap();
+        let ids: Vec<_> = programs.iter().map(|(id, _, _)| id.to_string()).collect();
+        assert_eq!(vec!["credits.aleo", "hello.aleo"], ids);
+        assert_eq!(Some(token.id()), next.as_ref());
+
+        let (programs, next) = store.list(next, 2).unwrap();
+        assert_eq!(1, programs.len());
+        assert_eq!(token.id(), &programs[0].0);
+        assert!(next.is_none());
+
+        // FIXME patching rocksdb weird behavior
+        std::mem::forget(store);
+    }
+
     #[test]
     fn credits_loaded() {
         let program = Program::credits().expect("Problem loading Credits");
@@ -196,7 +450,82 @@ mod tests {
         assert!(store.exists(program.id()));
     }
 
-    fn store_program(program_store: &ProgramStore, path: &str) -> Result<vm::Program> {
+    #[test]
+    fn credits_keys_mismatch() {
+        let (credits, _) = lib::credits_verifying_keys().unwrap();
+        let (hello, hello_build) =
+            vm::build_program(include_str!("../../aleo/hello.aleo")).unwrap();
+        let verifying_keys = lib::verifying_keys(&hello_build);
+
+        // store credits with keys that don't match the pinned ones
+        {
+            let store = rocksdb::DB::open_default(db_path("credits_mismatch.deployed.db")).unwrap();
+            let stored = StoredEditions {
+                owner: None,
+                editions: vec![StoredEdition {
+                    info: DeploymentInfo::new(&hello, &verifying_keys, None).unwrap(),
+                    program: credits.clone(),
+                    verifying_keys,
+                }],
+            };
+            store
+                .put(
+                    credits.id().to_string().as_bytes(),
+                    bincode::serialize(&stored).unwrap(),
+                )
+                .unwrap();
+        }
+
+        let error = ProgramStore::new(&db_path("credits_mismatch")).unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("Stored credits program is invalid"));
+    }
+
+    #[test]
+    fn read_legacy_programs() {
+        let (hello, hello_build) =
+            vm::build_program(include_str!("../../aleo/hello.aleo")).unwrap();
+        let verifying_keys = lib::verifying_keys(&hello_build);
+
+        // store a program the way it was stored before programs had editions
+        {
+            let store = rocksdb::DB::open_default(db_path("legacy.deployed.db")).unwrap();
+            let legacy: StoredProgram = (hello.clone(), verifying_keys.clone());
+            store
+                .put(
+                    hello.id().to_string().as_bytes(),
+                    bincode::serialize(&legacy).unwrap(),
+                )
+                .unwrap();
+            store
+                .put("broken.aleo".as_bytes(), b"not a program".to_vec())
+                .unwrap();
+        }
+
+        // it's read as its only edition, which can't be upgraded
+        let store = ProgramStore::new(&db_path("legacy")).unwrap();
+        let editions = store.editions(hello.id()).unwrap().unwrap();
+        assert_eq!(None, editions.owner);
+        assert_eq!(0, editions.latest());
+        assert!(store.get_edition(hello.id(), 0).unwrap().is_some());
+        assert!(store_program(&store, "/aleo/hello.aleo", None).is_err());
+
+        // values that can't be decoded fail instead of panicking
+        let broken = vm::ProgramID::from_str("broken.aleo").unwrap();
+        let error = store.get(&broken).unwrap_err();
+        assert!(error.to_string().contains("can't be decoded"));
+        assert!(store.list(None, 10).is_err());
+
+        // FIXME patching rocksdb weird behavior
+        std::mem::forget(store);
+    }
+
+    fn store_program(
+        program_store: &ProgramStore,
+        path: &str,
+        owner: Option<vm::Address>,
+    ) -> Result<vm::Program> {
         let program_path = format!("{}{}", env!("CARGO_MANIFEST_DIR"), path);
 
         let program_string = fs::read_to_string(program_path).unwrap();
@@ -204,14 +533,16 @@ mod tests {
         // generate program keys (proving and verifying) and keep the verifying one for the store
         let (program, program_build) = vm::build_program(&program_string)?;
 
-        let keys = program_build
-            .map
-            .into_iter()
-            .map(|(i, (_, verifying_key))| (i, verifying_key))
-            .collect();
-
-        program_store.add(program.id(), &program, &VerifyingKeyMap { map: keys })?;
+        let verifying_keys = lib::verifying_keys(&program_build);
+        let info = DeploymentInfo::new(&program, &verifying_keys, owner)?;
+        program_store.add(program.id(), &program, &verifying_keys, info)?;
 
         Ok(program)
     }
+
+    fn account_address() -> vm::Address {
+        let private_key = vm::PrivateKey::new(&mut rand::thread_rng()).unwrap();
+        let view_key = vm::ViewKey::try_from(&private_key).unwrap();
+        vm::Address::try_from(&view_key).unwrap()
+    }
 }
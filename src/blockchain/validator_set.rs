@@ -1,19 +1,21 @@
 use std::{
-    collections::{HashMap, HashSet},
+    collections::{BTreeMap, HashMap, HashSet, VecDeque},
     path::{Path, PathBuf},
 };
 
 use lib::vm;
-use log::{debug, error, warn};
+use log::{debug, error, info, warn};
 
-use anyhow::{anyhow, Result};
-use lib::validator::{Address, Stake, Validator, VotingPower};
+use anyhow::{anyhow, bail, ensure, Result};
+use lib::validator::{
+    Address, ChangeKind, Stake, StakerRequest, StakingParams, Supply, Treasury, TreasurySpend,
+    Validator, ValidatorChange, VotingPower,
+};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 
 type Fee = u64;
 
-/// There's a baseline for the credits distributed among validators, in addition to fees.
-/// For now it's constant, but it could be made to decrease based on height to control inflation.
-const BASELINE_BLOCK_REWARD: Fee = 100;
 /// The portion of the total block rewards that is given to the block proposer. The rest is distributed
 /// among voters weighted by their voting power.
 const PROPOSER_REWARD_PERCENTAGE: u64 = 50;
@@ -30,8 +32,10 @@ pub struct ValidatorSet {
     /// The currently known validator set, including the terndermint pub key/address to aleo account mapping
     /// and their last known voting power.
     validators: HashMap<Address, Validator>,
-    /// The fees collected for the current block.
+    /// The fees collected for the current block, including its baseline reward.
     fees: Fee,
+    /// The baseline credits minted as rewards in the current block, as set by the emission schedule.
+    baseline: Fee,
     /// The proposer of the current block.
     current_proposer: Option<Address>,
     /// The previous round block votes, to be considered to distribute this block's rewards.
@@ -40,43 +44,179 @@ pub struct ValidatorSet {
     current_height: u64,
     /// The list of validators that had voting power changes during the current block, including added or removed ones.
     updated_validators: HashSet<Address>,
+    /// The rules to punish misbehaving validators, set at genesis.
+    params: StakingParams,
+    /// The credits unstaked from validators that are waiting for the unbonding period to be over.
+    unbonding: Vec<Unbonding>,
+    /// The gates withdrawn by stakers during the current block, to be paid back when it's committed.
+    withdrawals: Vec<(vm::Address, u64)>,
+    /// For each validator, whether it missed each of the most recent blocks, up to the signed blocks window.
+    missed_blocks: HashMap<Address, VecDeque<bool>>,
+    /// The current address of the validators whose key was rotated, by their previous addresses. The
+    /// staked_credits records of those validators, and the votes until the rotation takes effect, still
+    /// point to the previous keys.
+    rotated_keys: HashMap<Address, Address>,
+    /// The keys rotated out during the current block, to be removed from the consensus.
+    replaced_keys: Vec<tendermint::PublicKey>,
+    /// The validators that take part in the consensus, as last informed to tendermint. The rest of the
+    /// known validators are candidates, which join it when they are among the ones with the most voting power.
+    active: HashSet<Address>,
+    /// The rewards accrued by each aleo account, which it can claim at any time.
+    rewards: Vec<(vm::Address, u64)>,
+    /// The rewards claimed during the current block, to be paid when it's committed.
+    claims: Vec<(vm::Address, u64)>,
+    /// The credits created and destroyed since genesis.
+    supply: Supply,
+    /// The credits accrued by the treasury from the block fees.
+    treasury: Treasury,
+    /// The credits spent from the treasury during the current block, to be paid when it's committed.
+    treasury_spends: Vec<(vm::Address, u64)>,
+}
+
+/// Credits unstaked from a validator, which can be withdrawn by the staker once they mature.
+/// Until then, they are still slashed if the validator misbehaves.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+struct Unbonding {
+    validator: Address,
+    aleo_address: vm::Address,
+    gates: u64,
+    /// The height from which the credits can be withdrawn.
+    mature_at: u64,
+}
+
+impl Unbonding {
+    /// Return whether these credits were unstaked by the account making the request from its validator.
+    fn is_from(&self, request: &StakerRequest) -> bool {
+        self.validator == request.validator_address() && self.aleo_address == request.aleo_address()
+    }
+}
+
+/// The validator set state persisted to disk.
+#[derive(Default, Deserialize, Serialize)]
+struct ValidatorSetFile {
+    params: StakingParams,
+    validators: Vec<Validator>,
+    #[serde(default)]
+    missed_blocks: Vec<(Address, VecDeque<bool>)>,
+    #[serde(default)]
+    unbonding: Vec<Unbonding>,
+    #[serde(default)]
+    rotated_keys: Vec<(Address, Address)>,
+    /// Files written before the active validators were tracked don't include them.
+    #[serde(default)]
+    active: Option<Vec<Address>>,
+    #[serde(default)]
+    rewards: Vec<(vm::Address, u64)>,
+    #[serde(default)]
+    supply: Supply,
+    #[serde(default)]
+    treasury: Treasury,
 }
 
 impl ValidatorSet {
     /// Create a new validator set. If a previous validators file is found, populate the set with its contents,
     /// otherwise start with an empty one.
     pub fn load_or_create(path: &Path) -> Self {
-        let validators = if let Ok(json) = std::fs::read_to_string(path) {
-            serde_json::from_str::<Vec<Validator>>(&json)
-                .expect("validators file content is invalid")
-                .into_iter()
-                .map(|validator| {
-                    debug!("loading validator {}", validator);
-                    (validator.address(), validator)
-                })
-                .collect()
-        } else {
-            HashMap::new()
-        };
-
-        Self {
+        let mut set = Self {
             path: path.into(),
-            validators,
+            validators: HashMap::new(),
             current_height: 0,
             fees: 0,
+            baseline: 0,
             current_proposer: None,
             current_votes: HashMap::new(),
             updated_validators: HashSet::new(),
+            params: StakingParams::default(),
+            unbonding: Vec::new(),
+            withdrawals: Vec::new(),
+            missed_blocks: HashMap::new(),
+            rotated_keys: HashMap::new(),
+            replaced_keys: Vec::new(),
+            active: HashSet::new(),
+            rewards: Vec::new(),
+            claims: Vec::new(),
+            supply: Supply::default(),
+            treasury: Treasury::default(),
+            treasury_spends: Vec::new(),
+        };
+
+        if let Ok(json) = std::fs::read_to_string(path) {
+            // files written before staking params were introduced only contain the validator list
+            let file = serde_json::from_str::<ValidatorSetFile>(&json).unwrap_or_else(|_| {
+                ValidatorSetFile {
+                    validators: serde_json::from_str(&json)
+                        .expect("validators file content is invalid"),
+                    ..Default::default()
+                }
+            });
+            for validator in &file.validators {
+                debug!("loading validator {}", validator);
+            }
+            set.params = file.params;
+            set.replace(file.validators);
+            set.missed_blocks = file.missed_blocks.into_iter().collect();
+            set.unbonding = file.unbonding;
+            set.rotated_keys = file.rotated_keys.into_iter().collect();
+            set.rewards = file.rewards;
+            set.supply = file.supply;
+            set.treasury = file.treasury;
+            set.active = match file.active {
+                Some(active) => active.into_iter().collect(),
+                // until then, every validator with voting power took part in the consensus
+                None => set
+                    .validators
+                    .values()
+                    .filter(|validator| validator.consensus_power() > 0)
+                    .map(Validator::address)
+                    .collect(),
+            };
         }
+        set
     }
 
     pub fn replace(&mut self, validators: Vec<Validator>) {
         self.validators = validators
             .into_iter()
-            .map(|validator| (validator.address(), validator))
+            .map(|mut validator| {
+                // validators that don't track their staked gates (e.g. persisted before slashing was
+                // introduced) are backed by their full voting power
+                if validator.staked == 0 {
+                    validator.staked = validator.voting_power;
+                }
+                (validator.address(), validator)
+            })
             .collect()
     }
 
+    /// Choose the validators that take part in the consensus, i.e. the ones with the most voting power
+    /// up to the configured maximum, and return them. Used at genesis, since tendermint starts with the
+    /// validators returned by init_chain.
+    pub fn select_validators(&mut self) -> Vec<Validator> {
+        self.active = self.top_validators();
+        let mut validators: Vec<Validator> = self
+            .active
+            .iter()
+            .map(|address| self.validators[address].clone())
+            .collect();
+        validators.sort_by_key(Validator::address);
+        validators
+    }
+
+    /// Set the rules to punish misbehaving validators, as defined in the genesis state.
+    pub fn set_params(&mut self, params: StakingParams) {
+        self.params = params;
+    }
+
+    /// Set the gates of the genesis records, from which the credits supply is tracked.
+    pub fn set_genesis_supply(&mut self, gates: u64) {
+        self.supply.genesis = gates;
+    }
+
+    /// Return the credits created and destroyed since genesis.
+    pub fn supply(&self) -> Supply {
+        self.supply.clone()
+    }
+
     /// Updates state based on previous commit votes, to know how awards should be assigned.
     pub fn begin_block(
         &mut self,
@@ -84,7 +224,10 @@ impl ValidatorSet {
         votes: HashMap<Address, VotingPower>,
         height: u64,
     ) {
-        if !self.validators.contains_key(proposer) {
+        if !self
+            .validators
+            .contains_key(&self.current_address(proposer))
+        {
             error!(
                 "received unknown address as proposer {}",
                 hex::encode_upper(proposer)
@@ -92,7 +235,7 @@ impl ValidatorSet {
         }
 
         for voter in votes.keys() {
-            if !self.validators.contains_key(voter) {
+            if !self.validators.contains_key(&self.current_address(voter)) {
                 error!(
                     "received unknown address as voter {}",
                     hex::encode_upper(voter)
@@ -101,50 +244,426 @@ impl ValidatorSet {
         }
 
         self.updated_validators = HashSet::new();
+        self.withdrawals = Vec::new();
+        self.claims = Vec::new();
+        self.treasury_spends = Vec::new();
+        self.replaced_keys = Vec::new();
         self.current_height = height;
-        self.current_proposer = Some(proposer.to_vec());
+        self.current_proposer = Some(self.current_address(proposer));
         // note that we rely on voting power for a given round as informed by tendermint as opposed to
         // using the one tracked in self.validators. This is because the voting power on the informed round
         // may not be the same as the last known one (e.g. there could be staking changes already applied
         // to self.validators that will take some rounds before affecting the consensus voting).
-        self.current_votes = votes;
-        self.fees = BASELINE_BLOCK_REWARD;
+        // For the same reason, votes can come from keys that were rotated since.
+        self.current_votes = votes
+            .into_iter()
+            .map(|(address, power)| (self.current_address(&address), power))
+            .collect();
+        self.baseline = self.params.emission.block_reward(height);
+        self.fees = self.baseline;
+    }
+
+    /// Return the height of the block currently being processed.
+    pub fn current_height(&self) -> u64 {
+        self.current_height
     }
 
     /// Return whether is valid to apply the given validator update, e.g.
     /// there's enough voting power to unstake and the tendermint and aleo addresses
-    /// the known mappings. This takes into account pending updates if any, so it's safe
+    /// the known mappings, or that the staker holds the key of a new validator. This takes into account pending updates if any, so it's safe
     /// to use both during lightweight mempool checks (check_tx) and transaction delivery (deliver_tx).
     pub fn validate(&self, update: &Stake) -> Result<()> {
+        let update = &self.rekey_stake(update.clone());
         if let Some(validator) = self.validators.get(&update.validator_address()) {
             // this is an already known validator, try to apply the staking update and see if it succeeds
             validator.clone().apply(update)?;
         } else {
-            // this is a new validator
+            // this is a new validator, the staker needs to prove it holds its key
             Validator::from_stake(update)?;
+            update.verify_key_signature()?;
         };
         Ok(())
     }
 
-    /// Add or update the given validator and its voting power.
+    /// Add or update the given validator and its voting power. If credits are unstaked, they
+    /// can be withdrawn by the staker once the unbonding period is over.
     /// Assumes this update has been validated previously with is_valid_update.
     pub fn apply(&mut self, update: Stake) {
+        let update = self.rekey_stake(update);
         // mark as updated so its included in the pending updates result
         self.updated_validators.insert(update.validator_address());
 
         // note that this could leave a validator with zero voting power, which will instruct
         // tendermint to remove it, but we still need to keep it around since we can receive
         // votes from that validator on subsequent rounds.
+        if let Some(validator) = self.validators.get_mut(&update.validator_address()) {
+            let payout = validator
+                .apply(&update)
+                .expect("attempted to apply an invalid update");
+            if update.gates_delta() < 0 {
+                // the part of the unstaked credits that was slashed is lost
+                self.supply.burned += update.gates_delta().unsigned_abs() - payout;
+            }
+            if payout > 0 {
+                self.unbonding.push(Unbonding {
+                    validator: update.validator_address(),
+                    aleo_address: update.aleo_address(),
+                    gates: payout,
+                    mature_at: self.current_height + self.params.unbonding_blocks,
+                });
+            }
+        } else {
+            let validator =
+                Validator::from_stake(&update).expect("attempted to apply an invalid update");
+            self.validators.insert(validator.address(), validator);
+        }
+    }
+
+    /// Punish a validator for double signing, as reported by tendermint evidence: cut its voting power by
+    /// the configured percentage, jailing it if configured to, and exclude it from the current block rewards.
+    /// Since the staked gates are kept, the validator staked_credits records are worth less when unstaked.
+    /// The credits being unbonded from the validator are cut by the same percentage.
+    /// Return the slashed voting power, or None if the validator is unknown.
+    /// Assumes it's called after begin_block, since the change is reported in the pending updates.
+    pub fn slash(&mut self, address: &Address) -> Option<VotingPower> {
+        let address = &self.current_address(address);
+        let percentage = self.params.double_sign_slash_percentage.min(100);
+        for unbonding in self
+            .unbonding
+            .iter_mut()
+            .filter(|unbonding| &unbonding.validator == address)
+        {
+            let slashed = (unbonding.gates as u128 * percentage as u128 / 100) as u64;
+            unbonding.gates -= slashed;
+            self.supply.burned += slashed;
+        }
+
+        let validator = self.validators.get_mut(address)?;
+        let slashed = validator.slash(percentage);
+        if self.params.jail_on_double_sign {
+            // validators that double sign can't return to the consensus
+            validator.jail(u64::MAX);
+        }
+        warn!(
+            "slashed {slashed} voting power from {validator} for double signing, jailed: {}",
+            validator.jailed
+        );
+
+        self.updated_validators.insert(address.clone());
+        self.current_votes.remove(address);
+        Some(slashed)
+    }
+
+    /// Record which of the validators of the previous block signed it and which didn't, jailing the ones
+    /// that missed more than the allowed amount of blocks within the signed blocks window.
+    /// Return the addresses of the jailed validators.
+    /// Assumes it's called after begin_block, since the changes are reported in the pending updates.
+    pub fn track_missed_blocks(&mut self, signed: &[Address], missed: &[Address]) -> Vec<Address> {
+        let window = self.params.signed_blocks_window as usize;
+        let signatures: Vec<(Address, bool)> = signed
+            .iter()
+            .map(|address| (self.current_address(address), false))
+            .chain(
+                missed
+                    .iter()
+                    .map(|address| (self.current_address(address), true)),
+            )
+            .collect();
+
+        let mut jailed = Vec::new();
+        for (address, missed_block) in &signatures {
+            let validator = match self.validators.get_mut(address) {
+                Some(validator) if !validator.jailed => validator,
+                _ => continue,
+            };
+
+            let history = self.missed_blocks.entry(address.clone()).or_default();
+            history.push_back(*missed_block);
+            while history.len() > window {
+                history.pop_front();
+            }
+
+            let missed_count = history.iter().filter(|missed| **missed).count() as u64;
+            if missed_count > self.params.max_missed_blocks {
+                validator.jail(self.current_height + self.params.downtime_jail_blocks);
+                warn!(
+                    "jailed {validator} for missing {missed_count} of the last {} blocks",
+                    history.len()
+                );
+
+                // start over when it returns
+                self.missed_blocks.remove(address);
+                self.updated_validators.insert(address.clone());
+                self.current_votes.remove(address);
+                jailed.push(address.clone());
+            }
+        }
+        jailed
+    }
+
+    /// Return whether it's valid to unjail a validator at the current height, e.g. it's jailed
+    /// for long enough and the request comes from its aleo account.
+    pub fn validate_unjail(&self, request: &StakerRequest) -> Result<()> {
+        let request = &self.rekey_request(request.clone());
+        let validator = self
+            .validators
+            .get(&request.validator_address())
+            .ok_or_else(|| anyhow!("validator {request} not found"))?;
+        validator.clone().unjail(request, self.current_height)
+    }
+
+    /// Return the given validator to the consensus with its current voting power.
+    /// Assumes this request has been validated previously with validate_unjail.
+    pub fn unjail(&mut self, request: StakerRequest) {
+        let request = self.rekey_request(request);
+        self.updated_validators.insert(request.validator_address());
         self.validators
-            .entry(update.validator_address())
-            .and_modify(|validator| {
-                validator
-                    .apply(&update)
-                    .expect("attempted to apply an invalid update")
+            .get_mut(&request.validator_address())
+            .expect("attempted to unjail an unknown validator")
+            .unjail(&request, self.current_height)
+            .expect("attempted to apply an invalid unjail");
+    }
+
+    /// Return whether the staker has matured unbonding credits to withdraw from the validator.
+    pub fn validate_withdraw(&self, request: &StakerRequest) -> Result<()> {
+        let request = &self.rekey_request(request.clone());
+        let next_maturity = self
+            .unbonding
+            .iter()
+            .filter(|unbonding| unbonding.is_from(request))
+            .map(|unbonding| unbonding.mature_at)
+            .min();
+
+        match next_maturity {
+            Some(height) => ensure!(
+                height <= self.current_height,
+                "the credits unbonding from {request} can't be withdrawn until block {height}"
+            ),
+            None => bail!("there are no credits unbonding from {request}"),
+        }
+        Ok(())
+    }
+
+    /// Take the matured unbonding credits of the staker from the given validator, to be paid back
+    /// in a single record when the block is committed.
+    /// Assumes this request has been validated previously with validate_withdraw.
+    pub fn withdraw(&mut self, request: StakerRequest) {
+        let request = self.rekey_request(request);
+        let height = self.current_height;
+        let (matured, pending): (Vec<Unbonding>, Vec<Unbonding>) = self
+            .unbonding
+            .drain(..)
+            .partition(|unbonding| unbonding.is_from(&request) && unbonding.mature_at <= height);
+        self.unbonding = pending;
+
+        let gates = matured.iter().map(|unbonding| unbonding.gates).sum();
+        self.withdrawals.push((request.aleo_address(), gates));
+    }
+
+    /// Return whether it's valid to apply the given change to a validator, i.e. it's requested by its
+    /// operator and signed by its key. Keys can only be rotated to keys that aren't known yet, once per
+    /// block. Jailed validators and candidates can rotate their keys too, e.g. after being jailed because
+    /// of a leaked key: their previous key is not part of the consensus, so it doesn't need to be replaced.
+    pub fn validate_change(&self, change: &ValidatorChange) -> Result<()> {
+        let address = self.current_address(&change.request().validator_address());
+        let validator = self
+            .validators
+            .get(&address)
+            .ok_or_else(|| anyhow!("validator {} not found", change.request()))?;
+
+        if let ChangeKind::RotateKey = change.kind() {
+            ensure!(
+                !self.updated_validators.contains(&address),
+                "the key of validator {validator} can only be rotated if it wasn't updated in the current block"
+            );
+        }
+        validator.authorize(change)?;
+
+        if let ChangeKind::RotateKey = change.kind() {
+            let mut rotated = validator.clone();
+            rotated.pub_key = change.new_key(&validator.pub_key)?;
+            let new_address = rotated.address();
+            ensure!(
+                !self.validators.contains_key(&new_address)
+                    && !self.rotated_keys.contains_key(&new_address),
+                "the key {} is already used by a validator",
+                hex::encode_upper(new_address)
+            );
+        }
+        Ok(())
+    }
+
+    /// Apply the change requested by the operator of a validator. When its key is rotated, the
+    /// previous key is removed from the consensus, and the new one added with the same voting power.
+    /// Assumes this change has been validated previously with validate_change.
+    pub fn change(&mut self, change: ValidatorChange) {
+        let address = self.current_address(&change.request().validator_address());
+        let mut validator = self
+            .validators
+            .remove(&address)
+            .expect("attempted to change an unknown validator");
+
+        match change.kind() {
+            ChangeKind::RewardAddress(reward_address) => {
+                info!("sending the rewards of {validator} to {reward_address}");
+                validator.reward_address = Some(*reward_address);
+                self.validators.insert(address, validator);
+            }
+            ChangeKind::RotateKey => {
+                let new_key = change
+                    .new_key(&validator.pub_key)
+                    .expect("attempted to apply an invalid key rotation");
+                // the new key joins the consensus with the pending updates
+                if self.active.remove(&address) {
+                    self.replaced_keys.push(validator.pub_key);
+                }
+                validator.pub_key = new_key;
+                let new_address = validator.address();
+                info!(
+                    "rotated the key of validator {} to {validator}",
+                    hex::encode_upper(&address)
+                );
+
+                // keep track of the previous addresses of the validator, which can still be referenced
+                for current in self.rotated_keys.values_mut() {
+                    if *current == address {
+                        *current = new_address.clone();
+                    }
+                }
+                self.rotated_keys
+                    .insert(address.clone(), new_address.clone());
+                for unbonding in self
+                    .unbonding
+                    .iter_mut()
+                    .filter(|unbonding| unbonding.validator == address)
+                {
+                    unbonding.validator = new_address.clone();
+                }
+                if let Some(history) = self.missed_blocks.remove(&address) {
+                    self.missed_blocks.insert(new_address.clone(), history);
+                }
+                if let Some(power) = self.current_votes.remove(&address) {
+                    self.current_votes.insert(new_address.clone(), power);
+                }
+                if self.current_proposer.as_ref() == Some(&address) {
+                    self.current_proposer = Some(new_address.clone());
+                }
+
+                self.updated_validators.insert(new_address.clone());
+                self.validators.insert(new_address, validator);
+            }
+        }
+    }
+
+    /// Return the keys rotated out during the current block, which need to be removed from the consensus.
+    pub fn replaced_keys(&self) -> &[tendermint::PublicKey] {
+        &self.replaced_keys
+    }
+
+    /// Return the voting power changes to inform to tendermint at the end of the current block. The keys
+    /// replaced by rotations and the validators that are no longer among the ones with the most voting
+    /// power are removed from the consensus (with zero voting power), while the validators that join it
+    /// or were updated while being part of it are set their current voting power.
+    pub fn consensus_updates(&self) -> Vec<(tendermint::PublicKey, VotingPower)> {
+        let active = self.top_validators();
+        let mut updates: Vec<(Address, tendermint::PublicKey, VotingPower)> = self
+            .active
+            .difference(&active)
+            .map(|address| (address.clone(), self.validators[address].pub_key, 0))
+            .collect();
+        for validator in self.pending_updates() {
+            if active.contains(&validator.address()) {
+                updates.push((
+                    validator.address(),
+                    validator.pub_key,
+                    validator.consensus_power(),
+                ));
+            }
+        }
+        for address in active
+            .difference(&self.active)
+            .filter(|address| !self.updated_validators.contains(*address))
+        {
+            let validator = &self.validators[address];
+            updates.push((
+                address.clone(),
+                validator.pub_key,
+                validator.consensus_power(),
+            ));
+        }
+        updates.sort_by(|(address1, ..), (address2, ..)| address1.cmp(address2));
+
+        self.replaced_keys()
+            .iter()
+            .map(|pub_key| (*pub_key, 0))
+            .chain(
+                updates
+                    .into_iter()
+                    .map(|(_address, pub_key, power)| (pub_key, power)),
+            )
+            .collect()
+    }
+
+    /// Return the validators that can take part in the consensus with the most voting power, up to the
+    /// configured maximum. Jailed validators and the ones whose operator didn't stake the minimum are
+    /// left out. Ties are broken by address, so all nodes choose the same validators.
+    fn top_validators(&self) -> HashSet<Address> {
+        let mut candidates: Vec<&Validator> = self
+            .validators
+            .values()
+            .filter(|validator| {
+                validator.consensus_power() > 0
+                    && validator.self_staked() >= self.params.min_self_stake
             })
-            .or_insert_with(|| {
-                Validator::from_stake(&update).expect("attempted to apply an invalid update")
-            });
+            .collect();
+        candidates.sort_by(|validator1, validator2| {
+            validator2
+                .consensus_power()
+                .cmp(&validator1.consensus_power())
+                .then_with(|| validator1.address().cmp(&validator2.address()))
+        });
+        candidates
+            .into_iter()
+            .take(self.params.max_validators as usize)
+            .map(Validator::address)
+            .collect()
+    }
+
+    /// Return whether the given validator is jailed, i.e. it has no voting power in the consensus.
+    pub fn is_jailed(&self, address: &Address) -> bool {
+        self.validators
+            .get(&self.current_address(address))
+            .map_or(false, |validator| validator.jailed)
+    }
+
+    /// Return the current address of a validator, which differs from the given one if its key was rotated.
+    fn current_address(&self, address: &Address) -> Address {
+        self.rotated_keys.get(address).unwrap_or(address).clone()
+    }
+
+    /// If the given key was rotated, return the current key of its validator.
+    fn rotated_key(&self, address: &Address) -> Option<tendermint::PublicKey> {
+        self.rotated_keys
+            .get(address)
+            .and_then(|current| self.validators.get(current))
+            .map(|validator| validator.pub_key)
+    }
+
+    /// Point a stake on a key that was rotated afterwards to the current key of its validator.
+    fn rekey_stake(&self, stake: Stake) -> Stake {
+        match self.rotated_key(&stake.validator_address()) {
+            Some(pub_key) => stake.with_pub_key(pub_key),
+            None => stake,
+        }
+    }
+
+    /// Point a request made with a staked_credits record of a key that was rotated afterwards to
+    /// the current key of its validator.
+    fn rekey_request(&self, request: StakerRequest) -> StakerRequest {
+        match self.rotated_key(&request.validator_address()) {
+            Some(pub_key) => request.with_pub_key(pub_key),
+            None => request,
+        }
     }
 
     /// Add the given amount to the current block collected fees.
@@ -167,30 +686,38 @@ impl ValidatorSet {
             })
     }
 
-    /// Distributes the sum of the block fees plus some baseline block credits
-    /// according to some rule, e.g. 50% for the proposer and 50% for validators
+    /// Distributes the sum of the block fees, except the parts that are burned or accrue to the treasury,
+    /// plus some baseline block credits according to some rule, e.g. 50% for the proposer and 50% for validators
     /// weighted by their voting power (which is assumed to be proportional to its stake).
     /// If there are credits left because of rounding errors when dividing by voting power,
-    /// they are assigned to the proposer.
-    pub fn block_rewards(&self) -> Vec<(vm::Field, vm::EncryptedRecord)> {
+    /// they are assigned to the proposer. The reward of each validator is then split between its
+    /// operator, who takes a commission, and the accounts that delegated credits to it.
+    /// Return the credits earned by each aleo account in the current block.
+    pub fn block_rewards(&self) -> Vec<(vm::Address, u64)> {
         if let Some(proposer) = &self.current_proposer {
+            let (burned_fees, treasury_fees) = self.fee_split();
+            let total_reward = self.fees - burned_fees - treasury_fees;
+
             // first calculate which part of the total belongs to voters
             let voter_reward_percentage = 100 - PROPOSER_REWARD_PERCENTAGE;
-            let total_voter_reward = (self.fees * voter_reward_percentage) / 100;
+            let total_voter_reward =
+                (total_reward as u128 * voter_reward_percentage as u128 / 100) as u64;
             let total_voting_power = self
                 .current_votes
                 .iter()
                 .fold(0, |accum, (_address, power)| accum + power);
             debug!(
                 "total block rewards: {}, total voting power: {}, total voter rewards: {}",
-                self.fees, total_voting_power, total_voter_reward
+                total_reward, total_voting_power, total_voter_reward
             );
 
-            // calculate how much belongs to each validator, proportional to its voting power
-            let mut remaining_fees = self.fees;
-            let mut rewards = HashMap::new();
+            // calculate how much belongs to each validator, proportional to its voting power.
+            // Validators are sorted, so all nodes add up the rewards of each account in the same order
+            let mut remaining_fees = total_reward;
+            let mut rewards = BTreeMap::new();
             for (address, voting_power) in &self.current_votes {
-                let credits = (*voting_power * total_voter_reward) / total_voting_power;
+                let credits = (*voting_power as u128 * total_voter_reward as u128
+                    / total_voting_power as u128) as u64;
                 remaining_fees -= credits;
                 rewards.insert(address, credits);
             }
@@ -207,13 +734,14 @@ impl ValidatorSet {
             *rewards.entry(proposer).or_default() += remaining_fees;
 
             assert_eq!(
-                self.fees,
+                total_reward,
                 rewards.values().sum::<u64>(),
                 "the sum of rewarded credits is different than the fees: {rewards:?}"
             );
 
-            // generate credits records based on the rewards
-            let mut output_records = Vec::new();
+            // split the reward of each validator between its operator and delegators, merging the
+            // credits of accounts that are rewarded through more than one validator
+            let mut account_rewards: Vec<(vm::Address, u64)> = Vec::new();
             for (address, credits) in rewards {
                 let validator = self
                     .validators
@@ -225,38 +753,211 @@ impl ValidatorSet {
                     self.current_votes.get(address).unwrap_or(&0)
                 );
 
-                let record = vm::mint_record(
-                    "credits.aleo",
-                    "credits",
-                    &validator.aleo_address,
-                    credits,
-                    self.current_height,
-                )
-                .expect("Couldn't mint credit records for reward");
-
-                output_records.push(record);
+                for (aleo_address, credits) in
+                    validator.split_reward(credits, self.params.commission_percentage)
+                {
+                    add_credits(&mut account_rewards, aleo_address, credits);
+                }
             }
-
-            output_records
+            account_rewards.retain(|(_, credits)| *credits > 0);
+            // the accrued rewards are persisted in this order, which needs to be the same on every node
+            account_rewards.sort_by_cached_key(|(aleo_address, _)| aleo_address.to_string());
+            account_rewards
         } else {
             warn!("no proposer on this round, skipping rewards");
             Vec::new()
         }
     }
 
-    /// Saves the currently known list of validators to disk.
+    /// Add the rewards of the current block to the ones accrued by each account, which are kept until
+    /// the account claims them instead of minting records on every block.
+    /// The baseline reward is minted and the configured parts of the fees are burned or added to the
+    /// treasury, unless there's no proposer to distribute the rewards to, in which case all the collected
+    /// fees are burned instead.
+    pub fn accrue_rewards(&mut self) {
+        if self.current_proposer.is_some() {
+            let (burned_fees, treasury_fees) = self.fee_split();
+            self.supply.minted += self.baseline;
+            self.supply.burned += burned_fees;
+            self.treasury.balance += treasury_fees;
+        } else {
+            self.supply.burned += self.fees - self.baseline;
+        }
+        for (aleo_address, credits) in self.block_rewards() {
+            add_credits(&mut self.rewards, aleo_address, credits);
+        }
+    }
+
+    /// Return the parts of the fees collected in the current block that are burned and that accrue to the
+    /// treasury. The baseline reward isn't split.
+    fn fee_split(&self) -> (Fee, Fee) {
+        let fees = self.fees - self.baseline;
+        let burned = fees * self.params.fee_burn_percentage / 100;
+        let treasury = fees * self.params.treasury_percentage / 100;
+        (burned, treasury)
+    }
+
+    /// Set the id of the chain, which the treasury keys sign along with each spend.
+    pub fn set_chain_id(&mut self, chain_id: String) {
+        self.treasury.chain_id = chain_id;
+    }
+
+    /// Return the credits accrued by the treasury and the amount of spends made from it.
+    pub fn treasury(&self) -> Treasury {
+        self.treasury.clone()
+    }
+
+    /// Return whether the spend is authorized by the treasury keys and the treasury has enough credits.
+    pub fn validate_spend(&self, spend: &TreasurySpend) -> Result<()> {
+        spend.authorize(&self.params, &self.treasury)?;
+        ensure!(
+            spend.amount() > 0,
+            "can't spend zero credits from the treasury"
+        );
+        ensure!(
+            spend.amount() <= self.treasury.balance,
+            "attempted to spend {} credits from the treasury, but it has {}",
+            spend.amount(),
+            self.treasury.balance
+        );
+        Ok(())
+    }
+
+    /// Take the spent credits out of the treasury, to be paid to the recipient when the block is committed.
+    /// Assumes this spend has been validated previously with validate_spend.
+    pub fn spend(&mut self, spend: TreasurySpend) {
+        info!(
+            "spending {} credits from the treasury to {}",
+            spend.amount(),
+            spend.recipient()
+        );
+        self.treasury.balance -= spend.amount();
+        self.treasury.spends += 1;
+        self.treasury_spends
+            .push((spend.recipient(), spend.amount()));
+    }
+
+    /// Return the rewards accrued by the given account that it hasn't claimed yet.
+    pub fn pending_rewards(&self, aleo_address: &vm::Address) -> u64 {
+        self.rewards
+            .iter()
+            .find(|(rewarded, _)| rewarded == aleo_address)
+            .map_or(0, |(_, credits)| *credits)
+    }
+
+    /// Return whether the account has accrued rewards to claim.
+    pub fn validate_claim(&self, aleo_address: &vm::Address) -> Result<()> {
+        ensure!(
+            self.pending_rewards(aleo_address) > 0,
+            "there are no rewards to claim for {aleo_address}"
+        );
+        Ok(())
+    }
+
+    /// Take the rewards accrued by the account, to be paid in a single record when the block is committed.
+    /// Assumes this claim has been validated previously with validate_claim.
+    pub fn claim(&mut self, aleo_address: vm::Address) {
+        let credits = self.pending_rewards(&aleo_address);
+        self.rewards
+            .retain(|(rewarded, _)| *rewarded != aleo_address);
+        self.claims.push((aleo_address, credits));
+    }
+
+    /// Generate the credits records that pay the rewards claimed during the current block.
+    pub fn claim_records(&self) -> Vec<(vm::Field, vm::EncryptedRecord)> {
+        self.payment_records("claim", &self.claims)
+    }
+
+    /// Generate the credits records that pay the credits spent from the treasury during the current block.
+    pub fn treasury_records(&self) -> Vec<(vm::Field, vm::EncryptedRecord)> {
+        self.payment_records("treasury", &self.treasury_spends)
+    }
+
+    /// Generate the credits records that pay back the unbonded credits withdrawn during the current block.
+    pub fn withdrawal_records(&self) -> Vec<(vm::Field, vm::EncryptedRecord)> {
+        self.payment_records("withdraw", &self.withdrawals)
+    }
+
+    /// Mint a credits record for each of the given payments of the current block, seeded by the kind of payment.
+    fn payment_records(
+        &self,
+        kind: &str,
+        payments: &[(vm::Address, u64)],
+    ) -> Vec<(vm::Field, vm::EncryptedRecord)> {
+        payments
+            .iter()
+            .enumerate()
+            .map(|(index, (aleo_address, gates))| {
+                debug!("Paying {gates} credits to {aleo_address} ({kind})");
+                vm::mint_record(
+                    "credits.aleo",
+                    "credits",
+                    aleo_address,
+                    *gates,
+                    record_seed(kind.as_bytes(), self.current_height, index),
+                )
+                .unwrap_or_else(|e| panic!("Couldn't mint credit records for {kind}: {e}"))
+            })
+            .collect()
+    }
+
+    /// Saves the currently known list of validators to disk, taking the top validators informed to
+    /// tendermint in end_block as the ones that are now part of the consensus.
     pub fn commit(&mut self) -> Result<()> {
-        let validators_vec: Vec<Validator> = self.validators.values().cloned().collect();
-        let json = serde_json::to_string(&validators_vec).expect("couldn't serialize validators");
+        self.active = self.top_validators();
+        let mut active: Vec<Address> = self.active.iter().cloned().collect();
+        active.sort();
+        let file = ValidatorSetFile {
+            params: self.params.clone(),
+            validators: self.validators.values().cloned().collect(),
+            missed_blocks: self
+                .missed_blocks
+                .iter()
+                .map(|(address, history)| (address.clone(), history.clone()))
+                .collect(),
+            unbonding: self.unbonding.clone(),
+            rotated_keys: self
+                .rotated_keys
+                .iter()
+                .map(|(previous, current)| (previous.clone(), current.clone()))
+                .collect(),
+            active: Some(active),
+            rewards: self.rewards.clone(),
+            supply: self.supply.clone(),
+            treasury: self.treasury.clone(),
+        };
+        let json = serde_json::to_string(&file).expect("couldn't serialize validators");
         std::fs::write(&self.path, json)
             .map_err(|e| anyhow!("failed to write validators file {:?} {e}", self.path))
     }
 }
 
+/// Add credits to the ones of the given account in a list of rewards.
+fn add_credits(rewards: &mut Vec<(vm::Address, u64)>, aleo_address: vm::Address, credits: u64) {
+    match rewards
+        .iter_mut()
+        .find(|(rewarded, _)| *rewarded == aleo_address)
+    {
+        Some((_, total)) => *total += credits,
+        None => rewards.push((aleo_address, credits)),
+    }
+}
+
+/// Derive the seed of a record minted by the validator set, unique for each kind of record, block
+/// height and position among the records of that kind, so all nodes generate the same records.
+fn record_seed(kind: &[u8], height: u64, index: usize) -> u64 {
+    let mut hasher = Sha256::new();
+    hasher.update(kind);
+    hasher.update(height.to_le_bytes());
+    hasher.update((index as u64).to_le_bytes());
+    u64::from_le_bytes(hasher.finalize()[..8].try_into().unwrap())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use assert_fs::NamedTempFile;
+    use lib::validator::{EmissionSchedule, KeySignature, DEFAULT_BLOCK_REWARD};
     use lib::vm;
 
     #[test]
@@ -300,14 +1001,14 @@ mod tests {
         let fees = 20 + 35;
 
         // get rewards
-        let records = set.block_rewards();
-        let rewards1 = decrypt_rewards(&aleo1, &records);
-        let rewards2 = decrypt_rewards(&aleo2, &records);
-        let rewards3 = decrypt_rewards(&aleo3, &records);
-        let rewards4 = decrypt_rewards(&aleo4, &records);
+        let rewards = set.block_rewards();
+        let rewards1 = earned(&aleo1, &rewards);
+        let rewards2 = earned(&aleo2, &rewards);
+        let rewards3 = earned(&aleo3, &rewards);
+        let rewards4 = earned(&aleo4, &rewards);
 
         // check proposer gets 50% and the rest is distributed according to vote power
-        let total_rewards = BASELINE_BLOCK_REWARD + fees;
+        let total_rewards = DEFAULT_BLOCK_REWARD + fees;
         let voter_rewards = total_rewards * PROPOSER_REWARD_PERCENTAGE / 100;
 
         // ensure the no credits are lost in the process
@@ -328,15 +1029,15 @@ mod tests {
         set.begin_block(&validator4.address(), votes, 2);
         set.collect(10);
 
-        let records = set.block_rewards();
-        let rewards1 = decrypt_rewards(&aleo1, &records);
-        let rewards2 = decrypt_rewards(&aleo2, &records);
-        let rewards3 = decrypt_rewards(&aleo3, &records);
-        let rewards4 = decrypt_rewards(&aleo4, &records);
+        let rewards = set.block_rewards();
+        let rewards1 = earned(&aleo1, &rewards);
+        let rewards2 = earned(&aleo2, &rewards);
+        let rewards3 = earned(&aleo3, &rewards);
+        let rewards4 = earned(&aleo4, &rewards);
         assert_eq!(0, rewards1);
         assert_eq!(0, rewards2);
         assert_eq!(0, rewards3);
-        assert_eq!(BASELINE_BLOCK_REWARD + 10, rewards4);
+        assert_eq!(DEFAULT_BLOCK_REWARD + 10, rewards4);
     }
 
     #[test]
@@ -368,12 +1069,12 @@ mod tests {
         let fees = 35;
 
         // get rewards
-        let records = set.block_rewards();
-        let rewards1 = decrypt_rewards(&aleo1, &records);
-        let rewards2 = decrypt_rewards(&aleo2, &records);
+        let rewards = set.block_rewards();
+        let rewards1 = earned(&aleo1, &rewards);
+        let rewards2 = earned(&aleo2, &rewards);
 
         // check proposer gets 50% and the rest is distributed according to vote power
-        let total_rewards = BASELINE_BLOCK_REWARD + fees;
+        let total_rewards = DEFAULT_BLOCK_REWARD + fees;
         let voter_rewards = total_rewards * PROPOSER_REWARD_PERCENTAGE / 100;
 
         // ensure the no credits are lost in the process
@@ -385,7 +1086,6 @@ mod tests {
     }
 
     #[test]
-    #[allow(clippy::clone_on_copy)]
     fn rewards_are_deterministic() {
         // create 2 different validators with the same amounts
         let tmint1 = "vM+mkdPMvplfxO7wM57z4FXy0TlBC2Onb+MaqcXE8ig=";
@@ -410,38 +1110,364 @@ mod tests {
         set2.begin_block(&validator1.address(), votes.clone(), 1);
         set1.collect(100);
         set2.collect(100);
+        set1.accrue_rewards();
+        set2.accrue_rewards();
 
-        let mut records11 = set1.block_rewards();
-        let mut records21 = set2.block_rewards();
-        records11.sort_by_key(|k| k.0.clone());
-        records21.sort_by_key(|k| k.0.clone());
-
-        // check that the records generated by both validators are the same
+        // the claimed rewards are paid with the same records by both validators,
         // regardless of the nonce component of the records
-        assert_eq!(records11, records21);
-
-        // prepare another block with the same fees, verify that even though
-        // the record amounts are the same, the records themselves are not
         set1.begin_block(&validator1.address(), votes.clone(), 2);
         set2.begin_block(&validator1.address(), votes.clone(), 2);
-        set1.collect(100);
-        set2.collect(100);
+        set1.claim(aleo1.1);
+        set2.claim(aleo1.1);
+        let records12 = set1.claim_records();
+        let records22 = set2.claim_records();
+        assert_eq!(records12, records22);
+        set1.accrue_rewards();
+        set2.accrue_rewards();
 
-        let mut records12 = set1.block_rewards();
-        let mut records22 = set2.block_rewards();
-        records12.sort_by_key(|k| k.0.clone());
-        records22.sort_by_key(|k| k.0.clone());
+        // claim again on another block, verify that even though
+        // the record amounts are the same, the records themselves are not
+        set1.begin_block(&validator1.address(), votes.clone(), 3);
+        set2.begin_block(&validator1.address(), votes.clone(), 3);
+        set1.claim(aleo1.1);
+        set2.claim(aleo1.1);
+        let records13 = set1.claim_records();
+        let records23 = set2.claim_records();
 
         // both validators see the same for this round
-        assert_eq!(records12, records22);
+        assert_eq!(records13, records23);
         // but the records are not equal to the previous one
-        assert_ne!(records11, records12);
-        assert_ne!(records21, records22);
+        assert_ne!(records12, records13);
+        assert_ne!(records22, records23);
 
         // the gates inside the records are the same
-        let rewards111 = decrypt_rewards(&aleo1, &records11);
-        let rewards121 = decrypt_rewards(&aleo1, &records12);
-        assert_eq!(rewards111, rewards121);
+        let rewards12 = decrypt_credits(&aleo1, &records12);
+        let rewards13 = decrypt_credits(&aleo1, &records13);
+        assert_eq!(rewards12, rewards13);
+    }
+
+    #[test]
+    fn claim_accrued_rewards() {
+        let tmint1 = "vM+mkdPMvplfxO7wM57z4FXy0TlBC2Onb+MaqcXE8ig=";
+        let aleo1 = account_keys();
+        let aleo2 = account_keys();
+        let validator1 = Validator::from_str(tmint1, &aleo1.1.to_string(), 1).unwrap();
+
+        let tempfile = NamedTempFile::new("validators").unwrap();
+        let mut set = ValidatorSet::load_or_create(tempfile.path());
+        set.replace(vec![validator1.clone()]);
+
+        // the rewards accrue across blocks and restarts
+        let mut votes = HashMap::new();
+        votes.insert(validator1.address(), 10);
+        set.begin_block(&validator1.address(), votes.clone(), 1);
+        set.collect(10);
+        set.accrue_rewards();
+        assert_eq!(DEFAULT_BLOCK_REWARD + 10, set.pending_rewards(&aleo1.1));
+        set.commit().unwrap();
+
+        let mut set = ValidatorSet::load_or_create(tempfile.path());
+        set.begin_block(&validator1.address(), votes.clone(), 2);
+        set.accrue_rewards();
+        assert_eq!(2 * DEFAULT_BLOCK_REWARD + 10, set.pending_rewards(&aleo1.1));
+
+        // accounts without rewards can't claim
+        let error = set.validate_claim(&aleo2.1).unwrap_err();
+        assert!(error.to_string().contains("there are no rewards to claim"));
+
+        // the accrued rewards are paid in a single record
+        set.begin_block(&validator1.address(), votes.clone(), 3);
+        set.validate_claim(&aleo1.1).unwrap();
+        set.claim(aleo1.1);
+        assert_eq!(0, set.pending_rewards(&aleo1.1));
+        let error = set.validate_claim(&aleo1.1).unwrap_err();
+        assert!(error.to_string().contains("there are no rewards to claim"));
+        let records = set.claim_records();
+        assert_eq!(1, records.len());
+        assert_eq!(
+            2 * DEFAULT_BLOCK_REWARD + 10,
+            decrypt_credits(&aleo1, &records)
+        );
+
+        // the rewards of the block where they are claimed accrue afterwards
+        set.accrue_rewards();
+        assert_eq!(DEFAULT_BLOCK_REWARD, set.pending_rewards(&aleo1.1));
+        set.begin_block(&validator1.address(), votes, 4);
+        assert!(set.claim_records().is_empty());
+    }
+
+    #[test]
+    fn emission_schedule() {
+        let halving = EmissionSchedule {
+            initial_reward: 100,
+            reduction_interval: 10,
+            reduction_percentage: 50,
+        };
+        assert_eq!(100, halving.block_reward(9));
+        assert_eq!(50, halving.block_reward(10));
+        assert_eq!(25, halving.block_reward(25));
+        assert_eq!(0, halving.block_reward(1000));
+
+        let decay = EmissionSchedule {
+            initial_reward: 100,
+            reduction_interval: 1,
+            reduction_percentage: 10,
+        };
+        assert_eq!(90, decay.block_reward(1));
+        assert_eq!(81, decay.block_reward(2));
+        assert_eq!(0, decay.block_reward(u64::MAX));
+
+        // small reductions keep cutting the reward, instead of stopping when the cut rounds to zero
+        let slow_decay = EmissionSchedule {
+            initial_reward: 100,
+            reduction_interval: 1,
+            reduction_percentage: 1,
+        };
+        assert_eq!(99, slow_decay.block_reward(1));
+        assert_eq!(36, slow_decay.block_reward(100));
+        assert_eq!(0, slow_decay.block_reward(u64::MAX));
+
+        // large rewards don't overflow
+        let large = EmissionSchedule {
+            initial_reward: u64::MAX,
+            reduction_interval: 1,
+            reduction_percentage: 50,
+        };
+        assert_eq!(u64::MAX / 2, large.block_reward(1));
+        assert_eq!(
+            DEFAULT_BLOCK_REWARD,
+            EmissionSchedule::default().block_reward(u64::MAX)
+        );
+    }
+
+    #[test]
+    fn track_supply() {
+        let tmint1 = "vM+mkdPMvplfxO7wM57z4FXy0TlBC2Onb+MaqcXE8ig=";
+        let aleo1 = account_keys();
+        let validator1 = Validator::from_str(tmint1, &aleo1.1.to_string(), 100).unwrap();
+
+        let tempfile = NamedTempFile::new("validators").unwrap();
+        let mut set = ValidatorSet::load_or_create(tempfile.path());
+        set.replace(vec![validator1.clone()]);
+        set.set_params(StakingParams {
+            double_sign_slash_percentage: 10,
+            jail_on_double_sign: false,
+            emission: EmissionSchedule {
+                initial_reward: 100,
+                reduction_interval: 10,
+                reduction_percentage: 50,
+            },
+            ..StakingParams::default()
+        });
+        set.set_genesis_supply(1000);
+
+        // the baseline reward is minted, while the fees are paid back to the validators
+        let mut votes = HashMap::new();
+        votes.insert(validator1.address(), 100);
+        set.begin_block(&validator1.address(), votes.clone(), 1);
+        set.collect(5);
+        set.accrue_rewards();
+        assert_eq!(
+            Supply {
+                genesis: 1000,
+                minted: 100,
+                burned: 0
+            },
+            set.supply()
+        );
+        assert_eq!(1100, set.supply().total());
+
+        // the slashed credits are burned when they are unstaked, or if they were unbonding
+        set.begin_block(&validator1.address(), votes.clone(), 10);
+        set.slash(&validator1.address());
+        set.apply(Stake::new(tmint1, aleo1.1, -50).unwrap());
+        assert_eq!(5, set.supply().burned);
+        set.slash(&validator1.address());
+        assert_eq!(9, set.supply().burned);
+        set.accrue_rewards();
+        set.commit().unwrap();
+
+        // the supply is persisted
+        let set = ValidatorSet::load_or_create(tempfile.path());
+        assert_eq!(
+            Supply {
+                genesis: 1000,
+                minted: 150,
+                burned: 9
+            },
+            set.supply()
+        );
+        assert_eq!(1141, set.supply().total());
+    }
+
+    #[test]
+    fn split_block_fees() {
+        let tmint1 = "vM+mkdPMvplfxO7wM57z4FXy0TlBC2Onb+MaqcXE8ig=";
+        let aleo1 = account_keys();
+        let validator1 = Validator::from_str(tmint1, &aleo1.1.to_string(), 100).unwrap();
+
+        let tempfile = NamedTempFile::new("validators").unwrap();
+        let mut set = ValidatorSet::load_or_create(tempfile.path());
+        set.replace(vec![validator1.clone()]);
+        set.set_params(StakingParams {
+            fee_burn_percentage: 20,
+            treasury_percentage: 30,
+            ..StakingParams::default()
+        });
+        set.set_genesis_supply(1000);
+
+        // part of the fees is burned and part goes to the treasury, but the baseline reward isn't split
+        let mut votes = HashMap::new();
+        votes.insert(validator1.address(), 100);
+        set.begin_block(&validator1.address(), votes, 1);
+        set.collect(100);
+        let rewards = set.block_rewards();
+        assert_eq!(DEFAULT_BLOCK_REWARD + 50, earned(&aleo1, &rewards));
+
+        set.accrue_rewards();
+        assert_eq!(DEFAULT_BLOCK_REWARD + 50, set.pending_rewards(&aleo1.1));
+        assert_eq!(
+            Treasury {
+                balance: 30,
+                spends: 0,
+                ..Treasury::default()
+            },
+            set.treasury()
+        );
+        assert_eq!(
+            Supply {
+                genesis: 1000,
+                minted: DEFAULT_BLOCK_REWARD,
+                burned: 20
+            },
+            set.supply()
+        );
+        set.commit().unwrap();
+
+        // the treasury is persisted
+        let set = ValidatorSet::load_or_create(tempfile.path());
+        assert_eq!(30, set.treasury().balance);
+    }
+
+    #[test]
+    fn spend_from_treasury() {
+        let tmint1 = "vM+mkdPMvplfxO7wM57z4FXy0TlBC2Onb+MaqcXE8ig=";
+        let aleo1 = account_keys();
+        let aleo2 = account_keys();
+        let validator1 = Validator::from_str(tmint1, &aleo1.1.to_string(), 100).unwrap();
+        let (treasury_key1, private_key1) = tendermint_keys(1);
+        let (treasury_key2, private_key2) = tendermint_keys(2);
+        let (treasury_key3, _) = tendermint_keys(3);
+        let (_, other_private_key) = tendermint_keys(4);
+
+        let tempfile = NamedTempFile::new("validators").unwrap();
+        let mut set = ValidatorSet::load_or_create(tempfile.path());
+        set.replace(vec![validator1.clone()]);
+        set.set_chain_id("test-chain".to_string());
+        set.set_params(StakingParams {
+            treasury_percentage: 50,
+            treasury_keys: vec![treasury_key1, treasury_key2, treasury_key3],
+            treasury_threshold: 2,
+            ..StakingParams::default()
+        });
+
+        let mut votes = HashMap::new();
+        votes.insert(validator1.address(), 100);
+        set.begin_block(&validator1.address(), votes.clone(), 1);
+        set.collect(60);
+        set.accrue_rewards();
+        assert_eq!(30, set.treasury().balance);
+
+        let message = TreasurySpend::message(&set.treasury(), &aleo2.1, 20);
+        let signature1 = KeySignature::new(&private_key1, &message).unwrap();
+        let signature2 = KeySignature::new(&private_key2, &message).unwrap();
+        let other_signature = KeySignature::new(&other_private_key, &message).unwrap();
+
+        // a spend needs the signatures of the threshold of treasury keys
+        set.begin_block(&validator1.address(), votes.clone(), 2);
+        let spend = TreasurySpend::new(aleo2.1, 20, vec![signature1.clone()]);
+        let error = set.validate_spend(&spend).unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("is signed by 1 treasury keys, but 2 are required"));
+        let spend = TreasurySpend::new(
+            aleo2.1,
+            20,
+            vec![signature1.clone(), signature1.clone(), other_signature],
+        );
+        let error = set.validate_spend(&spend).unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("is signed by 1 treasury keys, but 2 are required"));
+
+        // the signatures are for a given recipient and amount
+        let spend = TreasurySpend::new(aleo1.1, 20, vec![signature1.clone(), signature2.clone()]);
+        assert!(set.validate_spend(&spend).is_err());
+        let spend = TreasurySpend::new(aleo2.1, 25, vec![signature1.clone(), signature2.clone()]);
+        assert!(set.validate_spend(&spend).is_err());
+
+        // the signatures are for a given chain
+        let other_chain = Treasury {
+            chain_id: "other-chain".to_string(),
+            ..set.treasury()
+        };
+        let other_chain_message = TreasurySpend::message(&other_chain, &aleo2.1, 20);
+        let spend = TreasurySpend::new(
+            aleo2.1,
+            20,
+            vec![
+                KeySignature::new(&private_key1, &other_chain_message).unwrap(),
+                KeySignature::new(&private_key2, &other_chain_message).unwrap(),
+            ],
+        );
+        let error = set.validate_spend(&spend).unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("is signed by 0 treasury keys, but 2 are required"));
+
+        // can't spend more than the treasury balance
+        let message = TreasurySpend::message(&set.treasury(), &aleo2.1, 40);
+        let spend = TreasurySpend::new(
+            aleo2.1,
+            40,
+            vec![
+                KeySignature::new(&private_key1, &message).unwrap(),
+                KeySignature::new(&private_key2, &message).unwrap(),
+            ],
+        );
+        let error = set.validate_spend(&spend).unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("attempted to spend 40 credits from the treasury, but it has 30"));
+
+        // the spent credits are paid when the block is committed
+        let spend = TreasurySpend::new(aleo2.1, 20, vec![signature1, signature2]);
+        set.validate_spend(&spend).unwrap();
+        set.spend(spend.clone());
+        assert_eq!(
+            Treasury {
+                balance: 10,
+                spends: 1,
+                chain_id: "test-chain".to_string()
+            },
+            set.treasury()
+        );
+        let records = set.treasury_records();
+        assert_eq!(1, records.len());
+        assert_eq!(20, decrypt_credits(&aleo2, &records));
+
+        // the signatures can't be replayed for another spend
+        let error = set.validate_spend(&spend).unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("is signed by 0 treasury keys, but 2 are required"));
+
+        // the treasury can't be spent without keys
+        set.set_params(StakingParams::default());
+        let error = set.validate_spend(&spend).unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("the treasury has no keys to authorize spends"));
     }
 
     #[test]
@@ -466,10 +1492,10 @@ mod tests {
         set.collect(35);
         let fees = 20 + 35;
 
-        let records = set.block_rewards();
-        let rewards1 = decrypt_rewards(&aleo1, &records);
-        let rewards2 = decrypt_rewards(&aleo2, &records);
-        let total_rewards = BASELINE_BLOCK_REWARD + fees;
+        let rewards = set.block_rewards();
+        let rewards1 = earned(&aleo1, &rewards);
+        let rewards2 = earned(&aleo2, &rewards);
+        let total_rewards = DEFAULT_BLOCK_REWARD + fees;
 
         // proposer takes all
         assert_eq!(total_rewards, rewards1);
@@ -499,7 +1525,7 @@ mod tests {
         set.begin_block(&validator1.address(), votes, 1);
         // no updates on this round (should ignore default ones from before begin block)
         assert_eq!(0, set.pending_updates().len());
-        let _records = set.block_rewards();
+        let _rewards = set.block_rewards();
         set.commit().unwrap();
 
         // votes/begin block
@@ -522,7 +1548,7 @@ mod tests {
         assert_eq!(stake2.validator_address(), updates[1].address());
         assert_eq!(6, updates[1].voting_power);
 
-        let _records = set.block_rewards();
+        let _rewards = set.block_rewards();
         set.commit().unwrap();
     }
 
@@ -555,7 +1581,7 @@ mod tests {
         assert_eq!(stake2.validator_address(), updates[0].address());
         assert_eq!(2, updates[0].voting_power);
 
-        let _records = set.block_rewards();
+        let _rewards = set.block_rewards();
         set.commit().unwrap();
 
         // votes/begin block
@@ -574,7 +1600,7 @@ mod tests {
         assert_eq!(0, updates[0].voting_power);
 
         // get rewards check as expected, include removed
-        let _records = set.block_rewards();
+        let _rewards = set.block_rewards();
         set.commit().unwrap();
 
         // votes/begin block, shouldn't fail even if it includes votes from removed one
@@ -582,7 +1608,7 @@ mod tests {
         votes.insert(validator2.address(), 5);
         set.begin_block(&validator2.address(), votes, 1);
         assert_eq!(0, set.pending_updates().len());
-        let _records = set.block_rewards();
+        let _rewards = set.block_rewards();
         set.commit().unwrap();
     }
 
@@ -600,13 +1626,17 @@ mod tests {
         let validators = vec![validator1, validator2];
         set.replace(validators);
 
-        // invalid when aleo address doesn't match previously known one
-        let aleo2_fake = account_keys();
-        let validator2_fake = Stake::new(tmint2, aleo2_fake.1, 5).unwrap();
-        let error = set.validate(&validator2_fake).unwrap_err();
+        // valid when staking from a different aleo account, as a delegation, but the delegator
+        // can't unstake more than it delegated
+        let delegator = account_keys();
+        let delegation = Stake::new(tmint2, delegator.1, 5).unwrap();
+        set.validate(&delegation).unwrap();
+        set.apply(delegation);
+        let undelegation = Stake::new(tmint2, delegator.1, -6).unwrap();
+        let error = set.validate(&undelegation).unwrap_err();
         assert!(error
             .to_string()
-            .contains("attempted to apply a staking update on a different aleo account"));
+            .contains("attempted to unstake more voting power than available"));
 
         // invalid on new one and negative voting
         let tmint3 = "TtJ9B7yGXANFIJqH2LJO8JN6M2WOn2w7sRN0HHi14UE=";
@@ -630,6 +1660,574 @@ mod tests {
             .contains("attempted to unstake more voting power than available"));
     }
 
+    #[test]
+    fn slash_double_signer() {
+        let tmint1 = "vM+mkdPMvplfxO7wM57z4FXy0TlBC2Onb+MaqcXE8ig=";
+        let tmint2 = "2HWbuGk04WQm/CrI/0HxoEtjGY0DXp8oMY6RsyrWwbU=";
+        let aleo1 = account_keys();
+        let aleo2 = account_keys();
+        let validator1 = Validator::from_str(tmint1, &aleo1.1.to_string(), 100).unwrap();
+        let validator2 = Validator::from_str(tmint2, &aleo2.1.to_string(), 100).unwrap();
+
+        let tempfile = NamedTempFile::new("validators").unwrap();
+        let mut set = ValidatorSet::load_or_create(tempfile.path());
+        set.replace(vec![validator1.clone(), validator2.clone()]);
+        set.set_params(StakingParams {
+            double_sign_slash_percentage: 10,
+            jail_on_double_sign: true,
+            ..Default::default()
+        });
+
+        let mut votes = HashMap::new();
+        votes.insert(validator1.address(), 100);
+        votes.insert(validator2.address(), 100);
+        set.begin_block(&validator1.address(), votes, 1);
+
+        // validator2 double signed
+        assert_eq!(Some(10), set.slash(&validator2.address()));
+        assert!(set.is_jailed(&validator2.address()));
+        assert_eq!(None, set.slash(&b"unknown".to_vec()));
+
+        // the update removes it from the consensus, but keeps its remaining voting power
+        let updates = set.pending_updates();
+        assert_eq!(1, updates.len());
+        assert_eq!(90, updates[0].voting_power);
+        assert_eq!(0, updates[0].consensus_power());
+
+        // it doesn't get rewards for this block
+        let rewards = set.block_rewards();
+        assert_eq!(0, earned(&aleo2, &rewards));
+        assert_eq!(DEFAULT_BLOCK_REWARD, earned(&aleo1, &rewards));
+        set.commit().unwrap();
+
+        // the slashing and jailing survive restarts
+        let mut set = ValidatorSet::load_or_create(tempfile.path());
+        assert!(set.is_jailed(&validator2.address()));
+        set.begin_block(&validator1.address(), HashMap::new(), 2);
+
+        // double signers can't return
+        let error = set
+            .validate_unjail(&StakerRequest::new(tmint2, aleo2.1).unwrap())
+            .unwrap_err();
+        assert!(error.to_string().contains("can't be unjailed"));
+
+        // the staked credits are worth 10% less when unstaked
+        set.apply(Stake::new(tmint2, aleo2.1, -50).unwrap());
+        assert_eq!(45, set.pending_updates()[0].voting_power);
+
+        // unstaking the rest leaves nothing behind
+        set.apply(Stake::new(tmint2, aleo2.1, -50).unwrap());
+        assert_eq!(0, set.pending_updates()[0].voting_power);
+
+        // the unstaked credits are withdrawn together once they mature
+        let request = StakerRequest::new(tmint2, aleo2.1).unwrap();
+        set.begin_block(&validator1.address(), HashMap::new(), 102);
+        set.validate_withdraw(&request).unwrap();
+        set.withdraw(request);
+        let records = set.withdrawal_records();
+        assert_eq!(1, records.len());
+        assert_eq!(90, decrypt_credits(&aleo2, &records));
+    }
+
+    #[test]
+    fn withdraw_unbonding_credits() {
+        let tmint1 = "vM+mkdPMvplfxO7wM57z4FXy0TlBC2Onb+MaqcXE8ig=";
+        let tmint2 = "2HWbuGk04WQm/CrI/0HxoEtjGY0DXp8oMY6RsyrWwbU=";
+        let aleo1 = account_keys();
+        let aleo2 = account_keys();
+        let validator1 = Validator::from_str(tmint1, &aleo1.1.to_string(), 100).unwrap();
+        let validator2 = Validator::from_str(tmint2, &aleo2.1.to_string(), 100).unwrap();
+
+        let tempfile = NamedTempFile::new("validators").unwrap();
+        let mut set = ValidatorSet::load_or_create(tempfile.path());
+        set.replace(vec![validator1.clone(), validator2.clone()]);
+        set.set_params(StakingParams {
+            double_sign_slash_percentage: 10,
+            unbonding_blocks: 10,
+            ..Default::default()
+        });
+
+        // both validators unstake, the voting power drops immediately
+        set.begin_block(&validator1.address(), HashMap::new(), 1);
+        set.apply(Stake::new(tmint1, aleo1.1, -20).unwrap());
+        set.apply(Stake::new(tmint2, aleo2.1, -50).unwrap());
+        let mut updates = set.pending_updates();
+        updates.sort_by_key(|v| v.voting_power);
+        assert_eq!(50, updates[0].voting_power);
+        assert_eq!(80, updates[1].voting_power);
+        assert!(set.withdrawal_records().is_empty());
+        set.commit().unwrap();
+
+        // the credits can't be withdrawn before they mature, or by other accounts
+        let request1 = StakerRequest::new(tmint1, aleo1.1).unwrap();
+        let request2 = StakerRequest::new(tmint2, aleo2.1).unwrap();
+        let mut set = ValidatorSet::load_or_create(tempfile.path());
+        set.begin_block(&validator1.address(), HashMap::new(), 10);
+        let error = set.validate_withdraw(&request2).unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("can't be withdrawn until block 11"));
+        let error = set
+            .validate_withdraw(&StakerRequest::new(tmint2, aleo1.1).unwrap())
+            .unwrap_err();
+        assert!(error.to_string().contains("there are no credits unbonding"));
+
+        // validator2 double signs while its credits are unbonding, which slashes them too
+        assert_eq!(Some(5), set.slash(&validator2.address()));
+
+        set.begin_block(&validator1.address(), HashMap::new(), 11);
+        set.validate_withdraw(&request1).unwrap();
+        set.validate_withdraw(&request2).unwrap();
+        set.withdraw(request1.clone());
+        set.withdraw(request2);
+        let records = set.withdrawal_records();
+        assert_eq!(20, decrypt_credits(&aleo1, &records));
+        assert_eq!(45, decrypt_credits(&aleo2, &records));
+
+        // they can only be withdrawn once
+        let error = set.validate_withdraw(&request1).unwrap_err();
+        assert!(error.to_string().contains("there are no credits unbonding"));
+    }
+
+    #[test]
+    fn jail_offline_validator() {
+        let tmint1 = "vM+mkdPMvplfxO7wM57z4FXy0TlBC2Onb+MaqcXE8ig=";
+        let tmint2 = "2HWbuGk04WQm/CrI/0HxoEtjGY0DXp8oMY6RsyrWwbU=";
+        let aleo1 = account_keys();
+        let aleo2 = account_keys();
+        let validator1 = Validator::from_str(tmint1, &aleo1.1.to_string(), 10).unwrap();
+        let validator2 = Validator::from_str(tmint2, &aleo2.1.to_string(), 10).unwrap();
+        let signed = vec![validator1.address()];
+        let missed = vec![validator2.address()];
+        let all_signed = vec![validator1.address(), validator2.address()];
+
+        let tempfile = NamedTempFile::new("validators").unwrap();
+        let mut set = ValidatorSet::load_or_create(tempfile.path());
+        set.replace(vec![validator1.clone(), validator2.clone()]);
+        set.set_params(StakingParams {
+            signed_blocks_window: 4,
+            max_missed_blocks: 2,
+            downtime_jail_blocks: 10,
+            ..Default::default()
+        });
+
+        // validator2 misses two blocks and signs the next two
+        for (height, signed, missed) in [
+            (1, &signed, &missed),
+            (2, &signed, &missed),
+            (3, &all_signed, &vec![]),
+            (4, &all_signed, &vec![]),
+        ] {
+            set.begin_block(&validator1.address(), HashMap::new(), height);
+            assert!(set.track_missed_blocks(signed, missed).is_empty());
+            set.commit().unwrap();
+        }
+
+        // the missed blocks survive restarts. Missing two more is within the limit,
+        // since the first ones are out of the window by then
+        let mut set = ValidatorSet::load_or_create(tempfile.path());
+        for height in [5, 6] {
+            set.begin_block(&validator1.address(), HashMap::new(), height);
+            assert!(set.track_missed_blocks(&signed, &missed).is_empty());
+        }
+
+        // missing another one leaves it over the limit
+        set.begin_block(&validator1.address(), HashMap::new(), 7);
+        assert_eq!(missed, set.track_missed_blocks(&signed, &missed));
+        assert!(set.is_jailed(&validator2.address()));
+        let updates = set.pending_updates();
+        assert_eq!(1, updates.len());
+        assert_eq!(0, updates[0].consensus_power());
+        assert_eq!(10, updates[0].voting_power);
+
+        // it can't be unjailed by other accounts or before the jail time is over
+        let unjail = StakerRequest::new(tmint2, aleo2.1).unwrap();
+        let error = set
+            .validate_unjail(&StakerRequest::new(tmint2, aleo1.1).unwrap())
+            .unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("attempted to unjail a validator from a different aleo account"));
+        set.begin_block(&validator1.address(), HashMap::new(), 16);
+        let error = set.validate_unjail(&unjail).unwrap_err();
+        assert!(error.to_string().contains("is jailed until block 17"));
+
+        set.begin_block(&validator1.address(), HashMap::new(), 17);
+        set.validate_unjail(&unjail).unwrap();
+        set.unjail(unjail.clone());
+        assert!(!set.is_jailed(&validator2.address()));
+        assert_eq!(10, set.pending_updates()[0].consensus_power());
+
+        // it starts over with a clean window
+        set.begin_block(&validator1.address(), HashMap::new(), 18);
+        assert!(set.track_missed_blocks(&signed, &missed).is_empty());
+        let error = set.validate_unjail(&unjail).unwrap_err();
+        assert!(error.to_string().contains("is not jailed"));
+    }
+
+    #[test]
+    fn slash_without_jailing() {
+        let tmint1 = "vM+mkdPMvplfxO7wM57z4FXy0TlBC2Onb+MaqcXE8ig=";
+        let aleo1 = account_keys();
+        let validator1 = Validator::from_str(tmint1, &aleo1.1.to_string(), 10).unwrap();
+
+        let tempfile = NamedTempFile::new("validators").unwrap();
+        let mut set = ValidatorSet::load_or_create(tempfile.path());
+        set.replace(vec![validator1.clone()]);
+        set.set_params(StakingParams {
+            double_sign_slash_percentage: 50,
+            jail_on_double_sign: false,
+            ..Default::default()
+        });
+        set.begin_block(&validator1.address(), HashMap::new(), 1);
+
+        assert_eq!(Some(5), set.slash(&validator1.address()));
+        assert!(!set.is_jailed(&validator1.address()));
+        assert_eq!(5, set.pending_updates()[0].consensus_power());
+    }
+
+    #[test]
+    fn delegation_rewards() {
+        let tmint1 = "vM+mkdPMvplfxO7wM57z4FXy0TlBC2Onb+MaqcXE8ig=";
+        let aleo1 = account_keys();
+        let delegator = account_keys();
+        let validator1 = Validator::from_str(tmint1, &aleo1.1.to_string(), 60).unwrap();
+
+        let tempfile = NamedTempFile::new("validators").unwrap();
+        let mut set = ValidatorSet::load_or_create(tempfile.path());
+        set.replace(vec![validator1.clone()]);
+        set.set_params(StakingParams {
+            commission_percentage: 10,
+            ..Default::default()
+        });
+
+        let delegation = Stake::new(tmint1, delegator.1, 40).unwrap();
+        set.validate(&delegation).unwrap();
+        set.apply(delegation);
+        let updates = set.pending_updates();
+        assert_eq!(100, updates[0].voting_power);
+        assert_eq!(40, updates[0].delegated_power());
+
+        // the operator takes a 10% commission and the rest is split by staked credits
+        let mut votes = HashMap::new();
+        votes.insert(validator1.address(), 100);
+        set.begin_block(&validator1.address(), votes, 1);
+        let rewards = set.block_rewards();
+        assert_eq!(36, earned(&delegator, &rewards));
+        assert_eq!(DEFAULT_BLOCK_REWARD - 36, earned(&aleo1, &rewards));
+
+        // the operator can't unstake the delegated credits
+        let error = set
+            .validate(&Stake::new(tmint1, aleo1.1, -61).unwrap())
+            .unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("attempted to unstake more voting power than available"));
+
+        // once the delegator unstakes, the operator gets all the rewards
+        let undelegation = Stake::new(tmint1, delegator.1, -40).unwrap();
+        set.validate(&undelegation).unwrap();
+        set.apply(undelegation);
+        assert!(set.pending_updates()[0].delegations.is_empty());
+        set.begin_block(&validator1.address(), HashMap::new(), 2);
+        let rewards = set.block_rewards();
+        assert_eq!(0, earned(&delegator, &rewards));
+        assert_eq!(DEFAULT_BLOCK_REWARD, earned(&aleo1, &rewards));
+    }
+
+    #[test]
+    fn register_validator_with_key_signature() {
+        let (tmint1, private_key) = tendermint_keys(7);
+        let aleo1 = account_keys();
+        let aleo2 = account_keys();
+
+        let tempfile = NamedTempFile::new("validators").unwrap();
+        let set = ValidatorSet::load_or_create(tempfile.path());
+
+        // the first stake on a key needs to be signed by it
+        let stake = Stake::new(&tmint1, aleo1.1, 10).unwrap();
+        let error = set.validate(&stake).unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("must be signed by the validator key"));
+
+        // signatures of other keys are ignored
+        let other_key = KeySignature {
+            pub_key: "2HWbuGk04WQm/CrI/0HxoEtjGY0DXp8oMY6RsyrWwbU=".to_string(),
+            ..KeySignature::new(&private_key, &aleo1.1.to_string()).unwrap()
+        };
+        let error = set
+            .validate(&stake.clone().with_key_signature(&other_key).unwrap())
+            .unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("must be signed by the validator key"));
+
+        // a signature over a different aleo address can't be reused
+        let signature = KeySignature::new(&private_key, &aleo2.1.to_string()).unwrap();
+        assert_eq!(tmint1, signature.pub_key);
+        let error = set
+            .validate(&stake.clone().with_key_signature(&signature).unwrap())
+            .unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("invalid validator key signature"));
+
+        let signature = KeySignature::new(&private_key, &aleo1.1.to_string()).unwrap();
+        set.validate(&stake.with_key_signature(&signature).unwrap())
+            .unwrap();
+    }
+
+    #[test]
+    fn rotate_validator_key() {
+        let (tmint1, private_key1) = tendermint_keys(1);
+        let (tmint2, private_key2) = tendermint_keys(2);
+        let aleo1 = account_keys();
+        let aleo2 = account_keys();
+        let validator1 = Validator::from_str(&tmint1, &aleo1.1.to_string(), 10).unwrap();
+        let rotated_address = Validator::from_str(&tmint2, &aleo1.1.to_string(), 10)
+            .unwrap()
+            .address();
+
+        let tempfile = NamedTempFile::new("validators").unwrap();
+        let mut set = ValidatorSet::load_or_create(tempfile.path());
+        set.replace(vec![validator1.clone()]);
+        set.select_validators();
+        let mut votes = HashMap::new();
+        votes.insert(validator1.address(), 10);
+        set.begin_block(&validator1.address(), votes.clone(), 1);
+
+        let message = ValidatorChange::rotate_key_message(&aleo1.1, &tmint2);
+        let signature1 = KeySignature::new(&private_key1, &message).unwrap();
+        let signature2 = KeySignature::new(&private_key2, &message).unwrap();
+        let rotation = |aleo_address, key_signatures| {
+            ValidatorChange::new(
+                StakerRequest::new(&tmint1, aleo_address).unwrap(),
+                ChangeKind::RotateKey,
+                key_signatures,
+            )
+        };
+
+        // both the current and the new key need to sign the rotation, requested by the validator account
+        let error = set
+            .validate_change(&rotation(aleo1.1, vec![signature1.clone()]))
+            .unwrap_err();
+        assert!(error.to_string().contains("must be signed by the new key"));
+        let error = set
+            .validate_change(&rotation(aleo1.1, vec![signature2.clone()]))
+            .unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("must be signed by the validator key"));
+        let error = set
+            .validate_change(&rotation(
+                aleo2.1,
+                vec![signature1.clone(), signature2.clone()],
+            ))
+            .unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("attempted to change a validator from a different aleo account"));
+
+        let change = rotation(aleo1.1, vec![signature1, signature2]);
+        set.validate_change(&change).unwrap();
+        set.change(change.clone());
+        assert_eq!(&[validator1.pub_key], set.replaced_keys());
+        let rotated_key = set.pending_updates()[0].pub_key;
+        assert_eq!(
+            vec![(validator1.pub_key, 0), (rotated_key, 10)],
+            set.consensus_updates()
+        );
+        let updates = set.pending_updates();
+        assert_eq!(rotated_address, updates[0].address());
+        assert_eq!(10, updates[0].consensus_power());
+
+        // it can't be rotated again on the same block
+        let error = set.validate_change(&change).unwrap_err();
+        assert!(error.to_string().contains("can only be rotated"));
+
+        // votes and records of the previous key are taken as the validator's
+        set.begin_block(&validator1.address(), votes, 2);
+        assert!(set.replaced_keys().is_empty());
+        let rewards = set.block_rewards();
+        assert_eq!(DEFAULT_BLOCK_REWARD, earned(&aleo1, &rewards));
+        let unstake = Stake::new(&tmint1, aleo1.1, -4).unwrap();
+        set.validate(&unstake).unwrap();
+        set.apply(unstake);
+        assert_eq!(6, set.pending_updates()[0].voting_power);
+        assert_eq!(rotated_address, set.pending_updates()[0].address());
+
+        set.commit().unwrap();
+        let mut set = ValidatorSet::load_or_create(tempfile.path());
+        set.begin_block(&rotated_address, HashMap::new(), 102);
+        let withdrawal = StakerRequest::new(&tmint1, aleo1.1).unwrap();
+        set.validate_withdraw(&withdrawal).unwrap();
+        set.withdraw(withdrawal);
+        let records = set.withdrawal_records();
+        assert_eq!(4, decrypt_credits(&aleo1, &records));
+    }
+
+    #[test]
+    fn rotate_jailed_validator_key() {
+        let (tmint1, _) = tendermint_keys(1);
+        let (tmint2, private_key2) = tendermint_keys(2);
+        let (tmint3, private_key3) = tendermint_keys(3);
+        let aleo1 = account_keys();
+        let aleo2 = account_keys();
+        let validator1 = Validator::from_str(&tmint1, &aleo1.1.to_string(), 10).unwrap();
+        let validator2 = Validator::from_str(&tmint2, &aleo2.1.to_string(), 10).unwrap();
+        let rotated_address = Validator::from_str(&tmint3, &aleo2.1.to_string(), 10)
+            .unwrap()
+            .address();
+
+        let tempfile = NamedTempFile::new("validators").unwrap();
+        let mut set = ValidatorSet::load_or_create(tempfile.path());
+        set.replace(vec![validator1.clone(), validator2.clone()]);
+        set.select_validators();
+        set.begin_block(&validator1.address(), HashMap::new(), 1);
+        set.slash(&validator2.address());
+        assert!(set.is_jailed(&validator2.address()));
+
+        let message = ValidatorChange::rotate_key_message(&aleo2.1, &tmint3);
+        let change = ValidatorChange::new(
+            StakerRequest::new(&tmint2, aleo2.1).unwrap(),
+            ChangeKind::RotateKey,
+            vec![
+                KeySignature::new(&private_key2, &message).unwrap(),
+                KeySignature::new(&private_key3, &message).unwrap(),
+            ],
+        );
+
+        // it can't be rotated on the block it was jailed
+        let error = set.validate_change(&change).unwrap_err();
+        assert!(error.to_string().contains("can only be rotated"));
+        set.commit().unwrap();
+
+        // once out of the consensus, the new key doesn't join it while the validator is jailed
+        set.begin_block(&validator1.address(), HashMap::new(), 2);
+        set.validate_change(&change).unwrap();
+        set.change(change);
+        assert!(set.replaced_keys().is_empty());
+        assert!(set.consensus_updates().is_empty());
+        assert!(set.is_jailed(&rotated_address));
+        assert!(set.is_jailed(&validator2.address()));
+    }
+
+    #[test]
+    fn max_active_validators() {
+        let aleo1 = account_keys();
+        let aleo2 = account_keys();
+        let mut keys: Vec<String> = (1..=3).map(|seed| tendermint_keys(seed).0).collect();
+        keys.sort_by_key(|tmint| {
+            Validator::from_str(tmint, &aleo1.1.to_string(), 10)
+                .unwrap()
+                .address()
+        });
+        let validators: Vec<Validator> = keys
+            .iter()
+            .map(|tmint| Validator::from_str(tmint, &aleo1.1.to_string(), 10).unwrap())
+            .collect();
+        let (tmint4, _) = tendermint_keys(4);
+        let validator4 = Validator::from_str(&tmint4, &aleo1.1.to_string(), 2).unwrap();
+
+        let tempfile = NamedTempFile::new("validators").unwrap();
+        let mut set = ValidatorSet::load_or_create(tempfile.path());
+        set.set_params(StakingParams {
+            max_validators: 2,
+            min_self_stake: 5,
+            ..StakingParams::default()
+        });
+        let mut genesis = validators.clone();
+        genesis.push(validator4.clone());
+        set.replace(genesis);
+
+        // the fourth validator has the most voting power, but its operator didn't stake the minimum
+        set.apply(Stake::new(&tmint4, aleo2.1, 40).unwrap());
+        // ties are broken by address
+        let selected: Vec<Address> = set
+            .select_validators()
+            .iter()
+            .map(Validator::address)
+            .collect();
+        assert_eq!(
+            vec![validators[0].address(), validators[1].address()],
+            selected
+        );
+
+        // a candidate with more voting power replaces the last of the active validators
+        set.begin_block(&validators[0].address(), HashMap::new(), 1);
+        set.apply(Stake::new(&keys[2], aleo1.1, 1).unwrap());
+        assert_eq!(
+            vec![(validators[1].pub_key, 0), (validators[2].pub_key, 11)],
+            set.consensus_updates()
+        );
+
+        // the active validators are persisted
+        set.commit().unwrap();
+        let mut set = ValidatorSet::load_or_create(tempfile.path());
+        set.begin_block(&validators[0].address(), HashMap::new(), 2);
+        assert!(set.consensus_updates().is_empty());
+
+        // once its operator stakes the minimum, the fourth validator joins the consensus
+        set.apply(Stake::new(&tmint4, aleo1.1, 3).unwrap());
+        let updates = set.consensus_updates();
+        assert_eq!(2, updates.len());
+        assert!(updates.contains(&(validators[0].pub_key, 0)));
+        assert!(updates.contains(&(validator4.pub_key, 45)));
+    }
+
+    #[test]
+    fn change_reward_address() {
+        let (tmint1, private_key1) = tendermint_keys(1);
+        let aleo1 = account_keys();
+        let aleo2 = account_keys();
+        let validator1 = Validator::from_str(&tmint1, &aleo1.1.to_string(), 10).unwrap();
+
+        let tempfile = NamedTempFile::new("validators").unwrap();
+        let mut set = ValidatorSet::load_or_create(tempfile.path());
+        set.replace(vec![validator1.clone()]);
+
+        let change = |key_signatures| {
+            ValidatorChange::new(
+                StakerRequest::new(&tmint1, aleo1.1).unwrap(),
+                ChangeKind::RewardAddress(aleo2.1),
+                key_signatures,
+            )
+        };
+
+        // the signature needs to be over the requested change
+        let message = ValidatorChange::reward_address_message(&aleo1.1, &aleo1.1);
+        let signature = KeySignature::new(&private_key1, &message).unwrap();
+        let error = set.validate_change(&change(vec![signature])).unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("invalid validator key signature"));
+
+        let message = ValidatorChange::reward_address_message(&aleo1.1, &aleo2.1);
+        let signature = KeySignature::new(&private_key1, &message).unwrap();
+        let change = change(vec![signature]);
+        set.validate_change(&change).unwrap();
+        set.change(change);
+
+        let mut votes = HashMap::new();
+        votes.insert(validator1.address(), 10);
+        set.begin_block(&validator1.address(), votes, 1);
+        let rewards = set.block_rewards();
+        assert_eq!(0, earned(&aleo1, &rewards));
+        assert_eq!(DEFAULT_BLOCK_REWARD, earned(&aleo2, &rewards));
+    }
+
+    /// Return a base64 encoded tendermint public key and keypair, generated from the given seed.
+    fn tendermint_keys(seed: u8) -> (String, String) {
+        let secret = ed25519_dalek::SecretKey::from_bytes(&[seed; 32]).unwrap();
+        let public = ed25519_dalek::PublicKey::from(&secret);
+        let keypair = ed25519_dalek::Keypair { secret, public };
+        (
+            base64::encode(public.as_bytes()),
+            base64::encode(keypair.to_bytes()),
+        )
+    }
+
     pub fn account_keys() -> (vm::ViewKey, vm::Address) {
         let private_key = vm::PrivateKey::new(&mut rand::thread_rng()).unwrap();
         let view_key = vm::ViewKey::try_from(&private_key).unwrap();
@@ -637,7 +2235,15 @@ mod tests {
         (view_key, address)
     }
 
-    fn decrypt_rewards(
+    fn earned(owner: &(vm::ViewKey, vm::Address), rewards: &[(vm::Address, u64)]) -> u64 {
+        rewards
+            .iter()
+            .filter(|(aleo_address, _)| *aleo_address == owner.1)
+            .map(|(_, credits)| credits)
+            .sum()
+    }
+
+    fn decrypt_credits(
         owner: &(vm::ViewKey, vm::Address),
         rewards: &[(vm::Field, vm::EncryptedRecord)],
     ) -> u64 {
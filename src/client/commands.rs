@@ -2,17 +2,25 @@ use crate::{account, tendermint};
 use anyhow::{anyhow, bail, Result};
 use clap::Parser;
 use itertools::Itertools;
+use lib::check;
+use lib::cost;
+use lib::deployment::{self, DeploymentInfo, Edition, ProgramEditions};
 use lib::program_file::ProgramFile;
+use lib::prover::ProverRequest;
 use lib::query::AbciQuery;
-use lib::transaction::Transaction;
+use lib::test_runner::{self, TestFile};
+use lib::transaction::{ExecutionAuthorization, Transaction};
+use lib::validator;
 use lib::vm::{self, compute_serial_number};
 #[allow(unused_imports)]
 use lib::vm::{EncryptedRecord, ProgramID};
-use log::debug;
+use log::{debug, warn};
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
 use serde_json::json;
 use std::collections::HashSet;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
 
 #[derive(Debug, Parser)]
@@ -35,6 +43,8 @@ pub enum Account {
     Records,
     /// Fetches the unspent records owned by the given account and calculates the final credits balance.
     Balance,
+    /// Fetches the validator rewards accrued by the account, which can be claimed with `credits claim`.
+    Rewards,
 }
 
 #[derive(Debug, Parser)]
@@ -91,6 +101,10 @@ pub enum Credits {
         /// The tendermint address of the validator that will stake the credits.
         #[clap()]
         validator: String,
+        /// Path to the priv_validator_key.json file of the validator, used to sign the aleo address of the
+        /// account to prove it holds the validator key. Required by the first stake on a validator.
+        #[clap(long)]
+        validator_key: Option<PathBuf>,
         /// Amount of gates to pay as fee for this execution. If omitted not fee is paid.
         #[clap(long)]
         fee: Option<u64>,
@@ -99,6 +113,8 @@ pub enum Credits {
         fee_record: Option<vm::UserInputValueType>,
     },
     /// Take credits out of a stake record, reducing the voting power of the validator.
+    /// The unstaked gates can be withdrawn after the unbonding period, discounting the
+    /// part of the stake that was slashed if the validator misbehaved.
     Unstake {
         /// The amount of gates to unstake. Should at most what this validator has already staked.
         #[clap()]
@@ -113,17 +129,132 @@ pub enum Credits {
         #[clap(long, value_parser=parse_input_record)]
         fee_record: Option<vm::UserInputValueType>,
     },
+    /// Withdraw the credits unstaked from a validator whose unbonding period is over,
+    /// receiving them in a new credits record.
+    Withdraw {
+        /// A stake record on the validator, which proves the request comes from the account that unstaked.
+        #[clap(value_parser=parse_input_record)]
+        record: vm::UserInputValueType,
+        /// Amount of gates to pay as fee for this execution. If omitted not fee is paid.
+        #[clap(long)]
+        fee: Option<u64>,
+        /// The record to use to subtract the fee amount. If omitted, the record with most gates in the account is used.
+        #[clap(long, value_parser=parse_input_record)]
+        fee_record: Option<vm::UserInputValueType>,
+    },
+    /// Return a validator jailed for being offline to the consensus, once its jail time is over.
+    Unjail {
+        /// A stake record on the validator, which proves the request comes from its account.
+        #[clap(value_parser=parse_input_record)]
+        record: vm::UserInputValueType,
+        /// Amount of gates to pay as fee for this execution. If omitted not fee is paid.
+        #[clap(long)]
+        fee: Option<u64>,
+        /// The record to use to subtract the fee amount. If omitted, the record with most gates in the account is used.
+        #[clap(long, value_parser=parse_input_record)]
+        fee_record: Option<vm::UserInputValueType>,
+    },
+    /// Fetches the credits supply: the gates created at genesis, the ones minted as block rewards and
+    /// the ones burned since then, along with the total in circulation.
+    Supply,
+    /// Fetches the credits accrued by the treasury from the block fees and the amount of spends made from it.
+    Treasury,
+    /// Sign a spend from the treasury with a treasury key, outputting the signature to be passed to
+    /// spend-treasury with --signature. The signature is only valid for the next spend of the treasury.
+    /// Doesn't require account credentials.
+    SignTreasurySpend {
+        /// The address of the account that will receive the credits.
+        #[clap()]
+        recipient: String,
+        /// Amount of gates to pay from the treasury.
+        #[clap()]
+        amount: u64,
+        /// Path to a priv_validator_key.json formatted file with the treasury key that signs the spend.
+        #[clap(long = "treasury-key")]
+        treasury_key: PathBuf,
+    },
+    /// Pay credits from the treasury to an account, with the signatures of enough treasury keys.
+    SpendTreasury {
+        /// The address of the account that will receive the credits.
+        #[clap()]
+        recipient: String,
+        /// Amount of gates to pay from the treasury.
+        #[clap()]
+        amount: u64,
+        /// Path to a priv_validator_key.json formatted file with a treasury key that authorizes the spend.
+        /// Can be repeated to sign with multiple keys.
+        #[clap(long = "treasury-key", required_unless_present = "signatures")]
+        treasury_keys: Vec<PathBuf>,
+        /// A signature of the spend output by sign-treasury-spend, as JSON. Can be repeated to
+        /// include the signatures of multiple keys.
+        #[clap(long = "signature", value_parser=parse_key_signature)]
+        signatures: Vec<validator::KeySignature>,
+        /// Amount of gates to pay as fee for this execution. If omitted not fee is paid.
+        #[clap(long)]
+        fee: Option<u64>,
+        /// The record to use to subtract the fee amount. If omitted, the record with most gates in the account is used.
+        #[clap(long, value_parser=parse_input_record)]
+        fee_record: Option<vm::UserInputValueType>,
+    },
+    /// Claim the validator rewards accrued by the account, receiving them in a new credits record.
+    Claim {
+        /// Amount of gates to pay as fee for this execution. If omitted not fee is paid.
+        #[clap(long)]
+        fee: Option<u64>,
+        /// The record to use to subtract the fee amount. If omitted, the record with most gates in the account is used.
+        #[clap(long, value_parser=parse_input_record)]
+        fee_record: Option<vm::UserInputValueType>,
+    },
+    /// Replace the consensus key of a validator operated by the account, e.g. if it was compromised.
+    RotateKey {
+        /// A stake record on the validator, which proves the request comes from its account.
+        #[clap(value_parser=parse_input_record)]
+        record: vm::UserInputValueType,
+        /// Path to the priv_validator_key.json file with the current key of the validator, which authorizes the rotation.
+        #[clap(long)]
+        validator_key: PathBuf,
+        /// Path to the priv_validator_key.json file with the new key of the validator.
+        #[clap(long)]
+        new_validator_key: PathBuf,
+        /// Amount of gates to pay as fee for this execution. If omitted not fee is paid.
+        #[clap(long)]
+        fee: Option<u64>,
+        /// The record to use to subtract the fee amount. If omitted, the record with most gates in the account is used.
+        #[clap(long, value_parser=parse_input_record)]
+        fee_record: Option<vm::UserInputValueType>,
+    },
+    /// Send the rewards of a validator operated by the account to a different account.
+    ChangeRewardAddress {
+        /// A stake record on the validator, which proves the request comes from its account.
+        #[clap(value_parser=parse_input_record)]
+        record: vm::UserInputValueType,
+        /// The address of the account that will receive the rewards.
+        #[clap()]
+        address: String,
+        /// Path to the priv_validator_key.json file with the key of the validator, which authorizes the change.
+        #[clap(long)]
+        validator_key: PathBuf,
+        /// Amount of gates to pay as fee for this execution. If omitted not fee is paid.
+        #[clap(long)]
+        fee: Option<u64>,
+        /// The record to use to subtract the fee amount. If omitted, the record with most gates in the account is used.
+        #[clap(long, value_parser=parse_input_record)]
+        fee_record: Option<vm::UserInputValueType>,
+    },
 }
 
 /// Commands to manage program transactions.
 #[derive(Debug, Parser)]
 pub enum Program {
-    /// Builds and sends a deployment transaction to the Blockchain, returning the Transaction ID
+    /// Builds and sends a deployment transaction to the Blockchain, returning the Transaction ID.
+    /// If the program was already deployed by the current account, a new edition of it is deployed.
     Deploy {
-        /// Path where the aleo program file resides.
+        /// Path where the aleo program file resides. If it's an .avm file built with `program build`,
+        /// its program and verifying keys are deployed as is, instead of building the keys again.
         #[clap(value_parser)]
         path: PathBuf,
-        /// Amount of gates to pay as fee for this execution. If omitted not fee is paid.
+        /// Amount of gates to pay as fee for this deployment. If omitted, the minimum fee of one gate per
+        /// program function is paid.
         #[clap(long)]
         fee: Option<u64>,
         /// The record to use to subtract the fee amount. If omitted, the record with most gates in the account is used.
@@ -150,13 +281,116 @@ pub enum Program {
         /// Run the input code locally, generating the execution proof but without sending it over to the blockchain. Displays execution and decrypted records.
         #[clap(long, short, default_value_t = false)]
         dry_run: bool,
+        /// Seed for the randomness of a dry run execution. The same inputs and seed produce the same transitions.
+        /// Only supported by the SnarkVM backend: LambdaVM draws the randomness of its proofs and record nonces
+        /// internally, so its executions can't be reproduced.
+        #[clap(long, requires = "dry_run")]
+        seed: Option<u64>,
+        /// Include every instruction evaluated by a dry run in the output, with the values of its operand and
+        /// destination registers. The LambdaVM backend only shows the values of the function inputs and outputs.
+        #[clap(long, requires = "dry_run")]
+        trace: bool,
+        /// Address of a prover process to send the proving work to, instead of proving in-process.
+        /// Only supported by the SnarkVM backend.
+        #[clap(long, env = "ALEO_PROVER", conflicts_with = "seed")]
+        prover: Option<String>,
+    },
+    /// Signs the execution of a program function and saves it to an authorization file, without generating
+    /// the proofs. The authorization can be proven without the account private key with `program prove`.
+    /// Only supported by the SnarkVM backend.
+    Authorize {
+        /// Program to execute (path or program_id).
+        #[clap(value_parser)]
+        program: String,
+        /// The function name.
+        #[clap(value_parser)]
+        function: vm::Identifier,
+        /// The function inputs.
+        #[clap(value_parser=parse_input_value)]
+        inputs: Vec<vm::UserInputValueType>,
+        /// Amount of gates to pay as fee for this execution. If omitted not fee is paid.
+        #[clap(long)]
+        fee: Option<u64>,
+        /// The record to use to subtract the fee amount. If omitted, the record with most gates in the account is used.
+        #[clap(long, value_parser=parse_input_record)]
+        fee_record: Option<vm::UserInputValueType>,
+        /// Path of the authorization file to write.
+        #[clap(long, short, default_value = "authorization.json")]
+        output: PathBuf,
+    },
+    /// Generates the proofs of an authorization file created with `program authorize` and sends the
+    /// resulting execution transaction to the blockchain. Doesn't require account credentials.
+    /// Only supported by the SnarkVM backend.
+    Prove {
+        /// Path of the authorization file.
+        #[clap(value_parser)]
+        path: PathBuf,
+        /// Generate the proofs but don't send the transaction to the blockchain.
+        #[clap(long, short, default_value_t = false)]
+        dry_run: bool,
+        /// Address of a prover process to send the proving work to, instead of proving in-process.
+        #[clap(long, env = "ALEO_PROVER")]
+        prover: Option<String>,
     },
-    /// Builds an .aleo program's keys and saves them to an .avm file
+    /// Builds an .aleo program's keys and saves them to an .avm file, reporting the cost of each function
     Build {
         /// Path to the .aleo program to build
         #[clap(value_parser)]
         path: PathBuf,
     },
+    /// Parses and type-checks an .aleo program without building its keys, reporting problems like unused
+    /// registers, public outputs that leak private inputs or functions over the VM input and output limits
+    Check {
+        /// Path to the .aleo program to check
+        #[clap(value_parser)]
+        path: PathBuf,
+    },
+    /// Runs the test cases declared in a JSON test file locally, without a blockchain node, and reports
+    /// which ones passed
+    Test {
+        /// Path to the test file
+        #[clap(value_parser)]
+        path: PathBuf,
+        /// Only evaluate the functions, without generating the execution proofs.
+        #[clap(long, default_value_t = false)]
+        skip_proofs: bool,
+    },
+    /// Reports the cost of each function of a program: constraint, variable and public input counts,
+    /// proving and verifying key sizes and the measured time to prove an execution with sample inputs.
+    Cost {
+        /// Path to the .aleo program source or to a built .avm file
+        #[clap(value_parser)]
+        path: PathBuf,
+    },
+    /// Prints the header of an .avm file: format version, backend, program source hash and verifying key hashes,
+    /// and whether the file is valid to be used with the current client.
+    Inspect {
+        /// Path to the .avm file to inspect
+        #[clap(value_parser)]
+        path: PathBuf,
+    },
+    /// Fetches the owner and the deployed editions of a program
+    Editions {
+        /// The id of the deployed program.
+        #[clap(value_parser)]
+        program_id: ProgramID,
+    },
+    /// Fetches the deployment metadata of each edition of a program: block height, transaction id,
+    /// fee, source hash, function signatures and verifying key hashes.
+    Info {
+        /// The id of the deployed program.
+        #[clap(value_parser)]
+        program_id: ProgramID,
+    },
+    /// Lists the deployed programs ordered by id, with the metadata of their latest edition.
+    List {
+        /// The program id to start listing from. Use the `next` value of a previous result to get the next page.
+        #[clap(long)]
+        from: Option<ProgramID>,
+        /// Maximum amount of programs to list.
+        #[clap(long, default_value = "20")]
+        limit: usize,
+    },
 }
 
 /// Return the status of a Transaction: Type, whether it is committed to the ledger, and the program name.
@@ -174,18 +408,75 @@ pub struct Get {
 
 impl Command {
     pub async fn run(self, url: String) -> Result<serde_json::Value> {
+        // lambdavm can't split executions in authorization and proving steps yet
+        #[cfg(feature = "lambdavm_backend")]
+        if let Command::Program(
+            Program::Authorize { .. }
+            | Program::Prove { .. }
+            | Program::Execute {
+                prover: Some(_), ..
+            },
+        ) = self
+        {
+            bail!("Proving executions separately from signing them is not supported by the lambdavm backend");
+        }
+
         let output = if let Command::Account(Account::New) = self {
             let credentials = account::Credentials::new()?;
             let path = credentials.save()?;
 
             json!({"path": path, "account": credentials})
+        } else if let Command::Program(Program::Prove {
+            path,
+            dry_run,
+            prover,
+        }) = self
+        {
+            // proving doesn't need the account credentials, so it can run on a separate machine
+            let authorization: ExecutionAuthorization =
+                serde_json::from_str(&fs::read_to_string(path)?)?;
+            let transaction = if let Some(prover) = prover {
+                let editions = authorization.editions();
+                let transitions = prove_remote(&url, prover, authorization, dry_run).await?;
+                Transaction::from_transitions(transitions, editions)?
+            } else {
+                let proving_key = get_cached_proving_key(
+                    &url,
+                    &authorization.program,
+                    &authorization.function_name,
+                    authorization.edition,
+                    dry_run,
+                )
+                .await?;
+                Transaction::prove_execution(&authorization, proving_key, &mut rand::thread_rng())?
+            };
+            if !dry_run {
+                let transaction_serialized = bincode::serialize(&transaction).unwrap();
+                tendermint::broadcast(transaction_serialized, &url).await?;
+            }
+            json!(transaction)
+        } else if let Command::Credits(Credits::SignTreasurySpend {
+            recipient,
+            amount,
+            treasury_key,
+        }) = self
+        {
+            // treasury key holders sign on their own machines, without account credentials
+            let recipient = vm::Address::from_str(&recipient)?;
+            let message = treasury_spend_message(&url, &recipient, amount).await?;
+            json!(validator::KeySignature::from_key_file(
+                &treasury_key,
+                &message
+            )?)
         } else {
             let credentials =
                 account::Credentials::load().map_err(|_| anyhow!("credentials not found"))?;
 
             match self {
-                Command::Account(Account::New) => {
-                    bail!("this shouldn't be reachable, the account new is a special case handled elsewhere")
+                Command::Account(Account::New)
+                | Command::Program(Program::Prove { .. })
+                | Command::Credits(Credits::SignTreasurySpend { .. }) => {
+                    bail!("this shouldn't be reachable, the account new, program prove and credits sign-treasury-spend are special cases handled elsewhere")
                 }
                 Command::Account(Account::Balance) => {
                     let balance = get_records(&credentials, &url).await?.iter().fold(
@@ -201,6 +492,14 @@ impl Command {
 
                     json!({ "balance": balance })
                 }
+                Command::Account(Account::Rewards) => {
+                    let query = AbciQuery::GetPendingRewards {
+                        address: credentials.address,
+                    };
+                    let rewards: u64 =
+                        bincode::deserialize(&tendermint::query(query.into(), &url).await?)?;
+                    json!({ "rewards": rewards })
+                }
                 Command::Account(Account::Records) => {
                     let records: Vec<serde_json::Value> = get_records(&credentials, &url)
                         .await?
@@ -220,9 +519,25 @@ impl Command {
                     fee,
                     fee_record,
                 }) => {
+                    // reuse the cached keys if the program was built before, so executions can use the same ones
+                    let program_file = if path.extension().map_or(false, |ext| ext == "avm") {
+                        let program_file = ProgramFile::read(&path)?;
+                        program_file.cache()?;
+                        program_file
+                    } else {
+                        ProgramFile::load_cached(&fs::read_to_string(&path)?)?
+                    };
+                    let fee = fee.or(Some(deployment::minimum_fee(&program_file.program)));
                     let fee = choose_fee_record(&credentials, &url, &fee, &fee_record, &[]).await?;
-                    let transaction =
-                        Transaction::deployment(&path, &credentials.private_key, fee)?;
+                    let edition = get_program_editions(&url, *program_file.program.id())
+                        .await?
+                        .map_or(0, |editions| editions.latest() + 1);
+                    let transaction = Transaction::deployment(
+                        &program_file,
+                        &credentials.private_key,
+                        edition,
+                        fee,
+                    )?;
                     let transaction_serialized = bincode::serialize(&transaction).unwrap();
                     tendermint::broadcast(transaction_serialized, &url).await?;
                     json!(transaction)
@@ -234,20 +549,72 @@ impl Command {
                     fee,
                     fee_record,
                     dry_run,
+                    seed,
+                    prover,
+                    trace,
                 }) => {
+                    // lambdavm doesn't take the randomness of its proofs and record nonces from an rng
+                    #[cfg(feature = "lambdavm_backend")]
+                    if seed.is_some() {
+                        bail!("--seed is not supported by the lambdavm backend, its executions can't be reproduced");
+                    }
                     let fee =
                         choose_fee_record(&credentials, &url, &fee, &fee_record, &inputs).await?;
-                    let program = match get_program(&url, &program).await? {
+                    let program_path = program;
+                    let program = match get_program(&url, &program_path).await? {
                         Some(program) => program,
-                        None => bail!("Could not find program {}", program),
+                        None => bail!("Could not find program {}", program_path),
+                    };
+                    // the trace uses its own rng from the same seed, so it sees the same values as the execution
+                    let new_rng =
+                        || seed.map_or_else(ChaCha8Rng::from_entropy, ChaCha8Rng::seed_from_u64);
+                    let trace = if trace {
+                        Some(Self::trace_execution(
+                            &program_path,
+                            &program,
+                            function,
+                            &inputs,
+                            &credentials.private_key,
+                            &mut new_rng(),
+                        )?)
+                    } else {
+                        None
+                    };
+                    // dry runs of local programs don't need a node, they assume the first edition
+                    let edition = if dry_run && Path::new(&program_path).is_file() {
+                        0
+                    } else {
+                        get_latest_edition(&url, &program).await?
+                    };
+                    let transaction = if let Some(prover) = prover {
+                        let authorization = Transaction::authorize_execution(
+                            program,
+                            function,
+                            &inputs,
+                            &credentials.private_key,
+                            edition,
+                            fee,
+                            &mut rand::thread_rng(),
+                        )?;
+                        let editions = authorization.editions();
+                        let transitions =
+                            prove_remote(&url, prover, authorization, dry_run).await?;
+                        Transaction::from_transitions(transitions, editions)?
+                    } else {
+                        let proving_key =
+                            get_cached_proving_key(&url, &program, &function, edition, dry_run)
+                                .await?;
+                        Transaction::execution(
+                            program,
+                            function,
+                            &inputs,
+                            &credentials.private_key,
+                            edition,
+                            proving_key,
+                            fee,
+                            &mut new_rng(),
+                        )?
                     };
-                    let transaction = Transaction::execution(
-                        program,
-                        function,
-                        &inputs,
-                        &credentials.private_key,
-                        fee,
-                    )?;
 
                     let mut transaction_json = json!(transaction);
                     if !dry_run {
@@ -275,14 +642,114 @@ impl Command {
                                 .insert("decrypted_records".to_string(), json!(records));
                         }
                     }
+                    if let Some(trace) = trace {
+                        transaction_json
+                            .as_object_mut()
+                            .unwrap()
+                            .insert("trace".to_string(), json!(trace));
+                    }
                     json!(transaction_json)
                 }
+                Command::Program(Program::Authorize {
+                    program,
+                    function,
+                    inputs,
+                    fee,
+                    fee_record,
+                    output,
+                }) => {
+                    let fee =
+                        choose_fee_record(&credentials, &url, &fee, &fee_record, &inputs).await?;
+                    let program = match get_program(&url, &program).await? {
+                        Some(program) => program,
+                        None => bail!("Could not find program {}", program),
+                    };
+                    let edition = get_latest_edition(&url, &program).await?;
+                    let authorization = Transaction::authorize_execution(
+                        program,
+                        function,
+                        &inputs,
+                        &credentials.private_key,
+                        edition,
+                        fee,
+                        &mut rand::thread_rng(),
+                    )?;
+                    fs::write(&output, serde_json::to_string(&authorization)?)?;
+                    json!({ "path": output })
+                }
                 Command::Program(Program::Build { path }) => {
                     let program_source = std::fs::read_to_string(&path)?;
                     let program_file = ProgramFile::build(&program_source)?;
                     let output_path = path.with_extension("avm");
                     program_file.save(&output_path)?;
-                    json!({ "path": output_path })
+                    program_file.cache()?;
+                    let functions = cost::program_cost(&program_file.program, &program_file.keys)?;
+                    json!({ "path": output_path, "functions": functions })
+                }
+                Command::Program(Program::Check { path }) => {
+                    let diagnostics = check::check_program(&fs::read_to_string(&path)?);
+                    json!({ "valid": !check::has_errors(&diagnostics), "diagnostics": diagnostics })
+                }
+                Command::Program(Program::Test { path, skip_proofs }) => {
+                    let test_file: TestFile = serde_json::from_str(&fs::read_to_string(&path)?)?;
+                    let program_path = path
+                        .parent()
+                        .unwrap_or_else(|| Path::new("."))
+                        .join(&test_file.program);
+                    let source = fs::read_to_string(&program_path)?;
+                    let program_file = if skip_proofs {
+                        None
+                    } else {
+                        Some(ProgramFile::load_cached(&source)?)
+                    };
+                    let program = vm::generate_program(&source)?;
+                    let keys = program_file.as_ref().map(|program_file| &program_file.keys);
+                    json!(test_runner::run_tests(&program, keys, &test_file.tests)?)
+                }
+                Command::Program(Program::Cost { path }) => {
+                    let program_file = if path.extension().map_or(false, |ext| ext == "avm") {
+                        ProgramFile::read(&path)?
+                    } else {
+                        ProgramFile::load_cached(&fs::read_to_string(&path)?)?
+                    };
+                    let functions = cost::program_cost(&program_file.program, &program_file.keys)?;
+                    json!({ "program": program_file.program.id(), "functions": functions })
+                }
+                Command::Program(Program::Inspect { path }) => {
+                    let header = ProgramFile::inspect(&path)?;
+                    let error = ProgramFile::read(&path).err().map(|e| e.to_string());
+                    json!({ "header": header, "valid": error.is_none(), "error": error })
+                }
+                Command::Program(Program::Editions { program_id }) => {
+                    match get_program_editions(&url, program_id).await? {
+                        Some(editions) => json!(editions),
+                        None => bail!("Could not find program {}", program_id),
+                    }
+                }
+                Command::Program(Program::Info { program_id }) => {
+                    let result =
+                        tendermint::query(AbciQuery::GetProgramInfo { program_id }.into(), &url)
+                            .await?;
+                    match bincode::deserialize::<Option<Vec<DeploymentInfo>>>(&result)? {
+                        Some(editions) => json!({ "program": program_id, "editions": editions }),
+                        None => bail!("Could not find program {}", program_id),
+                    }
+                }
+                Command::Program(Program::List { from, limit }) => {
+                    let result =
+                        tendermint::query(AbciQuery::ListPrograms { from, limit }.into(), &url)
+                            .await?;
+                    let (programs, next): (
+                        Vec<(ProgramID, Edition, DeploymentInfo)>,
+                        Option<ProgramID>,
+                    ) = bincode::deserialize(&result)?;
+                    let programs: Vec<_> = programs
+                        .into_iter()
+                        .map(|(program_id, edition, info)| {
+                            json!({ "program": program_id, "edition": edition, "info": info })
+                        })
+                        .collect();
+                    json!({ "programs": programs, "next": next })
                 }
                 Command::Credits(Credits::Transfer {
                     input_record,
@@ -323,6 +790,7 @@ impl Command {
                     amount,
                     record,
                     validator,
+                    validator_key,
                     fee,
                     fee_record,
                 }) => {
@@ -338,8 +806,23 @@ impl Command {
                         vm::u64_to_value(validator_split[3]),
                     ];
 
-                    run_credits_command(&credentials, &url, "stake", &inputs, &fee, &fee_record)
-                        .await?
+                    let mut transaction = credits_transaction(
+                        &credentials,
+                        &url,
+                        "stake",
+                        &inputs,
+                        &fee,
+                        &fee_record,
+                    )
+                    .await?;
+                    if let Some(path) = validator_key {
+                        let key_signature = validator::KeySignature::from_key_file(
+                            &path,
+                            &credentials.address.to_string(),
+                        )?;
+                        transaction = transaction.with_key_signature(key_signature)?;
+                    }
+                    broadcast_transaction(transaction, &url).await?
                 }
                 Command::Credits(Credits::Unstake {
                     amount,
@@ -351,6 +834,152 @@ impl Command {
                     run_credits_command(&credentials, &url, "unstake", &inputs, &fee, &fee_record)
                         .await?
                 }
+                Command::Credits(Credits::Withdraw {
+                    record,
+                    fee,
+                    fee_record,
+                }) => {
+                    let inputs = [record.clone()];
+                    run_credits_command(&credentials, &url, "withdraw", &inputs, &fee, &fee_record)
+                        .await?
+                }
+                Command::Credits(Credits::Unjail {
+                    record,
+                    fee,
+                    fee_record,
+                }) => {
+                    let inputs = [record.clone()];
+                    run_credits_command(&credentials, &url, "unjail", &inputs, &fee, &fee_record)
+                        .await?
+                }
+                Command::Credits(Credits::Supply) => {
+                    let supply: validator::Supply = bincode::deserialize(
+                        &tendermint::query(AbciQuery::GetSupply.into(), &url).await?,
+                    )?;
+                    json!({
+                        "genesis": supply.genesis,
+                        "minted": supply.minted,
+                        "burned": supply.burned,
+                        "total": supply.total(),
+                    })
+                }
+                Command::Credits(Credits::Treasury) => {
+                    let treasury: validator::Treasury = bincode::deserialize(
+                        &tendermint::query(AbciQuery::GetTreasury.into(), &url).await?,
+                    )?;
+                    json!(treasury)
+                }
+                Command::Credits(Credits::SpendTreasury {
+                    recipient,
+                    amount,
+                    treasury_keys,
+                    signatures,
+                    fee,
+                    fee_record,
+                }) => {
+                    let recipient = vm::Address::from_str(&recipient)?;
+                    let message = treasury_spend_message(&url, &recipient, amount).await?;
+
+                    let inputs = [
+                        vm::UserInputValueType::from_str(&recipient.to_string())?,
+                        vm::u64_to_value(amount),
+                    ];
+                    let mut transaction = credits_transaction(
+                        &credentials,
+                        &url,
+                        "spend_treasury",
+                        &inputs,
+                        &fee,
+                        &fee_record,
+                    )
+                    .await?;
+                    for path in treasury_keys {
+                        let key_signature =
+                            validator::KeySignature::from_key_file(&path, &message)?;
+                        transaction = transaction.with_key_signature(key_signature)?;
+                    }
+                    for key_signature in signatures {
+                        transaction = transaction.with_key_signature(key_signature)?;
+                    }
+                    broadcast_transaction(transaction, &url).await?
+                }
+                Command::Credits(Credits::Claim { fee, fee_record }) => {
+                    let inputs = [vm::UserInputValueType::from_str(
+                        &credentials.address.to_string(),
+                    )?];
+                    run_credits_command(
+                        &credentials,
+                        &url,
+                        "claim_rewards",
+                        &inputs,
+                        &fee,
+                        &fee_record,
+                    )
+                    .await?
+                }
+                Command::Credits(Credits::RotateKey {
+                    record,
+                    validator_key,
+                    new_validator_key,
+                    fee,
+                    fee_record,
+                }) => {
+                    // both the current and the new key sign the rotation
+                    let (new_key, new_private_key) = validator::read_key_file(&new_validator_key)?;
+                    let message = validator::ValidatorChange::rotate_key_message(
+                        &credentials.address,
+                        &new_key,
+                    );
+                    let key_signature =
+                        validator::KeySignature::from_key_file(&validator_key, &message)?;
+                    let new_key_signature =
+                        validator::KeySignature::new(&new_private_key, &message)?;
+
+                    let inputs = [record.clone()];
+                    let transaction = credits_transaction(
+                        &credentials,
+                        &url,
+                        "rotate_key",
+                        &inputs,
+                        &fee,
+                        &fee_record,
+                    )
+                    .await?
+                    .with_key_signature(key_signature)?
+                    .with_key_signature(new_key_signature)?;
+                    broadcast_transaction(transaction, &url).await?
+                }
+                Command::Credits(Credits::ChangeRewardAddress {
+                    record,
+                    address,
+                    validator_key,
+                    fee,
+                    fee_record,
+                }) => {
+                    let reward_address = vm::Address::from_str(&address)?;
+                    let message = validator::ValidatorChange::reward_address_message(
+                        &credentials.address,
+                        &reward_address,
+                    );
+                    let key_signature =
+                        validator::KeySignature::from_key_file(&validator_key, &message)?;
+
+                    let inputs = [
+                        record.clone(),
+                        vm::UserInputValueType::from_str(&reward_address.to_string())?,
+                    ];
+                    let transaction = credits_transaction(
+                        &credentials,
+                        &url,
+                        "change_reward_address",
+                        &inputs,
+                        &fee,
+                        &fee_record,
+                    )
+                    .await?
+                    .with_key_signature(key_signature)?;
+                    broadcast_transaction(transaction, &url).await?
+                }
                 Command::Get(Get {
                     transaction_id,
                     decrypt,
@@ -375,6 +1004,27 @@ impl Command {
         Ok(output)
     }
 
+    /// Evaluate the function printing each instruction with its register values to stderr, and fail
+    /// pointing to the source location of the failing instruction, if any.
+    fn trace_execution(
+        program_path: &str,
+        program: &vm::Program,
+        function: vm::Identifier,
+        inputs: &[vm::UserInputValueType],
+        private_key: &vm::PrivateKey,
+        rng: &mut ChaCha8Rng,
+    ) -> Result<Vec<String>> {
+        // locations refer to the program file if one was given, otherwise to the deployed source
+        let source = fs::read_to_string(program_path).unwrap_or_else(|_| program.to_string());
+        let function_name = function.to_string();
+
+        let trace = vm::trace(program, function, inputs, private_key, rng)?;
+        if let Some(error) = trace.error_message(program_path, &source, &function_name) {
+            bail!(error);
+        }
+        Ok(trace.lines(program_path, &source, &function_name))
+    }
+
     fn decrypt_records(
         transaction: &Transaction,
         credentials: account::Credentials,
@@ -398,16 +1048,41 @@ async fn run_credits_command(
     fee_amount: &Option<u64>,
     fee_record: &Option<vm::UserInputValueType>,
 ) -> Result<serde_json::Value> {
+    let transaction =
+        credits_transaction(credentials, url, function, inputs, fee_amount, fee_record).await?;
+    broadcast_transaction(transaction, url).await
+}
+
+async fn credits_transaction(
+    credentials: &account::Credentials,
+    url: &str,
+    function: &str,
+    inputs: &[vm::UserInputValueType],
+    fee_amount: &Option<u64>,
+    fee_record: &Option<vm::UserInputValueType>,
+) -> Result<Transaction> {
     let fee = choose_fee_record(credentials, url, fee_amount, fee_record, inputs).await?;
     let function_identifier = vm::Identifier::from_str(function)?;
-    let transaction =
-        Transaction::credits_execution(function_identifier, inputs, &credentials.private_key, fee)?;
+    Transaction::credits_execution(
+        function_identifier,
+        inputs,
+        &credentials.private_key,
+        fee,
+        &mut rand::thread_rng(),
+    )
+}
+
+async fn broadcast_transaction(transaction: Transaction, url: &str) -> Result<serde_json::Value> {
     let transaction_serialized = bincode::serialize(&transaction).unwrap();
     tendermint::broadcast(transaction_serialized, url).await?;
     Ok(json!(transaction))
 }
 
 /// Extends the snarkvm's default argument parsing to support using record ciphertexts as record inputs
+fn parse_key_signature(input: &str) -> Result<validator::KeySignature> {
+    serde_json::from_str(input).map_err(|e| anyhow!("invalid key signature {input}: {e}"))
+}
+
 fn parse_input_value(input: &str) -> Result<vm::UserInputValueType> {
     // try parsing an encrypted record string
     if input.starts_with("record") {
@@ -515,6 +1190,123 @@ async fn get_program_from_blockchain(
     Ok(program)
 }
 
+async fn get_program_editions(
+    url: &str,
+    program_id: vm::ProgramID,
+) -> Result<Option<ProgramEditions>> {
+    let result =
+        tendermint::query(AbciQuery::GetProgramEditions { program_id }.into(), url).await?;
+    let editions: Option<ProgramEditions> = bincode::deserialize(&result)?;
+    Ok(editions)
+}
+
+/// Return the proving key of the given function from the client cache, if the program was built or deployed
+/// from this client. If the program is deployed, the cached key is discarded when its verifying key differs
+/// from the one on chain, since the resulting proofs would be rejected, so the keys are built again.
+/// Dry runs don't send the proofs to the blockchain, so they use the cached key without querying it.
+async fn get_cached_proving_key(
+    url: &str,
+    program: &vm::Program,
+    function: &vm::Identifier,
+    edition: Edition,
+    dry_run: bool,
+) -> Result<Option<vm::ProvingKey>> {
+    let (proving_key, verifying_key) =
+        match ProgramFile::cached(program).and_then(|mut file| file.keys.map.remove(function)) {
+            Some(keys) => keys,
+            None => return Ok(None),
+        };
+    if dry_run {
+        return Ok(Some(proving_key));
+    }
+
+    match get_deployed_verifying_key_hash(url, program, function, edition).await? {
+        Some(deployed_hash) if deployed_hash != deployment::verifying_key_hash(&verifying_key)? => {
+            warn!(
+                "the cached verifying key of {} function {function} differs from the one deployed in edition {edition}, rebuilding the keys",
+                program.id()
+            );
+            Ok(None)
+        }
+        _ => Ok(Some(proving_key)),
+    }
+}
+
+/// Return the hash of the verifying key of the given function deployed in the given edition,
+/// if the program is deployed.
+async fn get_deployed_verifying_key_hash(
+    url: &str,
+    program: &vm::Program,
+    function: &vm::Identifier,
+    edition: Edition,
+) -> Result<Option<String>> {
+    let result = tendermint::query(
+        AbciQuery::GetProgramInfo {
+            program_id: *program.id(),
+        }
+        .into(),
+        url,
+    )
+    .await?;
+    Ok(
+        bincode::deserialize::<Option<Vec<DeploymentInfo>>>(&result)?
+            .and_then(|mut editions| {
+                let edition = edition as usize;
+                (edition < editions.len()).then(|| editions.swap_remove(edition))
+            })
+            .and_then(|info| {
+                info.verifying_key_hashes
+                    .get(&function.to_string())
+                    .cloned()
+            }),
+    )
+}
+
+/// Send an execution authorization to the prover process at the given address, along with the
+/// deployed verifying key hash the prover keys need to match, and wait for the proven transitions.
+async fn prove_remote(
+    url: &str,
+    prover: String,
+    authorization: ExecutionAuthorization,
+    dry_run: bool,
+) -> Result<Vec<vm::Transition>> {
+    let verifying_key_hash = if dry_run {
+        None
+    } else {
+        get_deployed_verifying_key_hash(
+            url,
+            &authorization.program,
+            &authorization.function_name,
+            authorization.edition,
+        )
+        .await?
+    };
+    let request = ProverRequest {
+        authorization,
+        verifying_key_hash,
+    };
+    // the prover connection blocks until the proofs are generated
+    tokio::task::spawn_blocking(move || lib::prover::prove_remote(&prover, &request)).await?
+}
+
+/// Return the message the treasury keys sign to authorize the next spend from the treasury, which
+/// includes the chain id and the current amount of spends so the signatures can't be replayed.
+async fn treasury_spend_message(url: &str, recipient: &vm::Address, amount: u64) -> Result<String> {
+    let treasury: validator::Treasury =
+        bincode::deserialize(&tendermint::query(AbciQuery::GetTreasury.into(), url).await?)?;
+    Ok(validator::TreasurySpend::message(
+        &treasury, recipient, amount,
+    ))
+}
+
+/// Executions target the latest deployed edition of the program. If the program is not
+/// deployed, the first edition is assumed.
+async fn get_latest_edition(url: &str, program: &vm::Program) -> Result<Edition> {
+    Ok(get_program_editions(url, *program.id())
+        .await?
+        .map_or(0, |editions| editions.latest()))
+}
+
 /// Select one of the records to be used to pay the requested fee,
 /// that is not already being used as input to the execution.
 /// The biggest record is chosen as the default under the assumption
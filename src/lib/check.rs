@@ -0,0 +1,710 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::vm;
+use itertools::Itertools;
+use serde::Serialize;
+
+/// A problem found in a program source by `check_program`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    /// The function the problem was found in, if any.
+    pub function: Option<String>,
+    pub message: String,
+    /// The statement of the source the problem was found in. Errors reported by the VM have no span.
+    pub span: Option<Span>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    /// The program can't be deployed or its functions can't be executed.
+    Error,
+    /// The program is valid, but probably doesn't do what's intended.
+    Warning,
+}
+
+/// A statement of a program source: its line, starting column and length in characters, starting at 1.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub struct Span {
+    pub line: usize,
+    pub column: usize,
+    pub length: usize,
+}
+
+/// Parse and type-check a program for the current VM backend, without building its keys, and lint
+/// its functions for problems that would otherwise show up at execution time or on chain:
+/// - instructions and outputs whose operands don't type-check, or that read registers before they're assigned
+/// - functions with more inputs or outputs than the VM supports
+/// - registers that are assigned but never used
+/// - public outputs computed from private inputs, which leak them, or hashed from them, which leak
+///   them if they can be guessed
+/// - subtractions from the gates of a record, which fail the execution if they underflow
+pub fn check_program(source: &str) -> Vec<Diagnostic> {
+    let mut diagnostics = match vm::generate_program(source) {
+        Ok(program) => vm::check_program(&program)
+            .err()
+            .map(|error| Diagnostic::vm_error(error.to_string()))
+            .into_iter()
+            .collect(),
+        Err(error) => vec![Diagnostic::vm_error(error.to_string())],
+    };
+
+    let records = parse_records(source);
+    for function in parse_functions(source) {
+        diagnostics.extend(function.check_types(&records));
+        diagnostics.extend(function.lint());
+    }
+    diagnostics
+}
+
+/// Return whether the diagnostics include errors, as opposed to just warnings.
+pub fn has_errors(diagnostics: &[Diagnostic]) -> bool {
+    diagnostics
+        .iter()
+        .any(|diagnostic| diagnostic.severity == Severity::Error)
+}
+
+impl Diagnostic {
+    fn vm_error(message: String) -> Self {
+        Self {
+            severity: Severity::Error,
+            function: None,
+            message,
+            span: None,
+        }
+    }
+}
+
+/// A register declaration or use: an input, an output or an instruction operand or destination.
+#[derive(Debug)]
+struct Statement {
+    span: Span,
+    opcode: String,
+    operands: Vec<String>,
+    destinations: Vec<String>,
+    /// The declared type of inputs and outputs, e.g. `u64.private`, or the type an instruction casts to.
+    value_type: Option<String>,
+}
+
+#[derive(Debug)]
+struct Function {
+    name: String,
+    span: Span,
+    inputs: Vec<Statement>,
+    instructions: Vec<Statement>,
+    outputs: Vec<Statement>,
+}
+
+/// The type of each entry of a record declaration, e.g. `u64` for `amount as u64.private`, by entry name.
+type RecordEntries = HashMap<String, String>;
+
+/// Parse the record declarations of the source, by record name.
+fn parse_records(source: &str) -> HashMap<String, RecordEntries> {
+    let mut records = HashMap::new();
+    let mut current = None;
+
+    for line in source.lines() {
+        let code = line.split("//").next().unwrap_or_default();
+        let tokens: Vec<&str> = code
+            .trim()
+            .trim_end_matches(|c| c == ';' || c == ':')
+            .split_whitespace()
+            .collect();
+        match tokens.as_slice() {
+            ["record", name] => current = Some(name.to_string()),
+            [entry, "as", value_type] if current.is_some() => {
+                records
+                    .entry(current.clone().unwrap())
+                    .or_insert_with(RecordEntries::new)
+                    .insert(entry.to_string(), literal_or_record_type(value_type));
+            }
+            [] => {}
+            _ => current = None,
+        }
+    }
+    records
+}
+
+/// Split the source into its functions, parsing the statements of each one. Closures, finalize
+/// blocks and declarations other than functions are skipped.
+fn parse_functions(source: &str) -> Vec<Function> {
+    let mut functions: Vec<Function> = Vec::new();
+    let mut in_function = false;
+
+    for (number, line) in source.lines().enumerate() {
+        let code = line.split("//").next().unwrap_or_default();
+        let text = code.trim();
+        if text.is_empty() {
+            continue;
+        }
+        let span = Span {
+            line: number + 1,
+            column: code.len() - code.trim_start().len() + 1,
+            length: text.len(),
+        };
+
+        let tokens: Vec<&str> = text
+            .trim_end_matches(|c| c == ';' || c == ':')
+            .split_whitespace()
+            .collect();
+        match tokens[0] {
+            "function" => {
+                in_function = true;
+                functions.push(Function {
+                    name: tokens.get(1).unwrap_or(&"").to_string(),
+                    span,
+                    inputs: vec![],
+                    instructions: vec![],
+                    outputs: vec![],
+                });
+            }
+            "program" | "import" | "closure" | "finalize" | "record" | "interface" | "mapping" => {
+                in_function = false
+            }
+            _ if in_function => {
+                let function = functions.last_mut().unwrap();
+                let statement = Statement::parse(span, &tokens);
+                match tokens[0] {
+                    "input" => function.inputs.push(statement),
+                    "output" => function.outputs.push(statement),
+                    _ => function.instructions.push(statement),
+                }
+            }
+            _ => {}
+        }
+    }
+    functions
+}
+
+impl Statement {
+    fn parse(span: Span, tokens: &[&str]) -> Self {
+        let opcode = tokens[0].to_string();
+        if opcode == "input" || opcode == "output" {
+            // e.g. `input r0 as u64.private`
+            return Self {
+                span,
+                opcode,
+                operands: tokens.get(1).map(|t| t.to_string()).into_iter().collect(),
+                destinations: vec![],
+                value_type: tokens.get(3).map(|t| t.to_string()),
+            };
+        }
+
+        // e.g. `cast r1 0u64 r0 into r2 as token.record`
+        let arguments = &tokens[1..];
+        let (operands, destinations) = match arguments.iter().position(|t| *t == "into") {
+            Some(into) => {
+                let destinations = arguments[into + 1..]
+                    .iter()
+                    .take_while(|t| **t != "as")
+                    .map(|t| t.to_string())
+                    .collect();
+                (&arguments[..into], destinations)
+            }
+            None => (arguments, vec![]),
+        };
+        let value_type = arguments
+            .iter()
+            .position(|t| *t == "as")
+            .and_then(|position| arguments.get(position + 1))
+            .map(|t| t.to_string());
+        Self {
+            span,
+            opcode,
+            operands: operands.iter().map(|t| t.to_string()).collect(),
+            destinations,
+            value_type,
+        }
+    }
+}
+
+impl Function {
+    /// Infer the types of the registers of the function from its inputs and instructions, reporting the
+    /// instructions and outputs that don't type-check. Registers of unknown type (e.g. results of calls)
+    /// are not checked, so this only catches a subset of the errors the VM reports when building the keys.
+    fn check_types(&self, records: &HashMap<String, RecordEntries>) -> Vec<Diagnostic> {
+        let mut diagnostics = Vec::new();
+        let mut report = |span, message| {
+            diagnostics.push(Diagnostic {
+                severity: Severity::Error,
+                function: Some(self.name.clone()),
+                message,
+                span: Some(span),
+            })
+        };
+
+        let mut assigned = HashSet::new();
+        let mut types = HashMap::new();
+        for input in &self.inputs {
+            if let Some(register) = input.operands.first() {
+                assigned.insert(register.as_str());
+                if let Some(value_type) = &input.value_type {
+                    types.insert(register.as_str(), literal_or_record_type(value_type));
+                }
+            }
+        }
+
+        for instruction in &self.instructions {
+            for register in instruction.operands.iter().filter_map(|r| register(r)) {
+                if !assigned.contains(register) {
+                    report(
+                        instruction.span,
+                        format!("register {register} is used before it's assigned"),
+                    );
+                }
+            }
+
+            let operand_types: Vec<Option<String>> = instruction
+                .operands
+                .iter()
+                .map(|operand| operand_type(operand, &types, records))
+                .collect();
+            let destination_type = destination_type(
+                &instruction.opcode,
+                &operand_types,
+                instruction.value_type.as_deref(),
+            )
+            .unwrap_or_else(|message| {
+                report(instruction.span, message);
+                None
+            });
+
+            for destination in &instruction.destinations {
+                assigned.insert(destination.as_str());
+                if let Some(destination_type) = &destination_type {
+                    types.insert(destination.as_str(), destination_type.clone());
+                }
+            }
+        }
+
+        for output in &self.outputs {
+            if let (Some(operand), Some(value_type)) = (output.operands.first(), &output.value_type)
+            {
+                let declared_type = literal_or_record_type(value_type);
+                if register(operand).map_or(false, |register| !assigned.contains(register)) {
+                    report(output.span, format!("output {operand} is never assigned"));
+                } else if let Some(operand_type) = operand_type(operand, &types, records) {
+                    if operand_type != declared_type {
+                        report(
+                            output.span,
+                            format!("output {operand} is declared as {declared_type} but it's a {operand_type}"),
+                        );
+                    }
+                }
+            }
+        }
+
+        diagnostics
+    }
+
+    fn lint(&self) -> Vec<Diagnostic> {
+        let mut diagnostics = Vec::new();
+        let mut report = |severity, span, message| {
+            diagnostics.push(Diagnostic {
+                severity,
+                function: Some(self.name.clone()),
+                message,
+                span: Some(span),
+            })
+        };
+
+        if self.inputs.len() > vm::MAX_INPUTS {
+            report(
+                Severity::Error,
+                self.span,
+                format!(
+                    "function {} has {} inputs, the maximum is {}",
+                    self.name,
+                    self.inputs.len(),
+                    vm::MAX_INPUTS
+                ),
+            );
+        }
+        if self.outputs.len() > vm::MAX_OUTPUTS {
+            report(
+                Severity::Error,
+                self.span,
+                format!(
+                    "function {} has {} outputs, the maximum is {}",
+                    self.name,
+                    self.outputs.len(),
+                    vm::MAX_OUTPUTS
+                ),
+            );
+        }
+
+        // registers derived from private inputs. Commitments don't reveal their inputs, and hashes
+        // only do if the inputs can be guessed, so hashed registers are tracked apart.
+        let mut private = HashSet::new();
+        let mut hashed = HashSet::new();
+        for input in &self.inputs {
+            let value_type = input.value_type.as_deref().unwrap_or_default();
+            if value_type.ends_with(".private") || value_type.ends_with(".record") {
+                private.extend(input.operands.iter().filter_map(|r| register(r)));
+            }
+        }
+        for instruction in &self.instructions {
+            let reads = |registers: &HashSet<&str>| {
+                instruction
+                    .operands
+                    .iter()
+                    .filter_map(|operand| register(operand))
+                    .any(|register| registers.contains(register))
+            };
+            let destinations = instruction.destinations.iter().map(String::as_str);
+            if instruction.opcode.starts_with("commit.") {
+                // commitments are randomized, so they don't reveal their inputs
+            } else if instruction.opcode.starts_with("hash.") {
+                if reads(&private) || reads(&hashed) {
+                    hashed.extend(destinations);
+                }
+            } else if reads(&private) {
+                private.extend(destinations);
+            } else if reads(&hashed) {
+                hashed.extend(destinations);
+            }
+
+            if instruction.opcode == "sub"
+                && instruction
+                    .operands
+                    .first()
+                    .map_or(false, |operand| operand.ends_with(".gates"))
+            {
+                report(
+                    Severity::Warning,
+                    instruction.span,
+                    format!(
+                        "subtracting from {} can underflow, failing the execution if the record doesn't have enough gates",
+                        instruction.operands[0]
+                    ),
+                );
+            }
+        }
+        for output in &self.outputs {
+            let value_type = output.value_type.as_deref().unwrap_or_default();
+            if let Some(register) = output.operands.first().and_then(|r| register(r)) {
+                if value_type.ends_with(".public") && private.contains(register) {
+                    report(
+                        Severity::Warning,
+                        output.span,
+                        format!("public output {register} is computed from private inputs, which leaks them"),
+                    );
+                } else if value_type.ends_with(".public") && hashed.contains(register) {
+                    report(
+                        Severity::Warning,
+                        output.span,
+                        format!("public output {register} is a hash of private inputs, which leaks them if they can be guessed (use a commitment instead)"),
+                    );
+                }
+            }
+        }
+
+        // registers assigned by inputs and instructions that are never read afterwards
+        let mut assigned: HashMap<&str, Span> = HashMap::new();
+        let mut order = Vec::new();
+        for statement in self.inputs.iter().chain(&self.instructions) {
+            let registers = if statement.opcode == "input" {
+                &statement.operands
+            } else {
+                &statement.destinations
+            };
+            for register in registers {
+                assigned.insert(register, statement.span);
+                order.push(register.as_str());
+            }
+        }
+        let used: HashSet<&str> = self
+            .instructions
+            .iter()
+            .chain(&self.outputs)
+            .flat_map(|statement| statement.operands.iter())
+            .filter_map(|operand| register(operand))
+            .collect();
+        for register in order {
+            if !used.contains(register) {
+                report(
+                    Severity::Warning,
+                    assigned[register],
+                    format!("register {register} is assigned but never used"),
+                );
+            }
+        }
+
+        diagnostics
+    }
+}
+
+/// Types of the literals that can be written as a number followed by the type, e.g. `1u64`.
+const NUMERIC_TYPES: [&str; 13] = [
+    "u8", "u16", "u32", "u64", "u128", "i8", "i16", "i32", "i64", "i128", "field", "group",
+    "scalar",
+];
+
+/// Return the type of a declared value without its visibility, e.g. `u64` for `u64.private`.
+/// Record types keep their suffix, e.g. `token.record`.
+fn literal_or_record_type(value_type: &str) -> String {
+    [".private", ".public", ".constant"]
+        .iter()
+        .find_map(|visibility| value_type.strip_suffix(visibility))
+        .unwrap_or(value_type)
+        .to_string()
+}
+
+/// Return the type of an operand: of the literal, of the register or of the record entry it references,
+/// or None if it's not known.
+fn operand_type(
+    operand: &str,
+    types: &HashMap<&str, String>,
+    records: &HashMap<String, RecordEntries>,
+) -> Option<String> {
+    if operand == "true" || operand == "false" {
+        return Some("boolean".to_string());
+    }
+    if operand == "self.caller" || operand.starts_with("aleo1") {
+        return Some("address".to_string());
+    }
+    let number = operand.strip_prefix('-').unwrap_or(operand);
+    if let Some(suffix) = number.find(|c: char| !c.is_ascii_digit() && c != '_') {
+        if suffix > 0 && NUMERIC_TYPES.contains(&&number[suffix..]) {
+            return Some(number[suffix..].to_string());
+        }
+    }
+
+    let register_type = types.get(register(operand)?)?;
+    match operand.split_once('.') {
+        None => Some(register_type.clone()),
+        Some((_, entry)) => {
+            let record = register_type.strip_suffix(".record")?;
+            records.get(record)?.get(entry).cloned()
+        }
+    }
+}
+
+/// Return the type of the destination of an instruction given the types of its operands, or an error
+/// message if they don't type-check. Operands of unknown type are not checked, and instructions that are
+/// not known, or whose result type can't be inferred, yield an unknown type.
+fn destination_type(
+    opcode: &str,
+    operand_types: &[Option<String>],
+    cast_type: Option<&str>,
+) -> Result<Option<String>, String> {
+    // the operands of most instructions must have the same type, which is also the type of the result
+    let same_type = |operand_types: &[Option<String>]| {
+        let known: Vec<&String> = operand_types.iter().flatten().unique().collect();
+        if known.len() > 1 {
+            Err(format!(
+                "operands of {opcode} have different types: {}",
+                known.iter().join(" and ")
+            ))
+        } else {
+            Ok(known.first().map(|operand_type| operand_type.to_string()))
+        }
+    };
+
+    match opcode {
+        "add" | "add.w" | "sub" | "sub.w" | "mul" | "mul.w" | "div" | "div.w" | "rem" | "rem.w"
+        | "mod" | "and" | "or" | "xor" | "nand" | "nor" => same_type(operand_types),
+        "abs" | "abs.w" | "neg" | "not" | "double" | "square" | "inv" | "sqrt" | "shl"
+        | "shl.w" | "shr" | "shr.w" | "pow" | "pow.w" => {
+            Ok(operand_types.first().cloned().flatten())
+        }
+        "gt" | "gte" | "lt" | "lte" | "is.eq" | "is.neq" => {
+            same_type(operand_types).map(|_| Some("boolean".to_string()))
+        }
+        "assert.eq" | "assert.neq" => same_type(operand_types).map(|_| None),
+        "ternary" => match operand_types.first().cloned().flatten() {
+            Some(condition_type) if condition_type != "boolean" => Err(format!(
+                "the condition of ternary is a {condition_type}, not a boolean"
+            )),
+            _ => same_type(operand_types.get(1..).unwrap_or_default()),
+        },
+        "cast" => Ok(cast_type.map(literal_or_record_type)),
+        _ if opcode.starts_with("hash.") || opcode.starts_with("commit.") => Ok(Some(
+            cast_type.map_or("field".to_string(), literal_or_record_type),
+        )),
+        _ => Ok(None),
+    }
+}
+
+/// Return the register referenced by an operand, e.g. `r0` for `r0.gates`, or None for literals
+/// and other operands.
+fn register(operand: &str) -> Option<&str> {
+    let register = operand.split('.').next()?;
+    let index = register.strip_prefix('r')?;
+    (!index.is_empty() && index.chars().all(|c| c.is_ascii_digit())).then_some(register)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The diagnostics of the linter, leaving out the ones reported by the VM, which have no span.
+    fn lints(source: &str) -> Vec<Diagnostic> {
+        check_program(source)
+            .into_iter()
+            .filter(|diagnostic| diagnostic.span.is_some())
+            .collect()
+    }
+
+    fn messages(source: &str) -> Vec<(Severity, usize, String)> {
+        lints(source)
+            .into_iter()
+            .map(|diagnostic| {
+                (
+                    diagnostic.severity,
+                    diagnostic.span.map_or(0, |span| span.line),
+                    diagnostic.message,
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn valid_program() {
+        let source = r#"program token.aleo;
+
+record token:
+    owner as address.private;
+    gates as u64.private;
+    amount as u64.private;
+
+function mint:
+    input r0 as u64.private;
+    input r1 as address.private;
+    cast r1 0u64 r0 into r2 as token.record;
+    output r2 as token.record;
+"#;
+        assert!(check_program(source).is_empty());
+    }
+
+    #[test]
+    fn type_errors() {
+        let source = r#"program types.aleo;
+
+record token:
+    owner as address.private;
+    gates as u64.private;
+    amount as u64.private;
+
+function main:
+    input r0 as token.record;
+    input r1 as u32.private;
+    add r0.amount r1 into r2;
+    is.eq r0.owner self.caller into r3;
+    ternary r1 r0.amount 1u64 into r4;
+    add r0.amount r9 into r5;
+    output r2 as u64.private;
+    output r3 as u64.private;
+    output r4 as u64.private;
+    output r5 as u64.private;
+"#;
+        assert_eq!(
+            vec![
+                (
+                    Severity::Error,
+                    11,
+                    "operands of add have different types: u64 and u32".to_string()
+                ),
+                (
+                    Severity::Error,
+                    13,
+                    "the condition of ternary is a u32, not a boolean".to_string()
+                ),
+                (
+                    Severity::Error,
+                    14,
+                    "register r9 is used before it's assigned".to_string()
+                ),
+                (
+                    Severity::Error,
+                    16,
+                    "output r3 is declared as u64 but it's a boolean".to_string()
+                ),
+            ],
+            messages(source)
+        );
+    }
+
+    #[test]
+    fn invalid_program() {
+        let diagnostics = check_program("program invalid.aleo;\n\nfunction main:\n    input r0;\n");
+        assert!(has_errors(&diagnostics));
+        assert!(diagnostics[0].span.is_none());
+    }
+
+    #[test]
+    fn lint_functions() {
+        let source = r#"program lints.aleo;
+
+record token:
+    owner as address.private;
+    gates as u64.private;
+
+function burn:
+    input r0 as token.record;
+    input r1 as u64.private;
+    input r2 as u64.public;
+    sub r0.gates r1 into r3;
+    hash.bhp256 r1 into r4;
+    add r1 1u64 into r5;
+    cast r0.owner r3 into r6 as token.record;
+    output r6 as token.record;
+    output r4 as field.public;
+    output r5 as u64.public;
+"#;
+        let diagnostics = lints(source);
+        assert!(!has_errors(&diagnostics));
+        assert_eq!(
+            vec![
+                (
+                    Severity::Warning,
+                    11,
+                    "subtracting from r0.gates can underflow, failing the execution if the record doesn't have enough gates".to_string()
+                ),
+                (
+                    Severity::Warning,
+                    16,
+                    "public output r4 is a hash of private inputs, which leaks them if they can be guessed (use a commitment instead)".to_string()
+                ),
+                (
+                    Severity::Warning,
+                    17,
+                    "public output r5 is computed from private inputs, which leaks them".to_string()
+                ),
+                (
+                    Severity::Warning,
+                    10,
+                    "register r2 is assigned but never used".to_string()
+                ),
+            ],
+            messages(source)
+        );
+        assert_eq!(
+            Some(Span {
+                line: 11,
+                column: 5,
+                length: 24
+            }),
+            diagnostics[0].span
+        );
+    }
+
+    #[test]
+    fn too_many_inputs() {
+        let inputs: String = (0..=vm::MAX_INPUTS)
+            .map(|i| format!("    input r{i} as u64.public;\n"))
+            .collect();
+        let source = format!(
+            "program inputs.aleo;\n\nfunction main:\n{inputs}    add r0 r{} into r100;\n    output r100 as u64.public;\n",
+            vm::MAX_INPUTS
+        );
+
+        let diagnostics = check_program(&source);
+        assert!(has_errors(&diagnostics));
+        assert!(diagnostics
+            .iter()
+            .any(|diagnostic| diagnostic.severity == Severity::Error
+                && diagnostic.span.map(|span| span.line) == Some(3)));
+    }
+}
@@ -0,0 +1,112 @@
+use std::str::FromStr;
+use std::time::Instant;
+
+use crate::vm;
+use anyhow::Result;
+use indexmap::IndexMap;
+use log::debug;
+use serde::{Deserialize, Serialize};
+
+/// Size of the circuit of a program function and of its keys.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CircuitSize {
+    pub constraints: usize,
+    pub variables: usize,
+    pub public_inputs: usize,
+    /// Size of the serialized proving key, in bytes.
+    pub proving_key_size: usize,
+    /// Size of the serialized verifying key, in bytes.
+    pub verifying_key_size: usize,
+}
+
+/// The cost of proving and verifying the executions of a program function.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct FunctionCost {
+    #[serde(flatten)]
+    pub circuit: CircuitSize,
+    /// Milliseconds it took to prove an execution of the function with sample inputs. Not measured
+    /// if the function takes inputs that can't be sampled (e.g. records), or if the execution failed.
+    pub proving_time_ms: Option<u128>,
+}
+
+/// Report the cost of each function of a built program. Proving times are measured by executing each
+/// function with zero-valued inputs, signed by a throwaway account.
+pub fn program_cost(
+    program: &vm::Program,
+    keys: &vm::ProgramBuild,
+) -> Result<IndexMap<String, FunctionCost>> {
+    let rng = &mut rand::thread_rng();
+    let private_key = vm::PrivateKey::new(rng)?;
+    let view_key = vm::ViewKey::try_from(&private_key)?;
+    let address = vm::Address::try_from(&view_key)?;
+
+    let mut costs = IndexMap::new();
+    for (function_name, (proving_key, verifying_key)) in keys.map.iter() {
+        let circuit = vm::circuit_size(proving_key, verifying_key)?;
+
+        let inputs: Option<Vec<_>> = vm::function_input_types(program, function_name)?
+            .iter()
+            .map(|input_type| sample_input(input_type, &address))
+            .collect();
+        let proving_time_ms = inputs.and_then(|inputs| {
+            let start = Instant::now();
+            vm::execution(
+                program.clone(),
+                *function_name,
+                &inputs,
+                &private_key,
+                Some(proving_key.clone()),
+                rng,
+            )
+            .map(|_| start.elapsed().as_millis())
+            .map_err(|e| debug!("couldn't measure the proving time of {function_name}: {e}"))
+            .ok()
+        });
+
+        costs.insert(
+            function_name.to_string(),
+            FunctionCost {
+                circuit,
+                proving_time_ms,
+            },
+        );
+    }
+    Ok(costs)
+}
+
+/// Return a zero value of the given input type (e.g. `0u64` for `u64.private`), or None if the type
+/// can't be sampled.
+fn sample_input(input_type: &str, address: &vm::Address) -> Option<vm::UserInputValueType> {
+    if input_type.ends_with(".record") {
+        return None;
+    }
+
+    let literal_type = input_type.split('.').next()?;
+    let value = match literal_type {
+        "address" => address.to_string(),
+        "boolean" => "false".to_string(),
+        "string" | "signature" => return None,
+        _ => format!("0{literal_type}"),
+    };
+    vm::UserInputValueType::from_str(&value).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sample_inputs() {
+        let private_key = vm::PrivateKey::new(&mut rand::thread_rng()).unwrap();
+        let view_key = vm::ViewKey::try_from(&private_key).unwrap();
+        let address = vm::Address::try_from(&view_key).unwrap();
+
+        let value = sample_input("u64.private", &address).unwrap();
+        assert_eq!("0u64", value.to_string());
+
+        let value = sample_input("address.public", &address).unwrap();
+        assert_eq!(address.to_string(), value.to_string());
+
+        assert!(sample_input("token.record", &address).is_none());
+    }
+}
@@ -0,0 +1,144 @@
+use crate::transaction::Transaction;
+use crate::vm;
+use anyhow::{bail, Result};
+use indexmap::IndexMap;
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+/// Sequential number of a program deployment. The first deployment of a program is edition 0,
+/// and each subsequent deployment by its owner registers the next one.
+pub type Edition = u16;
+
+/// Gates that a deployment needs to pay as fee for each function of the program. Nodes verify the
+/// deployed keys against the program, which can be as expensive as building them, so deployments
+/// can't be free.
+pub const DEPLOYMENT_FEE_PER_FUNCTION: u64 = 1;
+
+/// Return the minimum fee of a deployment of the given program.
+pub fn minimum_fee(program: &vm::Program) -> u64 {
+    DEPLOYMENT_FEE_PER_FUNCTION * program.functions().len() as u64
+}
+
+/// The deployment history of a program id: the account that first deployed it, which is the
+/// only one allowed to deploy new editions, and the source of each edition in deployment order.
+/// Built-in programs like credits have no owner and can't be upgraded.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ProgramEditions {
+    pub owner: Option<vm::Address>,
+    pub editions: Vec<vm::Program>,
+}
+
+impl ProgramEditions {
+    /// Return the edition number of the most recent deployment.
+    pub fn latest(&self) -> Edition {
+        self.editions.len().saturating_sub(1) as Edition
+    }
+}
+
+/// Metadata of a deployed program edition, stored by the blockchain next to the program itself.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct DeploymentInfo {
+    /// The account that deployed the edition. Built-in programs have no deployer.
+    pub deployer: Option<vm::Address>,
+    /// Height of the block that included the deployment.
+    pub height: u64,
+    /// Id of the deployment transaction. Built-in programs are not deployed through transactions.
+    pub transaction_id: Option<String>,
+    /// Amount of gates paid as deployment fee.
+    pub fee: u64,
+    /// Hex encoded sha256 hash of the program source.
+    pub source_hash: String,
+    /// Signature of each program function, e.g. `hello(u32.public, u32.private) -> (u32.private)`.
+    pub functions: Vec<String>,
+    /// Hex encoded sha256 hash of the verifying key of each function.
+    pub verifying_key_hashes: IndexMap<String, String>,
+}
+
+impl DeploymentInfo {
+    /// Collect the metadata of a program edition that is not deployed through a transaction,
+    /// as is the case of built-in programs.
+    pub fn new(
+        program: &vm::Program,
+        verifying_keys: &vm::VerifyingKeyMap,
+        deployer: Option<vm::Address>,
+    ) -> Result<Self> {
+        let source_hash = hex::encode(Sha256::digest(program.to_string()));
+
+        let functions = program
+            .functions()
+            .values()
+            .map(|function| {
+                let inputs = function
+                    .inputs()
+                    .iter()
+                    .map(|input| input.value_type().to_string())
+                    .join(", ");
+                let outputs = function
+                    .outputs()
+                    .iter()
+                    .map(|output| output.value_type().to_string())
+                    .join(", ");
+                format!("{}({inputs}) -> ({outputs})", function.name())
+            })
+            .collect();
+
+        let mut verifying_key_hashes = IndexMap::new();
+        for (function_name, verifying_key) in verifying_keys.map.iter() {
+            verifying_key_hashes.insert(
+                function_name.to_string(),
+                verifying_key_hash(verifying_key)?,
+            );
+        }
+
+        Ok(Self {
+            deployer,
+            height: 0,
+            transaction_id: None,
+            fee: 0,
+            source_hash,
+            functions,
+            verifying_key_hashes,
+        })
+    }
+
+    /// Collect the metadata of a deployment transaction included in the block at the given height.
+    pub fn from_transaction(transaction: &Transaction, height: u64) -> Result<Self> {
+        if let Transaction::Deployment {
+            id,
+            program,
+            verifying_keys,
+            owner,
+            ..
+        } = transaction
+        {
+            Ok(Self {
+                height,
+                transaction_id: Some(id.clone()),
+                fee: transaction.fees().unsigned_abs(),
+                ..Self::new(program, verifying_keys, Some(*owner))?
+            })
+        } else {
+            bail!("Transaction {} is not a deployment", transaction.id())
+        }
+    }
+}
+
+/// Return the hex encoded sha256 hash of a verifying key.
+pub fn verifying_key_hash(verifying_key: &vm::VerifyingKey) -> Result<String> {
+    #[cfg(feature = "snarkvm_backend")]
+    let serialization = serde_json::to_string(verifying_key)?;
+    #[cfg(feature = "lambdavm_backend")]
+    let serialization = lambdavm::serialize_verifying_key(verifying_key.clone())?;
+    Ok(hex::encode(Sha256::digest(serialization)))
+}
+
+/// Return the hex encoded sha256 hash of every function name and verifying key of a program, in order.
+pub fn verifying_keys_hash(verifying_keys: &vm::VerifyingKeyMap) -> Result<String> {
+    let mut hasher = Sha256::new();
+    for (function_name, verifying_key) in verifying_keys.map.iter() {
+        hasher.update(function_name.to_string());
+        hasher.update(verifying_key_hash(verifying_key)?);
+    }
+    Ok(hex::encode(hasher.finalize()))
+}
@@ -1,11 +1,28 @@
+use anyhow::{anyhow, ensure, Result};
 use std::{path::PathBuf, str::FromStr};
 
+pub mod check;
+pub mod cost;
+pub mod deployment;
 pub mod program_file;
+pub mod prover;
 pub mod query;
+pub mod test_runner;
+pub mod trace;
 pub mod transaction;
 pub mod validator;
 pub mod vm;
 
+pub const CREDITS_SOURCE: &str = include_str!("../../aleo/credits.aleo");
+
+/// Id of the built-in credits program.
+pub const CREDITS_PROGRAM_ID: &str = "credits.aleo";
+
+/// Return whether the given program id is the one of the built-in credits program.
+pub fn is_credits_program(program_id: &vm::ProgramID) -> bool {
+    *program_id == vm::ProgramID::from_str(CREDITS_PROGRAM_ID).expect("invalid credits program id")
+}
+
 /// Directory to store aleo related files (e.g. account, cached programs). Typically ~/.aleo/
 pub fn aleo_home() -> PathBuf {
     std::env::var("ALEO_HOME")
@@ -13,30 +30,130 @@ pub fn aleo_home() -> PathBuf {
         .unwrap_or_else(|_| dirs::home_dir().unwrap().join(".aleo"))
 }
 
+/// Seed used to synthesize the credits program keys, so that every node and client builds the same ones.
+pub const CREDITS_SEED: u64 = 0xa1e0_c4ed_175;
+
+/// The credits verifying keys, as generated with `make credits_keys` from `CREDITS_SEED`.
+/// They are embedded so nodes don't need to synthesize them, and all use the same ones.
+#[cfg(feature = "snarkvm_backend")]
+const CREDITS_VERIFYING_KEYS: &str = include_str!("../../aleo/credits.snarkvm.keys");
+#[cfg(feature = "lambdavm_backend")]
+const CREDITS_VERIFYING_KEYS: &str = include_str!("../../aleo/credits.lambdavm.keys");
+
+/// Expected hash of the credits verifying keys (see `deployment::verifying_keys_hash`).
+/// Keys built, cached or stored by nodes are checked against it.
+#[cfg(feature = "snarkvm_backend")]
+const CREDITS_VERIFYING_KEYS_HASH: &str = include_str!("../../aleo/credits.snarkvm.sha256");
+#[cfg(feature = "lambdavm_backend")]
+const CREDITS_VERIFYING_KEYS_HASH: &str = include_str!("../../aleo/credits.lambdavm.sha256");
+
+/// Return the pinned hash of the credits verifying keys, failing if the pinned files haven't been
+/// generated with `make credits_keys`.
+pub fn credits_verifying_keys_hash() -> Result<&'static str> {
+    let hash = CREDITS_VERIFYING_KEYS_HASH.trim();
+    ensure!(
+        !hash.is_empty(),
+        "credits verifying keys are not pinned, run `make credits_keys`"
+    );
+    Ok(hash)
+}
+
+/// Fail if the given keys don't match the pinned credits verifying keys hash.
+pub fn ensure_credits_verifying_keys(verifying_keys: &vm::VerifyingKeyMap) -> Result<()> {
+    let expected_hash = credits_verifying_keys_hash()?;
+    let hash = deployment::verifying_keys_hash(verifying_keys)?;
+    ensure!(
+        hash == expected_hash,
+        "credits verifying keys hash {hash} doesn't match the expected {expected_hash}, run `make credits_keys`"
+    );
+    Ok(())
+}
+
+/// Get the credits program source and the verifying keys embedded in the binary.
+/// Fails if they haven't been generated with `make credits_keys` or don't match the pinned hash.
+pub fn credits_verifying_keys() -> Result<(vm::Program, vm::VerifyingKeyMap)> {
+    let program = vm::generate_program(CREDITS_SOURCE)?;
+    let verifying_keys: vm::VerifyingKeyMap = serde_json::from_str(CREDITS_VERIFYING_KEYS)
+        .map_err(|e| {
+            anyhow!("embedded credits verifying keys are invalid, run `make credits_keys`: {e}")
+        })?;
+    ensure_credits_verifying_keys(&verifying_keys)?;
+    Ok((program, verifying_keys))
+}
+
+/// Build the credits program keys from `CREDITS_SEED`. The resulting verifying keys are checked
+/// against the pinned hash, to make sure the proofs generated with these keys will be accepted.
+pub fn build_credits() -> Result<program_file::ProgramFile> {
+    let (program, keys) = vm::build_program_from_seed(CREDITS_SOURCE, CREDITS_SEED)?;
+    ensure_credits_verifying_keys(&verifying_keys(&keys))?;
+    Ok(program_file::ProgramFile { program, keys })
+}
+
+/// Extract the verifying keys of a program build.
+pub fn verifying_keys(keys: &vm::ProgramBuild) -> vm::VerifyingKeyMap {
+    let map = keys
+        .map
+        .iter()
+        .map(|(function_name, (_, verifying_key))| (*function_name, verifying_key.clone()))
+        .collect();
+    vm::VerifyingKeyMap { map }
+}
+
 /// Get the credits program. This is a special built-in program of the system, which contains
 /// functions to move aleo money. Since it's required for most uses in clients and servers, it's
-/// cached to only be built once.
+/// cached to only be built once. Keys are built deterministically and need to match the pinned hash,
+/// so cached keys from a previous build that don't match it are rebuilt.
 pub fn load_credits() -> (vm::Program, vm::ProgramBuild) {
+    try_load_credits().expect("couldn't load credits program")
+}
+
+/// Same as `load_credits`, but returning an error instead of panicking if the keys can't be built.
+pub fn try_load_credits() -> Result<(vm::Program, vm::ProgramBuild)> {
     // TODO: move this to lambdaVM-specific module or to the crate
     // currently, lambda VM does not check whether the params are created on disk before using them
     // so if they do not exist, make sure they are generated
     #[cfg(feature = "lambdavm_backend")]
-    vm::ensure_srs_file_exists().expect("Error reading or creating Universal SRS file");
+    vm::ensure_srs_file_exists()
+        .map_err(|e| anyhow!("Error reading or creating Universal SRS file: {e}"))?;
 
     // try to fetch from cache
     let cache_path = aleo_home().join("cache/credits.avm");
-    if let Ok(program) = program_file::ProgramFile::load(&cache_path) {
-        log::debug!("found credits program in {cache_path:?}");
-        return program;
+    match program_file::ProgramFile::read(&cache_path) {
+        Ok(file) => match ensure_credits_verifying_keys(&verifying_keys(&file.keys)) {
+            Ok(()) => {
+                log::debug!("found credits program in {cache_path:?}");
+                return Ok((file.program, file.keys));
+            }
+            Err(e) => log::warn!("discarding cached credits program: {e}"),
+        },
+        Err(e) => log::debug!("couldn't read cached credits program: {e}"),
     }
 
     // else build keys and cache for future use
-    log::debug!("cached credits not found, building and saving to {cache_path:?}");
-    let source = include_str!("../../aleo/credits.aleo");
-    let file = program_file::ProgramFile::build(source).expect("couldn't build credits program");
-    std::fs::create_dir_all(aleo_home().join("cache")).expect("couldn't create cache dir");
+    log::debug!("building credits program and saving to {cache_path:?}");
+    let file = build_credits().map_err(|e| anyhow!("couldn't build credits program: {e}"))?;
+    std::fs::create_dir_all(aleo_home().join("cache"))
+        .map_err(|e| anyhow!("couldn't create cache dir: {e}"))?;
     file.save(&cache_path)
-        .expect("couldn't save credits program");
+        .map_err(|e| anyhow!("couldn't save credits program: {e}"))?;
+
+    Ok((file.program, file.keys))
+}
 
-    (file.program, file.keys)
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pinned_credits_keys() {
+        let (program, pinned_keys) = credits_verifying_keys().unwrap();
+        assert_eq!(program.functions().len(), pinned_keys.map.len());
+
+        // the pinned keys are the ones built from the seed
+        let file = build_credits().unwrap();
+        assert_eq!(
+            deployment::verifying_keys_hash(&verifying_keys(&file.keys)).unwrap(),
+            deployment::verifying_keys_hash(&pinned_keys).unwrap()
+        );
+    }
 }
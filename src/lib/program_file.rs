@@ -1,18 +1,49 @@
-use std::path::Path;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
 
-use crate::vm;
-use anyhow::{anyhow, Result};
+use crate::{deployment, vm};
+use anyhow::{anyhow, bail, ensure, Result};
+use indexmap::IndexMap;
+use log::{debug, warn};
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+/// Bytes that identify an .avm file.
+const MAGIC: &[u8; 4] = b"AVM\0";
+
+/// Version of the .avm format. Files with a different version are considered stale and rebuilt.
+pub const FORMAT_VERSION: u16 = 2;
+
+/// Length of the sha256 checksum at the end of an .avm file.
+const CHECKSUM_LENGTH: usize = 32;
 
 /// This helper struct provides methods to dump programs and their proving/verifying keys into
 /// files to support vm "built-in" programs, i.e. programs that come already built and can be
 /// shared between the network and clients without extra work, like the credits program.
-#[derive(Serialize, Deserialize, Debug)]
+///
+/// Programs are stored in the binary .avm format: the `MAGIC` bytes, followed by the length of
+/// the header as a big endian u32, the bincode encoded `AvmHeader`, the bincode encoded keys and
+/// the sha256 checksum of the encoded header and keys.
+#[derive(Debug)]
 pub struct ProgramFile {
     pub program: vm::Program,
     pub keys: vm::ProgramBuild,
 }
 
+/// Describes the contents of an .avm file, to validate it before using its keys.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct AvmHeader {
+    pub version: u16,
+    /// The vm backend that built the keys, which are not compatible across backends.
+    pub backend: String,
+    pub program_id: String,
+    /// Hex encoded sha256 hash of the program source.
+    pub source_hash: String,
+    /// Hex encoded sha256 hash of the verifying key of each function.
+    pub verifying_keys: IndexMap<String, String>,
+    pub source: String,
+}
+
 impl ProgramFile {
     pub fn build(program_source: &str) -> Result<Self> {
         let (program, keys) = vm::build_program(program_source)?;
@@ -21,14 +52,309 @@ impl ProgramFile {
     }
 
     pub fn save(&self, output_path: &Path) -> Result<()> {
-        let json = serde_json::to_string(self)?;
-        std::fs::write(output_path, json).map_err(|e| anyhow!(e))
+        let keys = bincode::serialize(&self.keys)?;
+        let source = self.program.to_string();
+
+        let mut verifying_keys = IndexMap::new();
+        for (function_name, (_, verifying_key)) in self.keys.map.iter() {
+            verifying_keys.insert(
+                function_name.to_string(),
+                deployment::verifying_key_hash(verifying_key)?,
+            );
+        }
+
+        let header = bincode::serialize(&AvmHeader {
+            version: FORMAT_VERSION,
+            backend: vm::BACKEND.to_string(),
+            program_id: self.program.id().to_string(),
+            source_hash: hex::encode(Sha256::digest(&source)),
+            verifying_keys,
+            source,
+        })?;
+
+        let mut file = std::fs::File::create(output_path)?;
+        file.write_all(MAGIC)?;
+        file.write_all(&(header.len() as u32).to_be_bytes())?;
+        file.write_all(&header)?;
+        file.write_all(&keys)?;
+        file.write_all(&checksum(&header, &keys))?;
+        Ok(())
+    }
+
+    /// Read the header of an .avm file, without loading its keys.
+    pub fn inspect(path: &Path) -> Result<AvmHeader> {
+        let mut file =
+            std::fs::File::open(path).map_err(|e| anyhow!("couldn't find stored program: {e}"))?;
+        Ok(bincode::deserialize(&read_header(&mut file)?)?)
+    }
+
+    /// Read an .avm file, checking that its format version and backend are the current ones,
+    /// that it's not corrupted and that its program and keys match the hashes of its header.
+    pub fn read(path: &Path) -> Result<Self> {
+        let mut file =
+            std::fs::File::open(path).map_err(|e| anyhow!("couldn't find stored program: {e}"))?;
+        let header_bytes = read_header(&mut file)?;
+        let header: AvmHeader = bincode::deserialize(&header_bytes)?;
+
+        ensure!(
+            header.version == FORMAT_VERSION,
+            "unsupported .avm format version {}, expected {FORMAT_VERSION}",
+            header.version
+        );
+        ensure!(
+            header.backend == vm::BACKEND,
+            "program was built with the {} backend, expected {}",
+            header.backend,
+            vm::BACKEND
+        );
+
+        let mut keys = Vec::new();
+        file.read_to_end(&mut keys)?;
+        ensure!(
+            keys.len() >= CHECKSUM_LENGTH,
+            "program checksum mismatch, the file may be truncated or corrupted"
+        );
+        let file_checksum = keys.split_off(keys.len() - CHECKSUM_LENGTH);
+        ensure!(
+            file_checksum == checksum(&header_bytes, &keys),
+            "program checksum mismatch, the file may be truncated or corrupted"
+        );
+
+        ensure!(
+            hex::encode(Sha256::digest(&header.source)) == header.source_hash,
+            "program source doesn't match its hash"
+        );
+        let program = vm::generate_program(&header.source)?;
+        ensure!(
+            program.id().to_string() == header.program_id,
+            "program id {} doesn't match the header id {}",
+            program.id(),
+            header.program_id
+        );
+
+        let keys: vm::ProgramBuild = bincode::deserialize(&keys)?;
+        ensure!(
+            keys.map.len() == header.verifying_keys.len(),
+            "the program keys don't match the functions of the header"
+        );
+        for (function_name, (_, verifying_key)) in keys.map.iter() {
+            let expected_hash = header
+                .verifying_keys
+                .get(&function_name.to_string())
+                .ok_or_else(|| {
+                    anyhow!("missing verifying key hash for function {function_name}")
+                })?;
+            ensure!(
+                deployment::verifying_key_hash(verifying_key)? == *expected_hash,
+                "the verifying key of function {function_name} doesn't match its hash"
+            );
+        }
+        Ok(Self { program, keys })
+    }
+
+    /// Path of the client cache file for the given program, keyed by the hash of its source.
+    pub fn cache_path(program: &vm::Program) -> PathBuf {
+        let source_hash = hex::encode(Sha256::digest(program.to_string()));
+        crate::aleo_home()
+            .join("cache/programs")
+            .join(format!("{source_hash}.avm"))
+    }
+
+    /// Return the cached build of the given program, if any.
+    pub fn cached(program: &vm::Program) -> Option<Self> {
+        let path = Self::cache_path(program);
+        match Self::read(&path) {
+            Ok(file) => Some(file),
+            Err(e) => {
+                debug!("no valid cached build of {} at {path:?}: {e}", program.id());
+                None
+            }
+        }
+    }
+
+    /// Load the cached build of the given program source, building and caching it if necessary.
+    pub fn load_cached(program_source: &str) -> Result<Self> {
+        let path = Self::cache_path(&vm::generate_program(program_source)?);
+        std::fs::create_dir_all(path.parent().unwrap())?;
+        Self::load(&path, program_source)
+    }
+
+    /// Save this build to the client cache, to be reused by later deployments and executions.
+    pub fn cache(&self) -> Result<PathBuf> {
+        let path = Self::cache_path(&self.program);
+        std::fs::create_dir_all(path.parent().unwrap())?;
+        self.save(&path)?;
+        Ok(path)
+    }
+
+    /// Load the program stored at the given path, rebuilding and saving it again if the file is missing,
+    /// invalid or was built from a different source than the given one.
+    pub fn load(path: &Path, program_source: &str) -> Result<Self> {
+        let program = vm::generate_program(program_source)?;
+
+        match Self::read(path) {
+            Ok(file) if file.program.to_string() == program.to_string() => return Ok(file),
+            Ok(_) => warn!("{path:?} was built from a different program source, rebuilding"),
+            Err(e) => warn!("couldn't load {path:?}, rebuilding: {e}"),
+        }
+
+        let file = Self::build(program_source)?;
+        file.save(path)?;
+        Ok(file)
     }
+}
+
+/// Return the sha256 checksum of the encoded header and keys of an .avm file.
+fn checksum(header: &[u8], keys: &[u8]) -> Vec<u8> {
+    let mut hasher = Sha256::new();
+    hasher.update(header);
+    hasher.update(keys);
+    hasher.finalize().to_vec()
+}
+
+/// Read the encoded header of an .avm file, leaving the file positioned at the start of the keys.
+fn read_header(file: &mut std::fs::File) -> Result<Vec<u8>> {
+    let mut magic = [0u8; 4];
+    file.read_exact(&mut magic)
+        .map_err(|_| anyhow!("not an .avm file"))?;
+    if &magic != MAGIC {
+        bail!("not an .avm file");
+    }
+
+    let mut length = [0u8; 4];
+    file.read_exact(&mut length)?;
+    let length = u32::from_be_bytes(length) as usize;
+
+    let mut header = Vec::new();
+    file.take(length as u64).read_to_end(&mut header)?;
+    ensure!(header.len() == length, "truncated .avm header");
+    Ok(header)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_fs::NamedTempFile;
+
+    const HELLO: &str = include_str!("../../aleo/hello.aleo");
+    const TOKEN: &str = include_str!("../../aleo/token.aleo");
+
+    #[test]
+    fn save_and_read() {
+        let path = NamedTempFile::new("hello.avm").unwrap();
+        let file = ProgramFile::build(HELLO).unwrap();
+        file.save(path.path()).unwrap();
+
+        let header = ProgramFile::inspect(path.path()).unwrap();
+        assert_eq!(FORMAT_VERSION, header.version);
+        assert_eq!(vm::BACKEND, header.backend);
+        assert_eq!("hello.aleo", header.program_id);
+        assert_eq!(
+            vec!["hello"],
+            header.verifying_keys.keys().collect::<Vec<_>>()
+        );
+
+        let read = ProgramFile::read(path.path()).unwrap();
+        assert_eq!(file.program.to_string(), read.program.to_string());
+        assert_eq!(file.keys.map.len(), read.keys.map.len());
+    }
+
+    #[test]
+    fn reject_corrupted_file() {
+        let path = NamedTempFile::new("hello.avm").unwrap();
+        ProgramFile::build(HELLO)
+            .unwrap()
+            .save(path.path())
+            .unwrap();
+
+        let mut bytes = std::fs::read(path.path()).unwrap();
+        bytes.truncate(bytes.len() - 1);
+        std::fs::write(path.path(), &bytes).unwrap();
+
+        let error = ProgramFile::read(path.path()).unwrap_err();
+        assert!(error.to_string().contains("checksum mismatch"));
+
+        // the header is covered by the checksum too
+        ProgramFile::build(HELLO)
+            .unwrap()
+            .save(path.path())
+            .unwrap();
+        let mut bytes = std::fs::read(path.path()).unwrap();
+        let instruction = bytes
+            .windows(9)
+            .position(|window| window == b"add r0 r1")
+            .unwrap();
+        bytes[instruction + 5] = b'0';
+        std::fs::write(path.path(), &bytes).unwrap();
+        let error = ProgramFile::read(path.path()).unwrap_err();
+        assert!(error.to_string().contains("checksum mismatch"));
+
+        std::fs::write(path.path(), "{}").unwrap();
+        let error = ProgramFile::read(path.path()).unwrap_err();
+        assert!(error.to_string().contains("not an .avm file"));
+    }
+
+    #[test]
+    fn reject_mismatched_header() {
+        let path = NamedTempFile::new("hello.avm").unwrap();
+        let file = ProgramFile::build(HELLO).unwrap();
+        file.save(path.path()).unwrap();
+        let header = ProgramFile::inspect(path.path()).unwrap();
+        let keys = bincode::serialize(&file.keys).unwrap();
+
+        // rewrite the file with a valid checksum, as a tampered file would have
+        let write = |header: &AvmHeader| {
+            let header = bincode::serialize(header).unwrap();
+            let mut bytes = MAGIC.to_vec();
+            bytes.extend((header.len() as u32).to_be_bytes());
+            bytes.extend(&header);
+            bytes.extend(&keys);
+            bytes.extend(checksum(&header, &keys));
+            std::fs::write(path.path(), bytes).unwrap();
+        };
+
+        write(&AvmHeader {
+            source: TOKEN.to_string(),
+            ..header.clone()
+        });
+        let error = ProgramFile::read(path.path()).unwrap_err();
+        assert!(error.to_string().contains("source doesn't match its hash"));
+
+        write(&AvmHeader {
+            source: TOKEN.to_string(),
+            source_hash: hex::encode(Sha256::digest(TOKEN)),
+            ..header.clone()
+        });
+        let error = ProgramFile::read(path.path()).unwrap_err();
+        assert!(error.to_string().contains("doesn't match the header id"));
+
+        let mut verifying_keys = header.verifying_keys.clone();
+        verifying_keys.insert("hello".to_string(), hex::encode([0u8; 32]));
+        write(&AvmHeader {
+            verifying_keys,
+            ..header.clone()
+        });
+        let error = ProgramFile::read(path.path()).unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("verifying key of function hello doesn't match its hash"));
+
+        write(&header);
+        ProgramFile::read(path.path()).unwrap();
+    }
+
+    #[test]
+    fn load_rebuilds_stale_file() {
+        let path = NamedTempFile::new("program.avm").unwrap();
+        ProgramFile::build(HELLO)
+            .unwrap()
+            .save(path.path())
+            .unwrap();
 
-    pub fn load(path: &Path) -> Result<(vm::Program, vm::ProgramBuild)> {
-        let json = std::fs::read_to_string(path)
-            .map_err(|e| anyhow!("couldn't find stored program: {e}"))?;
-        let stored: Self = serde_json::from_str(&json)?;
-        Ok((stored.program, stored.keys))
+        // loading with a different source rebuilds the file
+        let file = ProgramFile::load(path.path(), TOKEN).unwrap();
+        assert_eq!("token.aleo", file.program.id().to_string());
+        let header = ProgramFile::inspect(path.path()).unwrap();
+        assert_eq!("token.aleo", header.program_id);
     }
 }
@@ -0,0 +1,153 @@
+use std::io::{BufRead, BufReader, ErrorKind, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+use crate::transaction::ExecutionAuthorization;
+use crate::vm;
+use anyhow::{anyhow, ensure, Result};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+/// Default address of the prover process.
+pub const DEFAULT_PROVER_ADDRESS: &str = "127.0.0.1:26700";
+
+/// Time to wait for the connection to the prover to be established.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Time to wait for a request or response to be written to the connection.
+pub const WRITE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Time to wait for the prover response. Authorizations are proven one at a time, so the response
+/// can take as long as proving every authorization queued before this one.
+const RESPONSE_TIMEOUT: Duration = Duration::from_secs(30 * 60);
+
+/// Execution authorization sent to the prover. Requests and responses are exchanged as single
+/// lines of JSON, one request per connection.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ProverRequest {
+    pub authorization: ExecutionAuthorization,
+    /// Hash of the verifying key deployed for the authorized function, if the program is deployed.
+    /// The prover refuses to prove with keys that don't match it, since the blockchain would reject the proofs.
+    pub verifying_key_hash: Option<String>,
+}
+
+/// Response of the prover to an execution authorization.
+#[derive(Debug, Serialize, Deserialize)]
+pub enum ProverResponse {
+    Transitions(Vec<vm::Transition>),
+    Error(String),
+}
+
+/// Send an execution authorization to the prover listening on the given address,
+/// and wait for the proven transitions. This blocks until the prover responds, so async
+/// callers should run it in a blocking task.
+pub fn prove_remote(address: &str, request: &ProverRequest) -> Result<Vec<vm::Transition>> {
+    match send(address, request, RESPONSE_TIMEOUT)? {
+        ProverResponse::Transitions(transitions) => Ok(transitions),
+        ProverResponse::Error(error) => Err(anyhow!("prover error: {error}")),
+    }
+}
+
+/// Send a request over a new connection to the given address and read its response.
+fn send<Req: Serialize, Res: DeserializeOwned>(
+    address: &str,
+    request: &Req,
+    response_timeout: Duration,
+) -> Result<Res> {
+    let socket_address = address
+        .to_socket_addrs()?
+        .next()
+        .ok_or_else(|| anyhow!("couldn't resolve prover address {address}"))?;
+    let mut stream = TcpStream::connect_timeout(&socket_address, CONNECT_TIMEOUT)
+        .map_err(|e| anyhow!("couldn't connect to prover at {address}: {e}"))?;
+    stream.set_write_timeout(Some(WRITE_TIMEOUT))?;
+    stream.set_read_timeout(Some(response_timeout))?;
+
+    write_message(&mut stream, request)?;
+    read_message(&stream).map_err(|e| anyhow!("couldn't read prover response: {e}"))
+}
+
+/// Write a message to the connection as a single line of JSON.
+pub fn write_message<T: Serialize>(stream: &mut TcpStream, message: &T) -> Result<()> {
+    let mut line = serde_json::to_string(message)?;
+    line.push('\n');
+    stream.write_all(line.as_bytes())?;
+    Ok(())
+}
+
+/// Read a single line of JSON message from the connection, failing if the connection's
+/// read timeout expires first.
+pub fn read_message<T: DeserializeOwned>(stream: &TcpStream) -> Result<T> {
+    let mut line = String::new();
+    BufReader::new(stream)
+        .read_line(&mut line)
+        .map_err(|e| match e.kind() {
+            ErrorKind::WouldBlock | ErrorKind::TimedOut => anyhow!("timed out"),
+            _ => anyhow!(e),
+        })?;
+    ensure!(!line.is_empty(), "connection closed");
+    Ok(serde_json::from_str(&line)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+    use std::thread;
+
+    /// Start a server that handles a single connection with the given function, returning its address.
+    fn serve_once(handle: impl FnOnce(TcpStream) + Send + 'static) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        thread::spawn(move || handle(listener.accept().unwrap().0));
+        address
+    }
+
+    #[test]
+    fn exchange_messages() {
+        let address = serve_once(|mut stream| {
+            let request: String = read_message(&stream).unwrap();
+            write_message(&mut stream, &ProverResponse::Error(request)).unwrap();
+        });
+
+        let response: ProverResponse =
+            send(&address, &"some request".to_string(), RESPONSE_TIMEOUT).unwrap();
+        assert!(matches!(response, ProverResponse::Error(error) if error == "some request"));
+    }
+
+    #[test]
+    fn response_timeout() {
+        let address = serve_once(|stream| {
+            // hold the connection open without responding
+            let _: String = read_message(&stream).unwrap();
+            thread::sleep(Duration::from_secs(2));
+        });
+
+        let error = send::<_, ProverResponse>(&address, &"request", Duration::from_millis(100))
+            .unwrap_err();
+        assert!(error.to_string().contains("timed out"));
+    }
+
+    #[test]
+    fn connection_closed() {
+        let address = serve_once(|stream| {
+            // close the connection without responding
+            let _: String = read_message(&stream).unwrap();
+        });
+
+        let error = send::<_, ProverResponse>(&address, &"request", RESPONSE_TIMEOUT).unwrap_err();
+        assert!(error.to_string().contains("couldn't read prover response"));
+    }
+
+    #[test]
+    fn prover_not_listening() {
+        // bind and drop a listener to get a free port nobody is listening on
+        let address = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .to_string();
+
+        let error = send::<_, ProverResponse>(&address, &"request", RESPONSE_TIMEOUT).unwrap_err();
+        assert!(error.to_string().contains("couldn't connect to prover"));
+    }
+}
@@ -1,4 +1,4 @@
-use crate::vm::ProgramID;
+use crate::vm::{Address, ProgramID};
 use serde::{Deserialize, Serialize};
 
 #[derive(Clone, Serialize, Deserialize, Debug)]
@@ -7,8 +7,24 @@ pub enum AbciQuery {
     GetRecords,
     /// Returns all spent records's serial numbers
     GetSpentSerialNumbers,
-    /// Returns the program struct given it's id
+    /// Returns the program struct of the latest edition given it's id
     GetProgram { program_id: ProgramID },
+    /// Returns the deployment owner and every deployed edition of the program given it's id
+    GetProgramEditions { program_id: ProgramID },
+    /// Returns the deployment metadata of every edition of the program given it's id
+    GetProgramInfo { program_id: ProgramID },
+    /// Returns a page of deployed programs ordered by id, starting from the given one, with the
+    /// metadata of their latest edition, plus the id to request the next page from, if any
+    ListPrograms {
+        from: Option<ProgramID>,
+        limit: usize,
+    },
+    /// Returns the credits created at genesis, minted as rewards and burned since then
+    GetSupply,
+    /// Returns the credits accrued by the treasury and the amount of spends made from it
+    GetTreasury,
+    /// Returns the rewards accrued by the given account that it hasn't claimed yet
+    GetPendingRewards { address: Address },
 }
 
 impl From<AbciQuery> for Vec<u8> {
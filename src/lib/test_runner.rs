@@ -0,0 +1,252 @@
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use crate::vm;
+use anyhow::{anyhow, Result};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+/// A file declaring the test cases of a program, e.g.:
+///
+/// ```json
+/// {
+///   "program": "token.aleo",
+///   "tests": [
+///     { "name": "mint", "function": "mint", "inputs": ["10u64", "%account"], "outputs": [null] },
+///     { "name": "spend", "function": "fee", "inputs": [{ "record": "credits", "gates": 5 }, "10u64"], "error": "" }
+///   ]
+/// }
+/// ```
+#[derive(Debug, Deserialize)]
+pub struct TestFile {
+    /// Path of the program source, relative to the test file.
+    pub program: PathBuf,
+    pub tests: Vec<TestCase>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TestCase {
+    pub name: String,
+    pub function: String,
+    #[serde(default)]
+    pub inputs: Vec<TestInput>,
+    /// The expected output values. A null output is not checked (e.g. records, which have random nonces).
+    /// If omitted, the outputs are not checked.
+    pub outputs: Option<Vec<Option<String>>>,
+    /// If set, the execution is expected to fail with an error that contains this message.
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum TestInput {
+    /// A literal value, or `%account` for the address of the test account.
+    Value(String),
+    /// A record owned by the test account, minted with the given gates.
+    Record { record: String, gates: u64 },
+}
+
+#[derive(Debug, Serialize)]
+pub struct TestReport {
+    pub passed: usize,
+    pub failed: usize,
+    pub tests: Vec<TestResult>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TestResult {
+    pub name: String,
+    pub passed: bool,
+    /// The outputs of the execution, if it succeeded.
+    pub outputs: Option<Vec<String>>,
+    /// Why the test failed.
+    pub reason: Option<String>,
+}
+
+/// Run the test cases of a program locally, signed by a throwaway account. The outputs are obtained by
+/// evaluating each function; if the program keys are given, the executions are also proven.
+pub fn run_tests(
+    program: &vm::Program,
+    keys: Option<&vm::ProgramBuild>,
+    tests: &[TestCase],
+) -> Result<TestReport> {
+    let rng = &mut rand::thread_rng();
+    let private_key = vm::PrivateKey::new(rng)?;
+
+    let results: Vec<TestResult> = tests
+        .iter()
+        .map(|test| {
+            let result = run_test(program, keys, test, &private_key);
+            let (outputs, reason) = match (result, &test.error) {
+                (Ok(outputs), None) => {
+                    let reason = test
+                        .outputs
+                        .as_ref()
+                        .and_then(|expected| compare_outputs(expected, &outputs));
+                    (Some(outputs), reason)
+                }
+                (Ok(outputs), Some(expected)) => (
+                    Some(outputs),
+                    Some(format!(
+                        "expected the execution to fail with `{expected}`, but it succeeded"
+                    )),
+                ),
+                (Err(error), Some(expected)) if error.to_string().contains(expected) => {
+                    (None, None)
+                }
+                (Err(error), _) => (None, Some(error.to_string())),
+            };
+
+            TestResult {
+                name: test.name.clone(),
+                passed: reason.is_none(),
+                outputs,
+                reason,
+            }
+        })
+        .collect();
+
+    let passed = results.iter().filter(|result| result.passed).count();
+    Ok(TestReport {
+        passed,
+        failed: results.len() - passed,
+        tests: results,
+    })
+}
+
+fn run_test(
+    program: &vm::Program,
+    keys: Option<&vm::ProgramBuild>,
+    test: &TestCase,
+    private_key: &vm::PrivateKey,
+) -> Result<Vec<String>> {
+    let rng = &mut rand::thread_rng();
+    let view_key = vm::ViewKey::try_from(private_key)?;
+    let address = vm::Address::try_from(&view_key)?;
+
+    let function_name = vm::Identifier::from_str(&test.function)?;
+    let inputs = test
+        .inputs
+        .iter()
+        .map(|input| match input {
+            TestInput::Value(value) if value == "%account" => {
+                vm::UserInputValueType::from_str(&address.to_string())
+            }
+            TestInput::Value(value) => vm::UserInputValueType::from_str(value),
+            TestInput::Record { record, gates } => {
+                let (_, encrypted_record) = vm::mint_record(
+                    &program.id().to_string(),
+                    record,
+                    &address,
+                    *gates,
+                    rng.gen(),
+                )?;
+                encrypted_record
+                    .decrypt(&view_key)
+                    .map(vm::UserInputValueType::Record)
+            }
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let outputs = vm::evaluate(program, function_name, &inputs, private_key, rng)?;
+
+    if let Some(keys) = keys {
+        let (proving_key, _) = keys
+            .map
+            .get(&function_name)
+            .ok_or_else(|| anyhow!("function {function_name} not found"))?;
+        vm::execution(
+            program.clone(),
+            function_name,
+            &inputs,
+            private_key,
+            Some(proving_key.clone()),
+            rng,
+        )?;
+    }
+    Ok(outputs)
+}
+
+/// Return the reason why the outputs don't match the expected ones, if they don't.
+fn compare_outputs(expected: &[Option<String>], outputs: &[String]) -> Option<String> {
+    if expected.len() != outputs.len() {
+        return Some(format!(
+            "expected {} outputs, found {}",
+            expected.len(),
+            outputs.len()
+        ));
+    }
+
+    expected.iter().zip(outputs).enumerate().find_map(
+        |(index, (expected, output))| match expected {
+            Some(expected) if expected != output => Some(format!(
+                "expected output {index} to be {expected}, found {output}"
+            )),
+            _ => None,
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEST_FILE: &str = r#"{
+        "program": "hello.aleo",
+        "tests": [
+            { "name": "add", "function": "hello", "inputs": ["1u32", "2u32"], "outputs": ["3u32"] },
+            { "name": "wrong output", "function": "hello", "inputs": ["1u32", "2u32"], "outputs": ["4u32"] },
+            { "name": "overflow", "function": "hello", "inputs": ["4294967295u32", "1u32"], "error": "" },
+            { "name": "credits", "function": "fee", "inputs": [{ "record": "credits", "gates": 10 }, "5u64"] }
+        ]
+    }"#;
+
+    #[test]
+    fn parse_test_file() {
+        let test_file: TestFile = serde_json::from_str(TEST_FILE).unwrap();
+        assert_eq!(PathBuf::from("hello.aleo"), test_file.program);
+        assert_eq!(4, test_file.tests.len());
+        assert_eq!(
+            Some(vec![Some("3u32".to_string())]),
+            test_file.tests[0].outputs
+        );
+        assert_eq!(Some("".to_string()), test_file.tests[2].error);
+        assert!(matches!(
+            test_file.tests[3].inputs[0],
+            TestInput::Record { gates: 10, .. }
+        ));
+    }
+
+    #[test]
+    fn compare_expected_outputs() {
+        let outputs = vec!["3u32".to_string(), "1u64".to_string()];
+        assert!(compare_outputs(&[Some("3u32".to_string()), None], &outputs).is_none());
+        assert_eq!(
+            Some("expected output 1 to be 2u64, found 1u64".to_string()),
+            compare_outputs(&[None, Some("2u64".to_string())], &outputs)
+        );
+        assert_eq!(
+            Some("expected 1 outputs, found 2".to_string()),
+            compare_outputs(&[None], &outputs)
+        );
+    }
+
+    #[cfg(feature = "snarkvm_backend")]
+    #[test]
+    fn run_hello_tests() {
+        let test_file: TestFile = serde_json::from_str(TEST_FILE).unwrap();
+        let program = vm::generate_program(include_str!("../../aleo/hello.aleo")).unwrap();
+
+        // the last test calls a function that doesn't exist in hello
+        let report = run_tests(&program, None, &test_file.tests).unwrap();
+        assert_eq!(2, report.passed);
+        assert_eq!(2, report.failed);
+        assert!(report.tests[0].passed);
+        assert_eq!(
+            Some("expected output 0 to be 4u32, found 3u32".to_string()),
+            report.tests[1].reason
+        );
+        assert!(report.tests[2].passed);
+        assert!(!report.tests[3].passed);
+    }
+}
@@ -0,0 +1,254 @@
+use std::fmt::Write;
+
+use indexmap::IndexMap;
+use serde::Serialize;
+
+/// The instructions evaluated by an execution of a program function, with the values of the
+/// registers they read and wrote, used to debug programs on dry runs.
+#[derive(Debug, Default, Serialize)]
+pub struct Trace {
+    pub steps: Vec<TraceStep>,
+    /// Set if the evaluation stopped because an instruction failed.
+    pub error: Option<TraceError>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TraceStep {
+    /// Position of the instruction in the function body.
+    pub index: usize,
+    pub instruction: String,
+    /// Values of the instruction operands, by operand (literal operands are omitted).
+    pub operands: IndexMap<String, String>,
+    /// Values of the instruction destination registers, after evaluating it.
+    pub destinations: IndexMap<String, String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TraceError {
+    /// Position of the failing instruction in the function body, if the backend reports it.
+    pub index: Option<usize>,
+    pub instruction: Option<String>,
+    pub message: String,
+}
+
+/// A line and column of a program source, both starting at 1.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub struct Location {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Trace {
+    /// Return one line per evaluated instruction with its source location and register values.
+    pub fn lines(&self, source_name: &str, source: &str, function_name: &str) -> Vec<String> {
+        let locations = instruction_locations(source, function_name);
+        self.steps
+            .iter()
+            .map(|step| {
+                let mut line = format!(
+                    "{} {}",
+                    format_location(source_name, locations.get(step.index)),
+                    step.instruction
+                );
+                if !step.operands.is_empty() {
+                    write!(line, " {}", format_registers(&step.operands)).unwrap();
+                }
+                if !step.destinations.is_empty() {
+                    write!(line, " => {}", format_registers(&step.destinations)).unwrap();
+                }
+                line
+            })
+            .collect()
+    }
+
+    /// If the evaluation failed, return an error message pointing to the failing instruction in the source,
+    /// followed by the trace of the instructions evaluated before it.
+    pub fn error_message(
+        &self,
+        source_name: &str,
+        source: &str,
+        function_name: &str,
+    ) -> Option<String> {
+        let error = self.error.as_ref()?;
+        let mut message = match (&error.instruction, error.index) {
+            (Some(instruction), index) => {
+                let location = index.and_then(|index| {
+                    instruction_locations(source, function_name)
+                        .get(index)
+                        .copied()
+                });
+                format!(
+                    "Failed to evaluate instruction `{instruction}` at {}: {}",
+                    format_location(source_name, location.as_ref()),
+                    error.message
+                )
+            }
+            (None, _) => format!(
+                "Failed to evaluate function {function_name}: {}",
+                error.message
+            ),
+        };
+
+        let lines = self.lines(source_name, source, function_name);
+        if !lines.is_empty() {
+            message.push_str("\ntrace:");
+            for line in lines {
+                write!(message, "\n  {line}").unwrap();
+            }
+        }
+        Some(message)
+    }
+}
+
+/// Return the source location of each instruction of the given function, in order. Instructions
+/// are the statements of the function body other than its input and output declarations.
+pub fn instruction_locations(source: &str, function_name: &str) -> Vec<Location> {
+    let mut locations = Vec::new();
+    let mut in_function = false;
+
+    for (number, line) in source.lines().enumerate() {
+        let code = line.split("//").next().unwrap_or_default();
+        let statement = code.trim();
+        if statement.is_empty() {
+            continue;
+        }
+
+        // a top level declaration starts a new block
+        let first_word = statement.split_whitespace().next().unwrap_or_default();
+        if matches!(
+            first_word,
+            "program"
+                | "import"
+                | "function"
+                | "closure"
+                | "finalize"
+                | "record"
+                | "interface"
+                | "mapping"
+        ) {
+            in_function = first_word == "function"
+                && statement.trim_end_matches(':').split_whitespace().nth(1) == Some(function_name);
+            continue;
+        }
+
+        if in_function && !matches!(first_word, "input" | "output") {
+            locations.push(Location {
+                line: number + 1,
+                column: code.len() - code.trim_start().len() + 1,
+            });
+        }
+    }
+    locations
+}
+
+fn format_location(source_name: &str, location: Option<&Location>) -> String {
+    match location {
+        Some(Location { line, column }) => format!("{source_name}:{line}:{column}"),
+        None => source_name.to_string(),
+    }
+}
+
+fn format_registers(registers: &IndexMap<String, String>) -> String {
+    registers
+        .iter()
+        .map(|(register, value)| format!("{register} = {value}"))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOURCE: &str = r#"// The 'token.aleo' program.
+program token.aleo;
+
+record token:
+    owner as address.private;
+    gates as u64.private;
+    amount as u64.private;
+
+function mint:
+    input r0 as u64.private;
+    cast self.caller 0u64 r0 into r1 as token.record;
+    output r1 as token.record;
+
+function transfer:
+    input r0 as token.record;
+    input r1 as address.private;
+    input r2 as u64.private;
+    // check the balance
+    sub r0.amount r2 into r3;
+    cast r1 0u64 r2 into r4 as token.record;
+
+    cast r0.owner r0.gates r3 into r5 as token.record;
+    output r4 as token.record;
+    output r5 as token.record;
+"#;
+
+    #[test]
+    fn locate_instructions() {
+        let locations = instruction_locations(SOURCE, "transfer");
+        assert_eq!(
+            vec![
+                Location {
+                    line: 19,
+                    column: 5
+                },
+                Location {
+                    line: 20,
+                    column: 5
+                },
+                Location {
+                    line: 22,
+                    column: 5
+                }
+            ],
+            locations
+        );
+
+        assert_eq!(1, instruction_locations(SOURCE, "mint").len());
+        assert!(instruction_locations(SOURCE, "burn").is_empty());
+    }
+
+    #[test]
+    fn error_message_points_to_source() {
+        let mut operands = IndexMap::new();
+        operands.insert("r0.amount".to_string(), "3u64".to_string());
+        operands.insert("r2".to_string(), "2u64".to_string());
+
+        let trace = Trace {
+            steps: vec![],
+            error: Some(TraceError {
+                index: Some(0),
+                instruction: Some("sub r0.amount r2 into r3;".to_string()),
+                message: "Integer underflow".to_string(),
+            }),
+        };
+        assert_eq!(
+            "Failed to evaluate instruction `sub r0.amount r2 into r3;` at token.aleo:19:5: Integer underflow",
+            trace
+                .error_message("token.aleo", SOURCE, "transfer")
+                .unwrap()
+        );
+
+        let mut destinations = IndexMap::new();
+        destinations.insert("r3".to_string(), "1u64".to_string());
+        let trace = Trace {
+            steps: vec![TraceStep {
+                index: 0,
+                instruction: "sub r0.amount r2 into r3;".to_string(),
+                operands,
+                destinations,
+            }],
+            error: None,
+        };
+        assert!(trace
+            .error_message("token.aleo", SOURCE, "transfer")
+            .is_none());
+        assert_eq!(
+            vec!["token.aleo:19:5 sub r0.amount r2 into r3; r0.amount = 3u64, r2 = 2u64 => r3 = 1u64"],
+            trace.lines("token.aleo", SOURCE, "transfer")
+        );
+    }
+}
@@ -1,13 +1,15 @@
-use crate::load_credits;
+use crate::deployment::Edition;
+use crate::program_file::ProgramFile;
 use crate::validator;
-use crate::vm::{self, VerifyingKeyMap};
-use anyhow::{anyhow, ensure, Result};
+use crate::vm;
+use crate::{is_credits_program, load_credits};
+use anyhow::{anyhow, bail, ensure, Result};
+use indexmap::IndexMap;
 use itertools::Itertools;
 use log::debug;
+use rand::{CryptoRng, Rng};
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
-use std::fs;
-use std::path::Path;
 use std::str::FromStr;
 
 #[derive(Clone, Serialize, Deserialize, Debug)]
@@ -16,93 +18,262 @@ pub enum Transaction {
         id: String,
         program: Box<vm::Program>,
         verifying_keys: vm::VerifyingKeyMap,
+        certificates: vm::CertificateMap,
+        /// The account deploying the program. Only the owner of the first edition can deploy new ones.
+        owner: vm::Address,
+        /// The edition this deployment registers, which must be the one following the latest deployed.
+        edition: Edition,
+        /// The owner's signature of the transaction id.
+        signature: Option<vm::Signature>,
         fee: Option<vm::Transition>,
     },
     Execution {
         id: String,
         transitions: Vec<vm::Transition>,
+        /// The edition of each program the transitions were proven against. The credits program can't be
+        /// upgraded, so its transitions (e.g. fees) are always proven against the first edition and it's omitted.
+        editions: IndexMap<vm::ProgramID, Edition>,
+        /// Signatures of validator keys, needed by the stake transitions that register new validators
+        /// and by the transitions that change a validator.
+        #[serde(default)]
+        key_signatures: Vec<validator::KeySignature>,
     },
 }
 
+/// An execution signed by the account that owns its inputs, which can be proven without its
+/// private key (e.g. by a separate prover process) and then sent to the blockchain as a transaction.
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct ExecutionAuthorization {
+    pub program: vm::Program,
+    /// The edition of the program the execution will be proven against.
+    pub edition: Edition,
+    pub function_name: vm::Identifier,
+    pub authorization: vm::Authorization,
+    /// The authorization of the credits fee function, if a fee is paid.
+    pub fee: Option<vm::Authorization>,
+}
+
+impl ExecutionAuthorization {
+    /// Return the editions to include in the transaction of this execution.
+    pub fn editions(&self) -> IndexMap<vm::ProgramID, Edition> {
+        IndexMap::from([(*self.program.id(), self.edition)])
+    }
+
+    /// Generate the proofs of the authorized execution and its fee, returning the resulting transitions.
+    /// If the proving key is not given, it's synthesized from the program. If the fee proving key is
+    /// not given, it's taken from the credits program build.
+    pub fn prove<R: Rng + CryptoRng>(
+        &self,
+        proving_key: Option<vm::ProvingKey>,
+        fee_proving_key: Option<vm::ProvingKey>,
+        rng: &mut R,
+    ) -> Result<Vec<vm::Transition>> {
+        let mut transitions = vm::prove(&self.program, &self.authorization, proving_key, rng)?;
+
+        let implicit_fee: i64 = transitions.iter().map(Transaction::transition_fee).sum();
+        ensure!(
+            implicit_fee >= 0,
+            "execution produced a negative fee, cannot create credits"
+        );
+
+        if let Some(fee) = &self.fee {
+            let (credits, proving_key) = match fee_proving_key {
+                Some(proving_key) => (vm::generate_program(crate::CREDITS_SOURCE)?, proving_key),
+                None => {
+                    let (credits, mut keys) = load_credits();
+                    let (proving_key, _) = keys
+                        .map
+                        .remove(&vm::Identifier::from_str("fee")?)
+                        .ok_or_else(|| anyhow!("credits function not found"))?;
+                    (credits, proving_key)
+                }
+            };
+            transitions.extend(vm::prove(&credits, fee, Some(proving_key), rng)?);
+        }
+
+        Ok(transitions)
+    }
+}
+
 impl Transaction {
-    // Used to generate deployment of a new program in path
+    // Used to generate deployment of a new program (or a new edition of a program) from its built keys
     pub fn deployment(
-        path: &Path,
+        program_file: &ProgramFile,
         private_key: &vm::PrivateKey,
+        edition: Edition,
         fee: Option<(u64, vm::Record)>,
     ) -> Result<Self> {
-        let program_string = fs::read_to_string(path)?;
-        debug!("Deploying program {}", program_string);
+        debug!("Deploying program {}", program_file.program);
+        let rng = &mut rand::thread_rng();
 
-        // generate program keys (proving and verifying) and keep the verifying one for the deploy
-        let (program, program_build) = vm::build_program(&program_string)?;
+        // certify the keys so the blockchain can check they correspond to the program source
+        let certificates = vm::certify_program(&program_file.keys)?;
 
-        let verifying_keys = program_build
-            .map
-            .into_iter()
-            .map(|(i, keys)| (i, keys.1))
-            .collect();
-
-        let fee = Self::execute_fee(private_key, fee, 0)?;
+        let fee = Self::execute_fee(private_key, fee, 0, rng)?;
+        let view_key = vm::ViewKey::try_from(private_key)?;
+        let owner = vm::Address::try_from(&view_key)?;
 
         Transaction::Deployment {
             id: "not known yet".to_string(),
             fee,
-            program: Box::new(program),
-            verifying_keys: VerifyingKeyMap {
-                map: verifying_keys,
-            },
+            program: Box::new(program_file.program.clone()),
+            verifying_keys: crate::verifying_keys(&program_file.keys),
+            certificates,
+            owner,
+            edition,
+            signature: None,
         }
-        .set_hashed_id()
+        .set_hashed_id()?
+        .sign(private_key)
     }
 
-    // Used to generate an execution of a program in path or an execution of the credits program
-    pub fn execution(
+    // Used to generate an execution of a program in path or an execution of the credits program.
+    // If the proving key is not given, it's synthesized from the program. The randomness of the
+    // execution is taken from the given rng, so seeded rngs produce reproducible transactions.
+    #[allow(clippy::too_many_arguments)]
+    pub fn execution<R: Rng + CryptoRng>(
         program: vm::Program,
         function_name: vm::Identifier,
         inputs: &[vm::UserInputValueType],
         private_key: &vm::PrivateKey,
+        edition: Edition,
+        proving_key: Option<vm::ProvingKey>,
         requested_fee: Option<(u64, vm::Record)>,
+        rng: &mut R,
     ) -> Result<Self> {
-        let mut transitions = vm::execution(program, function_name, inputs, private_key, None)?;
+        let editions = IndexMap::from([(*program.id(), edition)]);
+        let mut transitions = vm::execution(
+            program,
+            function_name,
+            inputs,
+            private_key,
+            proving_key,
+            rng,
+        )?;
 
         // some amount of fees may be implicit if the execution drops credits. in that case, those credits are
         // subtracted from the fees that were requested to be paid.
-        let implicit_fees = transitions.iter().map(|transition| transition.fee()).sum();
-        if let Some(transition) = Self::execute_fee(private_key, requested_fee, implicit_fees)? {
+        let implicit_fees = transitions.iter().map(Self::transition_fee).sum();
+        if let Some(transition) = Self::execute_fee(private_key, requested_fee, implicit_fees, rng)?
+        {
             transitions.push(transition);
         }
 
         Self::Execution {
             id: "not known yet".to_string(),
             transitions,
+            editions,
+            key_signatures: Vec::new(),
         }
         .set_hashed_id()
     }
 
-    pub fn credits_execution(
+    pub fn credits_execution<R: Rng + CryptoRng>(
         function_name: vm::Identifier,
         inputs: &[vm::UserInputValueType],
         private_key: &vm::PrivateKey,
         requested_fee: Option<(u64, vm::Record)>,
+        rng: &mut R,
     ) -> Result<Self> {
         let mut transitions =
-            Self::execute_credits(&function_name.to_string(), inputs, private_key)?;
+            Self::execute_credits(&function_name.to_string(), inputs, private_key, rng)?;
 
         // some amount of fees may be implicit if the execution drops credits. in that case, those credits are
         // subtracted from the fees that were requested to be paid.
-        let implicit_fees = transitions.iter().map(|transition| transition.fee()).sum();
-        if let Some(transition) = Self::execute_fee(private_key, requested_fee, implicit_fees)? {
+        let implicit_fees = transitions.iter().map(Self::transition_fee).sum();
+        if let Some(transition) = Self::execute_fee(private_key, requested_fee, implicit_fees, rng)?
+        {
             transitions.push(transition);
         }
 
+        // the credits program is built-in and can't be upgraded, so its edition is implicit
         Self::Execution {
             id: "not known yet".to_string(),
             transitions,
+            editions: IndexMap::new(),
+            key_signatures: Vec::new(),
         }
         .set_hashed_id()
     }
 
+    /// Sign the requests to execute a program function, and to pay the requested fee if any, without
+    /// generating the proofs. The resulting authorization can be proven without the private key with
+    /// `prove_execution`. Since the implicit fees of the execution are not known until it's proven,
+    /// the requested fee is paid entirely by the fee transition.
+    pub fn authorize_execution<R: Rng + CryptoRng>(
+        program: vm::Program,
+        function_name: vm::Identifier,
+        inputs: &[vm::UserInputValueType],
+        private_key: &vm::PrivateKey,
+        edition: Edition,
+        requested_fee: Option<(u64, vm::Record)>,
+        rng: &mut R,
+    ) -> Result<ExecutionAuthorization> {
+        let authorization = vm::authorize(&program, function_name, inputs, private_key, rng)?;
+
+        let fee = match requested_fee {
+            Some((gates, record)) => {
+                let (credits, _) = load_credits();
+                let inputs = Self::fee_inputs(gates as i64, record)?;
+                let function_name = vm::Identifier::from_str("fee")?;
+                Some(vm::authorize(
+                    &credits,
+                    function_name,
+                    &inputs,
+                    private_key,
+                    rng,
+                )?)
+            }
+            None => None,
+        };
+
+        Ok(ExecutionAuthorization {
+            program,
+            edition,
+            function_name,
+            authorization,
+            fee,
+        })
+    }
+
+    /// Generate the proofs of an authorized execution and return the resulting transaction.
+    /// If the proving key is not given, it's synthesized from the program.
+    pub fn prove_execution<R: Rng + CryptoRng>(
+        authorization: &ExecutionAuthorization,
+        proving_key: Option<vm::ProvingKey>,
+        rng: &mut R,
+    ) -> Result<Self> {
+        let transitions = authorization.prove(proving_key, None, rng)?;
+        Self::from_transitions(transitions, authorization.editions())
+    }
+
+    /// Build an execution transaction from transitions proven elsewhere (e.g. by a prover process).
+    pub fn from_transitions(
+        transitions: Vec<vm::Transition>,
+        editions: IndexMap<vm::ProgramID, Edition>,
+    ) -> Result<Self> {
+        Self::Execution {
+            id: "not known yet".to_string(),
+            transitions,
+            editions,
+            key_signatures: Vec::new(),
+        }
+        .set_hashed_id()
+    }
+
+    /// Attach the signature of a validator key to an execution, e.g. over the staker aleo address to
+    /// register the validator with its stake transition, and update the transaction id accordingly.
+    pub fn with_key_signature(mut self, key_signature: validator::KeySignature) -> Result<Self> {
+        match self {
+            Transaction::Execution {
+                ref mut key_signatures,
+                ..
+            } => key_signatures.push(key_signature),
+            Transaction::Deployment { .. } => bail!("deployments can't register validators"),
+        }
+        self.set_hashed_id()
+    }
+
     pub fn id(&self) -> &str {
         match self {
             Transaction::Deployment { id, .. } => id,
@@ -168,60 +339,208 @@ impl Transaction {
             }
             Transaction::Execution { transitions, .. } => transitions
                 .iter()
-                .fold(0, |acc, transition| acc + transition.fee()),
+                .fold(0, |acc, transition| acc + Self::transition_fee(transition)),
         }
     }
 
+    /// Return the fee of a transition, i.e. the difference between its input and output gates.
+    /// The gates taken out of staked_credits records by the credits unstake function are not a fee,
+    /// since the blockchain pays them back to the staker when the transaction is applied.
+    fn transition_fee(transition: &vm::Transition) -> i64 {
+        let unstaked = match Self::staked_gates(transition) {
+            Ok(Some((gates, _))) if gates < 0 => gates,
+            _ => 0,
+        };
+        *transition.fee() + unstaked
+    }
+
+    /// If the transition is of the credits stake or unstake functions, return the amount of gates
+    /// it staked (positive) or unstaked (negative), along with the index of that output. The staker
+    /// address and the validator key parts are output right after it.
+    fn staked_gates(transition: &vm::Transition) -> Result<Option<(i64, usize)>> {
+        if !is_credits_program(transition.program_id()) {
+            return Ok(None);
+        }
+
+        let (index, sign) = match transition.function_name().to_string().as_str() {
+            "stake" => (2, 1),
+            "unstake" => (1, -1),
+            _ => return Ok(None),
+        };
+        let output = transition
+            .outputs()
+            .get(index)
+            .ok_or_else(|| anyhow!("couldn't find staking output in transition"))?;
+        let gates = vm::int_from_output::<u64>(output)? as i64;
+        Ok(Some((sign * gates, index)))
+    }
+
     /// Extract a list of validator updates that result from the current execution.
     /// This will return a non-empty vector in case some of the transitions are of the
     /// stake or unstake functions in the credits program.
     pub fn stake_updates(&self) -> Result<Vec<validator::Stake>> {
         let mut result = Vec::new();
-        if let Self::Execution { transitions, .. } = self {
+        if let Self::Execution {
+            transitions,
+            key_signatures,
+            ..
+        } = self
+        {
             for transition in transitions {
-                if transition.program_id().to_string() == "credits.aleo" {
-                    let extract_output = |index: usize| {
-                        transition
-                            .outputs()
-                            .get(index)
-                            .ok_or_else(|| anyhow!("couldn't find staking output in transition"))
-                    };
-
-                    let amount = match transition.function_name().to_string().as_str() {
-                        "stake" => vm::int_from_output::<u64>(extract_output(2)?)? as i64,
-                        "unstake" => -(vm::int_from_output::<u64>(extract_output(2)?)? as i64),
-                        _ => continue,
-                    };
-
-                    // TODO: Factor out the following extraction and test it as with the original conversion
+                if let Some((amount, index)) = Self::staked_gates(transition)? {
+                    let (validator, aleo_address) = Self::validator_output(transition, index + 1)?;
+                    let mut stake = validator::Stake::new(&validator, aleo_address, amount)?;
+                    for key_signature in key_signatures {
+                        stake = stake.with_key_signature(key_signature)?;
+                    }
+                    result.push(stake);
+                }
+            }
+        }
+        Ok(result)
+    }
 
-                    let validator_key: [u64; 4] = [
-                        vm::int_from_output(extract_output(4)?)?,
-                        vm::int_from_output(extract_output(5)?)?,
-                        vm::int_from_output(extract_output(6)?)?,
-                        vm::int_from_output(extract_output(7)?)?,
-                    ];
+    /// Extract the requests made by stakers to their validators in the current execution, i.e.
+    /// its transitions of the given function in the credits program, e.g. unjail or withdraw.
+    pub fn staker_requests(&self, function: &str) -> Result<Vec<validator::StakerRequest>> {
+        let mut result = Vec::new();
+        if let Self::Execution { transitions, .. } = self {
+            for transition in transitions {
+                if is_credits_program(transition.program_id())
+                    && transition.function_name().to_string() == function
+                {
+                    let (validator, aleo_address) = Self::validator_output(transition, 1)?;
+                    result.push(validator::StakerRequest::new(&validator, aleo_address)?);
+                }
+            }
+        }
+        Ok(result)
+    }
 
-                    let validator = Transaction::validator_key_from_u64s(&validator_key)?;
+    /// Extract the changes requested by validator operators in the current execution, i.e. its transitions
+    /// of the rotate_key and change_reward_address functions in the credits program, along with the
+    /// validator key signatures that authorize them.
+    pub fn validator_changes(&self) -> Result<Vec<validator::ValidatorChange>> {
+        let mut result = Vec::new();
+        if let Self::Execution {
+            transitions,
+            key_signatures,
+            ..
+        } = self
+        {
+            for transition in transitions {
+                if !is_credits_program(transition.program_id()) {
+                    continue;
+                }
+                let kind = match transition.function_name().to_string().as_str() {
+                    "rotate_key" => validator::ChangeKind::RotateKey,
+                    "change_reward_address" => {
+                        let output = transition.outputs().get(6).ok_or_else(|| {
+                            anyhow!("couldn't find reward address output in transition")
+                        })?;
+                        validator::ChangeKind::RewardAddress(vm::address_from_output(output)?)
+                    }
+                    _ => continue,
+                };
+                let (validator, aleo_address) = Self::validator_output(transition, 1)?;
+                result.push(validator::ValidatorChange::new(
+                    validator::StakerRequest::new(&validator, aleo_address)?,
+                    kind,
+                    key_signatures.clone(),
+                ));
+            }
+        }
+        Ok(result)
+    }
 
-                    let aleo_address = vm::address_from_output(extract_output(3)?)?;
-                    let validator = validator::Stake::new(&validator, aleo_address, amount)?;
+    /// Extract the accounts that claim their accrued rewards in the current execution, i.e. the addresses
+    /// output by its transitions of the claim_rewards function in the credits program.
+    pub fn reward_claims(&self) -> Result<Vec<vm::Address>> {
+        let mut result = Vec::new();
+        if let Self::Execution { transitions, .. } = self {
+            for transition in transitions {
+                if is_credits_program(transition.program_id())
+                    && transition.function_name().to_string() == "claim_rewards"
+                {
+                    let output = transition
+                        .outputs()
+                        .first()
+                        .ok_or_else(|| anyhow!("couldn't find claimer output in transition"))?;
+                    result.push(vm::address_from_output(output)?);
+                }
+            }
+        }
+        Ok(result)
+    }
 
-                    result.push(validator);
+    /// Extract the spends from the treasury requested in the current execution, i.e. its transitions of the
+    /// spend_treasury function in the credits program, along with the treasury key signatures that authorize them.
+    pub fn treasury_spends(&self) -> Result<Vec<validator::TreasurySpend>> {
+        let mut result = Vec::new();
+        if let Self::Execution {
+            transitions,
+            key_signatures,
+            ..
+        } = self
+        {
+            for transition in transitions {
+                if is_credits_program(transition.program_id())
+                    && transition.function_name().to_string() == "spend_treasury"
+                {
+                    let extract_output = |index: usize| {
+                        transition.outputs().get(index).ok_or_else(|| {
+                            anyhow!("couldn't find treasury spend output in transition")
+                        })
+                    };
+                    let recipient = vm::address_from_output(extract_output(0)?)?;
+                    let amount = vm::int_from_output::<u64>(extract_output(1)?)?;
+                    result.push(validator::TreasurySpend::new(
+                        recipient,
+                        amount,
+                        key_signatures.clone(),
+                    ));
                 }
             }
         }
         Ok(result)
     }
 
+    /// Return the validator public key and the staker aleo address output by a credits transition
+    /// starting at the given index: the address first, followed by the four parts of the key.
+    fn validator_output(
+        transition: &vm::Transition,
+        index: usize,
+    ) -> Result<(String, vm::Address)> {
+        let extract_output = |offset: usize| {
+            transition
+                .outputs()
+                .get(index + offset)
+                .ok_or_else(|| anyhow!("couldn't find staking output in transition"))
+        };
+
+        // TODO: Factor out the following extraction and test it as with the original conversion
+
+        let validator_key: [u64; 4] = [
+            vm::int_from_output(extract_output(1)?)?,
+            vm::int_from_output(extract_output(2)?)?,
+            vm::int_from_output(extract_output(3)?)?,
+            vm::int_from_output(extract_output(4)?)?,
+        ];
+
+        let validator = Transaction::validator_key_from_u64s(&validator_key)?;
+        let aleo_address = vm::address_from_output(extract_output(0)?)?;
+        Ok((validator, aleo_address))
+    }
+
     /// If there is some required fee, return the transition resulting of executing
     /// the fee function of the credits program for the requested amount.
     /// The fee function just burns the desired amount of credits, so its effect is just
     /// to produce a difference between the input/output records of its transition.
-    fn execute_fee(
+    fn execute_fee<R: Rng + CryptoRng>(
         private_key: &vm::PrivateKey,
         requested_fee: Option<(u64, vm::Record)>,
         implicit_fee: i64,
+        rng: &mut R,
     ) -> Result<Option<vm::Transition>> {
         if let Some((gates, record)) = requested_fee {
             ensure!(
@@ -234,36 +553,42 @@ impl Transaction {
                 return Ok(None);
             }
 
-            let gates = gates as i64 - implicit_fee;
-            #[cfg(feature = "lambdavm_backend")]
-            let inputs = [
-                vm::UserInputValueType::Record(crate::vm::Record {
-                    owner: record.owner,
-                    gates: record.gates,
-                    data: record.data,
-                    nonce: record.nonce,
-                }),
-                // TODO: Revisit the cast below.
-                vm::UserInputValueType::U64(gates as u64),
-            ];
-
-            #[cfg(feature = "snarkvm_backend")]
-            let inputs = [
-                vm::UserInputValueType::Record(record),
-                vm::UserInputValueType::from_str(&format!("{gates}u64"))?,
-            ];
-
-            let transitions = Self::execute_credits("fee", &inputs, private_key)?;
+            let inputs = Self::fee_inputs(gates as i64 - implicit_fee, record)?;
+            let transitions = Self::execute_credits("fee", &inputs, private_key, rng)?;
             Ok(Some(transitions.first().unwrap().clone()))
         } else {
             Ok(None)
         }
     }
 
-    fn execute_credits(
+    /// Return the inputs of the credits fee function to pay the given amount of gates with the given record.
+    fn fee_inputs(gates: i64, record: vm::Record) -> Result<[vm::UserInputValueType; 2]> {
+        #[cfg(feature = "lambdavm_backend")]
+        let inputs = [
+            vm::UserInputValueType::Record(crate::vm::Record {
+                owner: record.owner,
+                gates: record.gates,
+                data: record.data,
+                nonce: record.nonce,
+            }),
+            // TODO: Revisit the cast below.
+            vm::UserInputValueType::U64(gates as u64),
+        ];
+
+        #[cfg(feature = "snarkvm_backend")]
+        let inputs = [
+            vm::UserInputValueType::Record(record),
+            vm::UserInputValueType::from_str(&format!("{gates}u64"))?,
+        ];
+
+        Ok(inputs)
+    }
+
+    fn execute_credits<R: Rng + CryptoRng>(
         function: &str,
         inputs: &[vm::UserInputValueType],
         private_key: &vm::PrivateKey,
+        rng: &mut R,
     ) -> Result<Vec<vm::Transition>> {
         let function = vm::Identifier::from_str(function)?;
         let (program, keys) = load_credits();
@@ -278,19 +603,51 @@ impl Transaction {
             inputs,
             private_key,
             Some(proving_key.clone()),
+            rng,
         )
     }
 
     /// Verify that the transaction id is consistent with its contents, by checking it's sha256 hash.
+    /// Deployments additionally need to be signed by their owner.
     pub fn verify(&self) -> Result<()> {
         ensure!(
             self.id() == self.hash()?,
             "Corrupted transaction: Inconsistent transaction id"
         );
 
+        if let Transaction::Deployment {
+            id,
+            owner,
+            signature,
+            ..
+        } = self
+        {
+            let signature = signature
+                .as_ref()
+                .ok_or_else(|| anyhow!("Deployment is not signed by its owner"))?;
+            ensure!(
+                vm::verify_signature(owner, id.as_bytes(), signature),
+                "Invalid deployment signature for owner {owner}"
+            );
+        }
+
         Ok(())
     }
 
+    /// Sign the id of a deployment with the owner's private key. Since the id is a hash
+    /// of the transaction contents, the signature authorizes this specific deployment.
+    fn sign(mut self, private_key: &vm::PrivateKey) -> Result<Self> {
+        if let Transaction::Deployment {
+            ref id,
+            ref mut signature,
+            ..
+        } = self
+        {
+            *signature = Some(vm::sign(private_key, id.as_bytes())?);
+        }
+        Ok(self)
+    }
+
     /// Hash the contents of the given enum and return it with the hash as its id.
     fn set_hashed_id(mut self) -> Result<Self> {
         let new_id = self.hash()?;
@@ -316,9 +673,15 @@ impl Transaction {
                 id: _id,
                 program,
                 verifying_keys,
+                certificates,
+                owner,
+                edition,
+                signature: _signature,
                 fee,
             } => {
                 hasher.update(program.id().to_string());
+                hasher.update(owner.to_string());
+                hasher.update(edition.to_be_bytes());
 
                 for (key, value) in verifying_keys.map.clone().into_iter() {
                     hasher.update(key.to_string());
@@ -329,6 +692,11 @@ impl Transaction {
                     hasher.update(serialization);
                 }
 
+                for (key, value) in certificates.map.iter() {
+                    hasher.update(key.to_string());
+                    hasher.update(serde_json::to_string(value)?);
+                }
+
                 if let Some(fee) = fee {
                     hasher.update(serde_json::to_string(fee)?);
                 }
@@ -336,10 +704,19 @@ impl Transaction {
             Transaction::Execution {
                 id: _id,
                 transitions,
+                editions,
+                key_signatures,
             } => {
+                for (program_id, edition) in editions.iter() {
+                    hasher.update(program_id.to_string());
+                    hasher.update(edition.to_be_bytes());
+                }
                 for transition in transitions.iter() {
                     hasher.update(serde_json::to_string(transition)?);
                 }
+                for key_signature in key_signatures.iter() {
+                    hasher.update(serde_json::to_string(key_signature)?);
+                }
             }
         }
 
@@ -410,7 +787,9 @@ impl std::fmt::Display for Transaction {
             Transaction::Deployment { id, program, .. } => {
                 write!(f, "Deployment({},{})", id, program.id())
             }
-            Transaction::Execution { id, transitions } => {
+            Transaction::Execution {
+                id, transitions, ..
+            } => {
                 let transition = transitions.first().unwrap();
                 write!(f, "Execution({},{id})", transition.program_id())
             }
@@ -421,6 +800,10 @@ impl std::fmt::Display for Transaction {
 #[cfg(test)]
 mod tests {
     use crate::transaction::Transaction;
+    use crate::vm;
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+    use std::str::FromStr;
 
     #[test]
     fn convert_validator_address_u128() {
@@ -449,4 +832,37 @@ mod tests {
         assert_eq!(key_sections, expected_slice);
         assert!(Transaction::validator_key_from_u64s(&key_sections).unwrap() == pub_key);
     }
+
+    // lambdavm doesn't support injecting the execution randomness yet
+    #[cfg(feature = "snarkvm_backend")]
+    #[test]
+    fn seeded_execution_is_reproducible() {
+        let private_key = vm::PrivateKey::new(&mut ChaCha8Rng::seed_from_u64(1)).unwrap();
+        let program = vm::generate_program(include_str!("../../aleo/hello.aleo")).unwrap();
+        let inputs = [
+            vm::UserInputValueType::from_str("1u32").unwrap(),
+            vm::UserInputValueType::from_str("2u32").unwrap(),
+        ];
+        let execute = |seed| {
+            Transaction::execution(
+                program.clone(),
+                vm::Identifier::from_str("hello").unwrap(),
+                &inputs,
+                &private_key,
+                0,
+                None,
+                None,
+                &mut ChaCha8Rng::seed_from_u64(seed),
+            )
+            .unwrap()
+        };
+
+        let transaction = execute(42);
+        assert_eq!(transaction.id(), execute(42).id());
+        assert_eq!(
+            serde_json::to_string(&transaction).unwrap(),
+            serde_json::to_string(&execute(42)).unwrap()
+        );
+        assert_ne!(transaction.id(), execute(43).id());
+    }
 }
@@ -1,6 +1,8 @@
+use std::path::Path;
 use std::str::FromStr;
 
 use anyhow::{anyhow, ensure, Result};
+use ed25519_dalek::Signer;
 use log::debug;
 use serde::{Deserialize, Serialize};
 
@@ -9,13 +11,58 @@ use crate::vm;
 pub type VotingPower = u64;
 pub type Address = Vec<u8>;
 
+/// The baseline credits minted on every block, unless the genesis sets a different emission schedule.
+pub const DEFAULT_BLOCK_REWARD: u64 = 100;
+
+/// The maximum baseline credits minted on every block, so the minted supply can't overflow in any
+/// foreseeable amount of blocks.
+pub const MAX_BLOCK_REWARD: u64 = u32::MAX as u64;
+
+/// The scale of the fixed-point factor applied to the initial block reward by the emission schedule.
+const EMISSION_FACTOR_SCALE: u128 = 1_000_000_000_000_000_000;
+
 /// Represents a validator node in the blockchain with a given voting power for the consensus
-/// protocol. Each validator has an associated tendermint public key and an aleo account.
+/// protocol. Each validator has an associated tendermint public key and an aleo account, the
+/// operator's, which receives its rewards. Other accounts can delegate their credits to it.
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct Validator {
     pub aleo_address: vm::Address,
     pub pub_key: tendermint::PublicKey,
+    /// The gates currently backing the validator, after discounting slashed ones.
     pub voting_power: VotingPower,
+    /// The gates held by the staked_credits records bound to this validator (plus its genesis voting power).
+    /// It only differs from the voting power after the validator is slashed, in which case those records
+    /// are worth proportionally less when unstaked.
+    #[serde(default)]
+    pub staked: u64,
+    /// Jailed validators keep their stake but have no voting power in the consensus.
+    #[serde(default)]
+    pub jailed: bool,
+    /// The height from which a jailed validator can be unjailed. Validators jailed for
+    /// double signing can't return, so it's set to the maximum height for them.
+    #[serde(default)]
+    pub jailed_until: u64,
+    /// The gates staked on the validator by accounts other than the operator's, included in the staked gates.
+    #[serde(default)]
+    pub delegations: Vec<Delegation>,
+    /// The aleo account that receives the operator's rewards, if changed from the operator's account.
+    #[serde(default)]
+    pub reward_address: Option<vm::Address>,
+}
+
+/// The gates an account staked on a validator it doesn't operate.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct Delegation {
+    pub aleo_address: vm::Address,
+    pub staked: u64,
+}
+
+/// A request concerning a validator made by the aleo account that staked on it, e.g. to return it to
+/// the consensus after being jailed or to withdraw the credits unstaked from it.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct StakerRequest {
+    aleo_address: vm::Address,
+    pub_key: tendermint::PublicKey,
 }
 
 /// Represents an amount of credits (positive or negative) that are staked on a specific validator.
@@ -24,12 +71,196 @@ pub struct Stake {
     aleo_address: vm::Address,
     pub_key: tendermint::PublicKey,
     gates_delta: i64,
+    /// The base64 encoded signature of the validator key over the staker aleo address, required
+    /// to register a new validator.
+    #[serde(default)]
+    key_signature: Option<String>,
+}
+
+/// A signature of a tendermint validator key over the aleo address of a staker, which proves
+/// that the staker holds the key when its first stake registers it as a validator. Otherwise
+/// anyone could bind the key of someone else's node to their own aleo account.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq)]
+pub struct KeySignature {
+    /// The base64 encoded ed25519 public key of the validator, as it appears in tendermint JSON files.
+    pub pub_key: String,
+    /// The base64 encoded ed25519 signature of the signed message, e.g. the staker aleo address.
+    pub signature: String,
+}
+
+/// A change of a validator requested by its operator, which needs to be authorized both by the operator
+/// aleo account, by executing the credits function of the change, and by the validator tendermint key.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct ValidatorChange {
+    request: StakerRequest,
+    kind: ChangeKind,
+    /// The validator key signatures carried by the transaction that requested the change.
+    key_signatures: Vec<KeySignature>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq)]
+pub enum ChangeKind {
+    /// Replace the validator consensus key with the other key that signed the change.
+    RotateKey,
+    /// Send the rewards of the operator to a different aleo account.
+    RewardAddress(vm::Address),
 }
 
 #[derive(Deserialize, Serialize)]
 pub struct GenesisState {
     pub records: Vec<(vm::Field, vm::EncryptedRecord)>,
     pub validators: Vec<Validator>,
+    #[serde(default)]
+    pub staking: StakingParams,
+    /// The gates of the genesis records, which can't be added up from their ciphertexts.
+    #[serde(default)]
+    pub supply: u64,
+}
+
+/// Network parameters that rule how validators are rewarded and punished for misbehaving, set at genesis.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq)]
+#[serde(default)]
+pub struct StakingParams {
+    /// The percentage of its voting power a validator loses when there's evidence of it double signing.
+    pub double_sign_slash_percentage: u64,
+    /// Whether a validator that double signed is jailed, removing it from the consensus.
+    pub jail_on_double_sign: bool,
+    /// The amount of recent blocks considered to decide whether a validator is offline.
+    pub signed_blocks_window: u64,
+    /// A validator that doesn't sign more than this amount of blocks within the window is jailed.
+    pub max_missed_blocks: u64,
+    /// The amount of blocks a validator jailed for being offline has to wait before unjailing.
+    pub downtime_jail_blocks: u64,
+    /// The amount of blocks unstaked credits have to wait before they can be withdrawn.
+    pub unbonding_blocks: u64,
+    /// The percentage of the rewards of a validator its operator takes before splitting the rest
+    /// with its delegators.
+    pub commission_percentage: u64,
+    /// The maximum amount of validators that take part in the consensus. The ones with the most voting
+    /// power are chosen, the rest are kept as candidates.
+    pub max_validators: u64,
+    /// The gates the operator of a validator needs to have staked on it to take part in the consensus.
+    pub min_self_stake: u64,
+    /// How the baseline credits minted on every block change with the height.
+    pub emission: EmissionSchedule,
+    /// The percentage of the fees of each block that is burned instead of rewarded to validators.
+    pub fee_burn_percentage: u64,
+    /// The percentage of the fees of each block that accrues to the treasury instead of being rewarded
+    /// to validators.
+    pub treasury_percentage: u64,
+    /// The base64 encoded ed25519 public keys that can authorize spends from the treasury.
+    pub treasury_keys: Vec<String>,
+    /// The amount of treasury keys that need to sign a spend from the treasury.
+    pub treasury_threshold: u64,
+}
+
+/// The baseline credits minted on every block as validator rewards, which are cut by a percentage every
+/// given amount of blocks, e.g. halved every million blocks.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq)]
+#[serde(default)]
+pub struct EmissionSchedule {
+    /// The credits minted on each block until the first reduction.
+    pub initial_reward: u64,
+    /// The amount of blocks between reductions. If zero, the reward is never reduced.
+    pub reduction_interval: u64,
+    /// The percentage of the reward cut on each reduction.
+    pub reduction_percentage: u64,
+}
+
+/// The credits in circulation, including the staked ones, the rewards not claimed yet and the treasury,
+/// which can only come from the genesis records and the credits minted as block rewards.
+#[derive(Debug, Deserialize, Serialize, Clone, Default, PartialEq, Eq)]
+pub struct Supply {
+    /// The gates of the genesis records.
+    pub genesis: u64,
+    /// The baseline rewards minted since genesis.
+    pub minted: u64,
+    /// The credits taken out of circulation: the burned part of the fees, or all of them on blocks
+    /// without rewards, and the credits slashed from stakers, once they unstake them.
+    pub burned: u64,
+}
+
+/// The credits accrued from the fees of each block by the treasury, which can only be spent with the
+/// signatures of the treasury keys.
+#[derive(Debug, Deserialize, Serialize, Clone, Default, PartialEq, Eq)]
+pub struct Treasury {
+    pub balance: u64,
+    /// The amount of spends made from the treasury, which is part of the message signed by the treasury
+    /// keys so their signatures can't be replayed.
+    pub spends: u64,
+    /// The id of the chain, which is part of the message signed by the treasury keys so their signatures
+    /// can't be replayed on other networks that use the same keys.
+    #[serde(default)]
+    pub chain_id: String,
+}
+
+/// A payment of credits out of the treasury to an aleo account, along with the signatures of the
+/// treasury keys that authorize it.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq)]
+pub struct TreasurySpend {
+    recipient: vm::Address,
+    amount: u64,
+    key_signatures: Vec<KeySignature>,
+}
+
+impl Default for StakingParams {
+    fn default() -> Self {
+        Self {
+            double_sign_slash_percentage: 5,
+            jail_on_double_sign: true,
+            signed_blocks_window: 100,
+            max_missed_blocks: 50,
+            downtime_jail_blocks: 100,
+            unbonding_blocks: 100,
+            commission_percentage: 10,
+            max_validators: 100,
+            min_self_stake: 0,
+            emission: EmissionSchedule::default(),
+            fee_burn_percentage: 0,
+            treasury_percentage: 0,
+            treasury_keys: Vec::new(),
+            treasury_threshold: 0,
+        }
+    }
+}
+
+impl Default for EmissionSchedule {
+    fn default() -> Self {
+        Self {
+            initial_reward: DEFAULT_BLOCK_REWARD,
+            reduction_interval: 0,
+            reduction_percentage: 0,
+        }
+    }
+}
+
+impl EmissionSchedule {
+    /// Return the baseline credits minted on the block of the given height. The reductions are applied
+    /// to a fixed-point factor of the initial reward instead of the rounded reward itself, so small
+    /// percentages keep reducing it until it rounds down to zero.
+    pub fn block_reward(&self, height: u64) -> u64 {
+        if self.reduction_interval == 0 || self.reduction_percentage == 0 {
+            return self.initial_reward;
+        }
+
+        let kept_percentage = 100 - self.reduction_percentage.min(100) as u128;
+        let mut factor = EMISSION_FACTOR_SCALE;
+        for _ in 0..height / self.reduction_interval {
+            factor = factor * kept_percentage / 100;
+            // stop once the reward is zero, it can't grow back
+            if self.initial_reward as u128 * factor < EMISSION_FACTOR_SCALE {
+                return 0;
+            }
+        }
+        (self.initial_reward as u128 * factor / EMISSION_FACTOR_SCALE) as u64
+    }
+}
+
+impl Supply {
+    /// Return the total credits in circulation.
+    pub fn total(&self) -> u64 {
+        self.genesis + self.minted - self.burned
+    }
 }
 
 impl Validator {
@@ -41,6 +272,11 @@ impl Validator {
             pub_key: parse_pub_key(pub_key)?,
             aleo_address,
             voting_power,
+            staked: voting_power,
+            jailed: false,
+            jailed_until: 0,
+            delegations: Vec::new(),
+            reward_address: None,
         })
     }
 
@@ -54,13 +290,20 @@ impl Validator {
             aleo_address: stake.aleo_address,
             pub_key: stake.pub_key,
             voting_power: stake.gates_delta as u64,
+            staked: stake.gates_delta as u64,
+            jailed: false,
+            jailed_until: 0,
+            delegations: Vec::new(),
+            reward_address: None,
         })
     }
 
-    /// Update the validator voting power based on the given change in stake.
+    /// Update the validator voting power based on the given change in stake, returning the gates
+    /// to pay back to the staker, which are less than the unstaked ones if the validator was slashed.
+    /// Stakes from accounts other than the operator's are tracked as delegations.
     /// It will fail if the stake belongs to a different validator or if more stake than
-    /// available is attempted to be removed.
-    pub fn apply(&mut self, stake: &Stake) -> Result<()> {
+    /// the account has staked on the validator is attempted to be removed.
+    pub fn apply(&mut self, stake: &Stake) -> Result<u64> {
         ensure!(
             self.address() == stake.validator_address(),
             "attempted to apply a staking update on a different validator. expected {} received {}",
@@ -68,20 +311,165 @@ impl Validator {
             stake
         );
 
-        ensure!(self.aleo_address == stake.aleo_address,
-                "attempted to apply a staking update on a different aleo account. expected {} received {}",
-                self.aleo_address, stake.aleo_address);
+        let is_delegation = self.aleo_address != stake.aleo_address;
+        if stake.gates_delta > 0 {
+            let gates = stake.gates_delta as u64;
+            self.voting_power += gates;
+            self.staked += gates;
+            if is_delegation {
+                match self.delegation_mut(&stake.aleo_address) {
+                    Some(delegation) => delegation.staked += gates,
+                    None => self.delegations.push(Delegation {
+                        aleo_address: stake.aleo_address,
+                        staked: gates,
+                    }),
+                }
+            }
+            return Ok(0);
+        }
 
-        let new_power = self.voting_power as i64 + stake.gates_delta;
+        let unstaked = stake.gates_delta.unsigned_abs();
+        let available = if is_delegation {
+            self.delegation_mut(&stake.aleo_address)
+                .map_or(0, |delegation| delegation.staked)
+        } else {
+            self.self_staked()
+        };
         ensure!(
-            new_power >= 0,
+            unstaked <= available,
             "attempted to unstake more voting power than available for {self}"
         );
-        self.voting_power = new_power as u64;
+        // the staked credits are worth what's left of the voting power backing them
+        let payout = (unstaked as u128 * self.voting_power as u128 / self.staked as u128) as u64;
+        self.voting_power -= payout;
+        self.staked -= unstaked;
+        if is_delegation {
+            self.delegations.retain_mut(|delegation| {
+                if delegation.aleo_address == stake.aleo_address {
+                    delegation.staked -= unstaked;
+                }
+                delegation.staked > 0
+            });
+        }
+
+        Ok(payout)
+    }
+
+    /// Return the staked gates delegated to the validator by other accounts.
+    pub fn delegated(&self) -> u64 {
+        self.delegations
+            .iter()
+            .map(|delegation| delegation.staked)
+            .sum()
+    }
+
+    /// Return the staked gates of the validator that belong to its operator.
+    pub fn self_staked(&self) -> u64 {
+        self.staked - self.delegated()
+    }
+
+    /// Return the part of the voting power that backs the credits delegated by other accounts.
+    pub fn delegated_power(&self) -> VotingPower {
+        if self.staked == 0 {
+            return 0;
+        }
+        (self.delegated() as u128 * self.voting_power as u128 / self.staked as u128) as u64
+    }
+
+    /// Split a reward between the operator and the delegators of the validator: the operator takes the
+    /// given commission percentage and the rest is distributed in proportion to the gates each account
+    /// staked, including the operator. Leftovers from rounding go to the operator.
+    pub fn split_reward(&self, reward: u64, commission_percentage: u64) -> Vec<(vm::Address, u64)> {
+        let mut operator_reward = reward;
+        let mut rewards = Vec::new();
+        if self.staked > 0 {
+            let commission = reward * commission_percentage.min(100) / 100;
+            let stakers_reward = (reward - commission) as u128;
+            for delegation in &self.delegations {
+                let share =
+                    (stakers_reward * delegation.staked as u128 / self.staked as u128) as u64;
+                operator_reward -= share;
+                rewards.push((delegation.aleo_address, share));
+            }
+        }
+        rewards.insert(0, (self.reward_address(), operator_reward));
+        rewards
+    }
+
+    /// Return the aleo account that receives the operator's rewards.
+    pub fn reward_address(&self) -> vm::Address {
+        self.reward_address.unwrap_or(self.aleo_address)
+    }
+
+    /// Check that a change of the validator is requested by its operator and authorized by its key.
+    pub fn authorize(&self, change: &ValidatorChange) -> Result<()> {
+        ensure!(
+            self.aleo_address == change.request.aleo_address,
+            "attempted to change a validator from a different aleo account. expected {} received {}",
+            self.aleo_address,
+            change.request.aleo_address
+        );
+        change.verify(&self.pub_key)
+    }
+
+    fn delegation_mut(&mut self, aleo_address: &vm::Address) -> Option<&mut Delegation> {
+        self.delegations
+            .iter_mut()
+            .find(|delegation| &delegation.aleo_address == aleo_address)
+    }
 
+    /// Cut the given percentage of the validator voting power, returning the amount slashed.
+    pub fn slash(&mut self, percentage: u64) -> VotingPower {
+        let slashed = (self.voting_power as u128 * percentage.min(100) as u128 / 100) as u64;
+        self.voting_power -= slashed;
+        slashed
+    }
+
+    /// Jail the validator, removing it from the consensus until the given height.
+    pub fn jail(&mut self, until: u64) {
+        self.jailed = true;
+        self.jailed_until = self.jailed_until.max(until);
+    }
+
+    /// Return the validator to the consensus, failing if it's not jailed, the jail time isn't over yet
+    /// or the request comes from an account other than the validator's.
+    pub fn unjail(&mut self, request: &StakerRequest, height: u64) -> Result<()> {
+        ensure!(
+            self.address() == request.validator_address(),
+            "attempted to unjail a different validator. expected {} received {}",
+            self,
+            request
+        );
+        ensure!(
+            self.aleo_address == request.aleo_address,
+            "attempted to unjail a validator from a different aleo account. expected {} received {}",
+            self.aleo_address,
+            request.aleo_address
+        );
+        ensure!(self.jailed, "validator {self} is not jailed");
+        ensure!(
+            self.jailed_until != u64::MAX,
+            "validator {self} was jailed for double signing and can't be unjailed"
+        );
+        ensure!(
+            height >= self.jailed_until,
+            "validator {self} is jailed until block {}",
+            self.jailed_until
+        );
+
+        self.jailed = false;
         Ok(())
     }
 
+    /// Return the voting power to inform to tendermint, which is zero for jailed validators.
+    pub fn consensus_power(&self) -> VotingPower {
+        if self.jailed {
+            0
+        } else {
+            self.voting_power
+        }
+    }
+
     /// Return the tendermint validator address (which is derived from its public key) as bytes.
     pub fn address(&self) -> Address {
         pub_key_to_address(&self.pub_key)
@@ -97,6 +485,63 @@ impl Stake {
             pub_key: parse_pub_key(pub_key)?,
             aleo_address,
             gates_delta,
+            key_signature: None,
+        })
+    }
+
+    /// Attach the signature of the validator key over the staker aleo address, needed to register
+    /// a new validator. Signatures of other validator keys are ignored.
+    pub fn with_key_signature(mut self, key_signature: &KeySignature) -> Result<Self> {
+        if parse_pub_key(&key_signature.pub_key)? == self.pub_key {
+            self.key_signature = Some(key_signature.signature.clone());
+        }
+        Ok(self)
+    }
+
+    /// Verify that the staker holds the validator key, i.e. that the stake carries a valid
+    /// signature of the key over the staker aleo address.
+    pub fn verify_key_signature(&self) -> Result<()> {
+        let signature = self.key_signature.as_ref().ok_or_else(|| {
+            anyhow!("the first stake on validator {self} must be signed by the validator key")
+        })?;
+        verify_signature(
+            &self.pub_key,
+            signature,
+            self.aleo_address.to_string().as_bytes(),
+        )
+        .map_err(|_| anyhow!("invalid validator key signature for stake {self}"))
+    }
+
+    /// Point the stake to the given key, i.e. the current key of its validator if it was rotated
+    /// after the credits were staked.
+    pub fn with_pub_key(mut self, pub_key: tendermint::PublicKey) -> Self {
+        self.pub_key = pub_key;
+        self
+    }
+
+    /// Return the tendermint validator address (which is derived from its public key) as bytes.
+    pub fn validator_address(&self) -> Address {
+        pub_key_to_address(&self.pub_key)
+    }
+
+    /// Return the aleo account doing the staking, which receives the credits when unstaking.
+    pub fn aleo_address(&self) -> vm::Address {
+        self.aleo_address
+    }
+
+    /// Return the amount of gates staked (positive) or unstaked (negative).
+    pub fn gates_delta(&self) -> i64 {
+        self.gates_delta
+    }
+}
+
+impl StakerRequest {
+    /// Construct a request for a specific validator, identified by its base64 encoded ed25519
+    /// public key string, from the given aleo account.
+    pub fn new(pub_key: &str, aleo_address: vm::Address) -> Result<Self> {
+        Ok(Self {
+            pub_key: parse_pub_key(pub_key)?,
+            aleo_address,
         })
     }
 
@@ -104,6 +549,202 @@ impl Stake {
     pub fn validator_address(&self) -> Address {
         pub_key_to_address(&self.pub_key)
     }
+
+    /// Return the aleo account making the request.
+    pub fn aleo_address(&self) -> vm::Address {
+        self.aleo_address
+    }
+
+    /// Point the request to the given key, i.e. the current key of its validator if it was rotated
+    /// after the credits were staked.
+    pub fn with_pub_key(mut self, pub_key: tendermint::PublicKey) -> Self {
+        self.pub_key = pub_key;
+        self
+    }
+}
+
+impl ValidatorChange {
+    pub fn new(
+        request: StakerRequest,
+        kind: ChangeKind,
+        key_signatures: Vec<KeySignature>,
+    ) -> Self {
+        Self {
+            request,
+            kind,
+            key_signatures,
+        }
+    }
+
+    /// Return the message the current and new validator keys sign to rotate the key of a validator
+    /// operated by the given aleo account.
+    pub fn rotate_key_message(aleo_address: &vm::Address, new_key: &str) -> String {
+        format!("rotate_key {aleo_address} {new_key}")
+    }
+
+    /// Return the message the validator key signs to send the rewards of its operator to a different aleo account.
+    pub fn reward_address_message(
+        aleo_address: &vm::Address,
+        reward_address: &vm::Address,
+    ) -> String {
+        format!("change_reward_address {aleo_address} {reward_address}")
+    }
+
+    pub fn request(&self) -> &StakerRequest {
+        &self.request
+    }
+
+    pub fn kind(&self) -> &ChangeKind {
+        &self.kind
+    }
+
+    /// Return the key a validator with the given current key is rotated to: the other key that signed
+    /// the rotation, which proves the operator holds it.
+    pub fn new_key(&self, pub_key: &tendermint::PublicKey) -> Result<tendermint::PublicKey> {
+        self.key_signatures
+            .iter()
+            .find_map(|key_signature| {
+                let key = parse_pub_key(&key_signature.pub_key).ok()?;
+                let message = Self::rotate_key_message(
+                    &self.request.aleo_address,
+                    &base64::encode(key.to_bytes()),
+                );
+                (&key != pub_key
+                    && verify_signature(&key, &key_signature.signature, message.as_bytes()).is_ok())
+                .then_some(key)
+            })
+            .ok_or_else(|| {
+                anyhow!(
+                    "the key rotation of validator {} must be signed by the new key",
+                    self.request
+                )
+            })
+    }
+
+    /// Verify that the change is signed by the given validator key and, for key rotations, by the new key.
+    fn verify(&self, pub_key: &tendermint::PublicKey) -> Result<()> {
+        let message = match &self.kind {
+            ChangeKind::RotateKey => Self::rotate_key_message(
+                &self.request.aleo_address,
+                &base64::encode(self.new_key(pub_key)?.to_bytes()),
+            ),
+            ChangeKind::RewardAddress(reward_address) => {
+                Self::reward_address_message(&self.request.aleo_address, reward_address)
+            }
+        };
+        let key_signature = self
+            .key_signatures
+            .iter()
+            .find(|key_signature| {
+                parse_pub_key(&key_signature.pub_key).ok().as_ref() == Some(pub_key)
+            })
+            .ok_or_else(|| {
+                anyhow!(
+                    "the change of validator {} must be signed by the validator key",
+                    self.request
+                )
+            })?;
+        verify_signature(pub_key, &key_signature.signature, message.as_bytes()).map_err(|_| {
+            anyhow!(
+                "invalid validator key signature for the change of {}",
+                self.request
+            )
+        })
+    }
+}
+
+impl TreasurySpend {
+    pub fn new(recipient: vm::Address, amount: u64, key_signatures: Vec<KeySignature>) -> Self {
+        Self {
+            recipient,
+            amount,
+            key_signatures,
+        }
+    }
+
+    /// Return the message the treasury keys sign to authorize a spend, given the current state of the
+    /// treasury: its chain id and the amount of previous spends from it.
+    pub fn message(treasury: &Treasury, recipient: &vm::Address, amount: u64) -> String {
+        format!(
+            "spend_treasury {} {} {recipient} {amount}",
+            treasury.chain_id, treasury.spends
+        )
+    }
+
+    pub fn recipient(&self) -> vm::Address {
+        self.recipient
+    }
+
+    pub fn amount(&self) -> u64 {
+        self.amount
+    }
+
+    /// Check that the spend is signed by at least the threshold of treasury keys of the given params,
+    /// for the given state of the treasury.
+    pub fn authorize(&self, params: &StakingParams, treasury: &Treasury) -> Result<()> {
+        ensure!(
+            params.treasury_threshold > 0,
+            "the treasury has no keys to authorize spends"
+        );
+        let message = Self::message(treasury, &self.recipient, self.amount);
+        let signers = params
+            .treasury_keys
+            .iter()
+            .filter_map(|treasury_key| parse_pub_key(treasury_key).ok())
+            .filter(|treasury_key| {
+                self.key_signatures.iter().any(|key_signature| {
+                    parse_pub_key(&key_signature.pub_key).ok().as_ref() == Some(treasury_key)
+                        && verify_signature(
+                            treasury_key,
+                            &key_signature.signature,
+                            message.as_bytes(),
+                        )
+                        .is_ok()
+                })
+            })
+            .count() as u64;
+        ensure!(
+            signers >= params.treasury_threshold,
+            "the spend of {} credits from the treasury to {} is signed by {signers} treasury keys, but {} are required",
+            self.amount,
+            self.recipient,
+            params.treasury_threshold
+        );
+        Ok(())
+    }
+}
+
+impl KeySignature {
+    /// Sign the given message, e.g. a staker aleo address, with a base64 encoded ed25519 keypair,
+    /// as the private key appears in tendermint JSON files.
+    pub fn new(private_key: &str, message: &str) -> Result<Self> {
+        let keypair = ed25519_dalek::Keypair::from_bytes(&base64::decode(private_key)?)?;
+        let signature = keypair.sign(message.as_bytes());
+        Ok(Self {
+            pub_key: base64::encode(keypair.public.as_bytes()),
+            signature: base64::encode(signature.to_bytes()),
+        })
+    }
+
+    /// Sign the given message with the key of a tendermint validator, read from its
+    /// priv_validator_key.json file.
+    pub fn from_key_file(path: &Path, message: &str) -> Result<Self> {
+        let (_, private_key) = read_key_file(path)?;
+        Self::new(&private_key, message)
+    }
+}
+
+/// Read the base64 encoded public and private keys of a tendermint validator from its
+/// priv_validator_key.json file.
+pub fn read_key_file(path: &Path) -> Result<(String, String)> {
+    let json: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(path)?)?;
+    let key = |field: &str| {
+        json[field]["value"]
+            .as_str()
+            .map(String::from)
+            .ok_or_else(|| anyhow!("couldn't find the {field} in {}", path.display()))
+    };
+    Ok((key("pub_key")?, key("priv_key")?))
 }
 
 impl std::hash::Hash for Validator {
@@ -134,12 +775,33 @@ impl std::fmt::Display for Stake {
     }
 }
 
+impl std::fmt::Display for StakerRequest {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}/{}",
+            hex::encode_upper(self.validator_address()),
+            self.aleo_address
+        )
+    }
+}
+
 fn parse_pub_key(key: &str) -> Result<tendermint::PublicKey> {
     debug!("key: {}", key);
     tendermint::PublicKey::from_raw_ed25519(&base64::decode(key)?)
         .ok_or_else(|| anyhow!("failed to generate tendermint public key"))
 }
 
+fn verify_signature(
+    pub_key: &tendermint::PublicKey,
+    signature: &str,
+    message: &[u8],
+) -> Result<()> {
+    let public_key = ed25519_dalek::PublicKey::from_bytes(&pub_key.to_bytes())?;
+    let signature = ed25519_dalek::Signature::try_from(base64::decode(signature)?.as_slice())?;
+    Ok(public_key.verify_strict(message, &signature)?)
+}
+
 fn pub_key_to_address(key: &tendermint::PublicKey) -> Address {
     tendermint::account::Id::from(key.ed25519().expect("unsupported public key type"))
         .as_bytes()
@@ -1,17 +1,28 @@
 /// Library for interfacing with the VM, and generating Transactions
 ///
 use std::str::FromStr;
+use std::sync::Mutex;
 
+use crate::cost::CircuitSize;
+use crate::trace::{Trace, TraceError, TraceStep};
 use anyhow::{anyhow, bail, ensure, Result};
+use indexmap::IndexMap;
 pub use lambdavm::build_program;
 pub use lambdavm::jaleo::{get_credits_key, mint_credits};
 pub use lambdavm::jaleo::{Itertools, UserInputValueType};
 use lambdavm::VariableType;
 use log::debug;
+use rand::{CryptoRng, Rng};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
 use sha3::{Digest, Sha3_256};
 
-const MAX_INPUTS: usize = 8;
-const MAX_OUTPUTS: usize = 8;
+/// Maximum amount of inputs and outputs of a program function.
+pub const MAX_INPUTS: usize = 8;
+pub const MAX_OUTPUTS: usize = 8;
+
+/// Name of the vm backend, to tell apart keys and other artifacts that are not compatible across backends.
+pub const BACKEND: &str = "lambdavm";
 
 pub type Address = lambdavm::jaleo::Address;
 pub type Identifier = lambdavm::jaleo::Identifier;
@@ -28,9 +39,34 @@ pub type ProvingKey = lambdavm::jaleo::ProvingKey;
 pub type Deployment = lambdavm::jaleo::Deployment;
 pub type Transition = lambdavm::jaleo::Transition;
 pub type VerifyingKeyMap = lambdavm::jaleo::VerifyingKeyMap;
+pub type Signature = lambdavm::jaleo::Signature;
+
+/// lambdavm doesn't produce circuit certificates like snarkvm does, so lambdavm deployments don't
+/// carry any: this type can't be instantiated and only exists to preserve the API across backends.
+/// Instead, deployments are verified by synthesizing the keys from the program source.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Certificate {}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CertificateMap {
+    pub map: IndexMap<Identifier, Certificate>,
+}
+
+/// Amount of programs whose synthesized verifying keys are kept in memory by `verify_deployment`.
+const SYNTHESIZED_KEYS_CACHE_SIZE: usize = 16;
+
+/// The serialized verifying keys of each function of the programs recently synthesized by `verify_deployment`,
+/// by the hash of their source, so the same deployment isn't synthesized again when it's checked by the
+/// mempool, rechecked after each block and delivered.
+static SYNTHESIZED_KEYS: Mutex<Vec<(String, Vec<(String, Vec<u8>)>)>> = Mutex::new(Vec::new());
 
-/// Basic deployment validations
-pub fn verify_deployment(program: &Program, verifying_keys: VerifyingKeyMap) -> Result<()> {
+/// Basic deployment validations, plus a check of the verifying keys of each function against the ones
+/// synthesized from the program source, which ensures that they were derived from it.
+pub fn verify_deployment(
+    program: &Program,
+    verifying_keys: VerifyingKeyMap,
+    certificates: CertificateMap,
+) -> Result<()> {
     // Ensure the deployment contains verifying keys.
     let program_id = program.id();
     ensure!(
@@ -62,15 +98,123 @@ pub fn verify_deployment(program: &Program, verifying_keys: VerifyingKeyMap) ->
             )
         }
     }
+
+    ensure!(
+        certificates.map.is_empty(),
+        "Deployments for the lambdavm backend don't carry certificates"
+    );
+
+    // Synthesize the function keys from the source and check them against the deployed ones.
+    ensure_srs_file_exists()?;
+    let expected_keys = synthesized_verifying_keys(program)?;
+    for (function_name, verifying_key) in verifying_keys.map.iter() {
+        let expected_key = expected_keys
+            .iter()
+            .find(|(name, _)| *name == function_name.to_string())
+            .map(|(_, key)| key)
+            .ok_or_else(|| anyhow!("missing verifying key for function '{function_name}'"))?;
+
+        ensure!(
+            lambdavm::serialize_verifying_key(verifying_key.clone())? == *expected_key,
+            "The verifying key for function '{function_name}' doesn't match the program source"
+        );
+    }
     Ok(())
 }
 
+/// Return the serialized verifying keys of each function of the program, synthesizing them from its
+/// source unless they were recently synthesized.
+fn synthesized_verifying_keys(program: &Program) -> Result<Vec<(String, Vec<u8>)>> {
+    let source = program.to_string();
+    let source_hash = sha3_hash(source.as_bytes());
+    if let Some((_, keys)) = SYNTHESIZED_KEYS
+        .lock()
+        .unwrap()
+        .iter()
+        .find(|(hash, _)| *hash == source_hash)
+    {
+        return Ok(keys.clone());
+    }
+
+    let (_, program_build) = build_program(&source)?;
+    let keys = program_build
+        .map
+        .into_iter()
+        .map(|(function_name, (_, verifying_key))| {
+            Ok((
+                function_name.to_string(),
+                lambdavm::serialize_verifying_key(verifying_key)?,
+            ))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut cache = SYNTHESIZED_KEYS.lock().unwrap();
+    if cache.len() >= SYNTHESIZED_KEYS_CACHE_SIZE {
+        cache.remove(0);
+    }
+    cache.push((source_hash, keys.clone()));
+    Ok(keys)
+}
+
+/// lambdavm doesn't produce circuit certificates, so deployments carry an empty certificate map.
+pub fn certify_program(_program_build: &ProgramBuild) -> Result<CertificateMap> {
+    Ok(CertificateMap {
+        map: IndexMap::new(),
+    })
+}
+
+/// lambdavm key synthesis takes no randomness other than the universal SRS, which is shared by every
+/// program build, so the seed is not needed to obtain the same keys on every build. It's kept to
+/// preserve the API across backends. The SRS is not checked against the pinned hash, since the keys built
+/// from the seed are checked against the pinned credits keys, which are only the same for the same SRS.
+pub fn build_program_from_seed(
+    program_string: &str,
+    _seed: u64,
+) -> Result<(Program, ProgramBuild)> {
+    create_srs_file_if_missing()?;
+    build_program(program_string)
+}
+
+/// Same as `build_program_from_seed`, the rng is not needed by lambdavm key synthesis.
+pub fn build_program_with_rng<R: Rng + CryptoRng>(
+    program_string: &str,
+    _rng: &mut R,
+) -> Result<(Program, ProgramBuild)> {
+    ensure_srs_file_exists()?;
+    build_program(program_string)
+}
+
+/// Expected hash of the universal SRS file, as written by `make credits_keys`. Keys are derived from
+/// the SRS, so nodes can only verify deployments by synthesizing the same keys if they share it.
+const UNIVERSAL_SRS_HASH: &str = include_str!("../../../../aleo/universal_srs.lambdavm.sha256");
+
+/// Generate the universal SRS file if it doesn't exist, and fail if it doesn't match the pinned hash.
 pub fn ensure_srs_file_exists() -> Result<()> {
+    let expected_hash = UNIVERSAL_SRS_HASH.trim();
+    ensure!(
+        !expected_hash.is_empty(),
+        "the universal SRS hash is not pinned, run `make credits_keys`"
+    );
+    let hash = universal_srs_hash()?;
+    ensure!(
+        hash == expected_hash,
+        "universal SRS hash {hash} doesn't match the expected {expected_hash}, keys synthesized from it wouldn't match the ones of other nodes"
+    );
+    Ok(())
+}
+
+/// Return the hex encoded sha256 hash of the universal SRS file, generating the file if it doesn't exist.
+pub fn universal_srs_hash() -> Result<String> {
+    let srs_file_path = create_srs_file_if_missing()?;
+    Ok(hex::encode(Sha256::digest(std::fs::read(srs_file_path)?)))
+}
+
+fn create_srs_file_if_missing() -> Result<std::path::PathBuf> {
     let (_, srs_file_path) = lambdavm::universal_srs::get_universal_srs_dir_and_filepath()?;
-    if std::fs::File::open(srs_file_path).is_err() {
+    if std::fs::File::open(&srs_file_path).is_err() {
         let _ = lambdavm::universal_srs::generate_universal_srs_and_write_to_file()?;
     }
-    Ok(())
+    Ok(std::path::PathBuf::from(srs_file_path))
 }
 
 pub fn verify_execution(
@@ -165,6 +309,37 @@ pub fn verify_execution(
     Ok(())
 }
 
+/// lambdavm only type-checks instructions while synthesizing their circuits, so there's nothing
+/// left to check once the program is parsed. The type checks of `check::check_program` run on the
+/// source for both backends.
+pub fn check_program(_program: &Program) -> Result<()> {
+    Ok(())
+}
+
+/// Return the size of the circuit of a function and of its keys.
+pub fn circuit_size(proving_key: &ProvingKey, verifying_key: &VerifyingKey) -> Result<CircuitSize> {
+    let index_info = &verifying_key.index_info;
+    Ok(CircuitSize {
+        constraints: index_info.num_constraints,
+        variables: index_info.num_variables,
+        public_inputs: index_info.num_instance_variables,
+        proving_key_size: lambdavm::serialize_proving_key(proving_key.clone())?.len(),
+        verifying_key_size: lambdavm::serialize_verifying_key(verifying_key.clone())?.len(),
+    })
+}
+
+/// Return the types of the inputs of a program function, e.g. `u64.private` or `token.record`.
+pub fn function_input_types(program: &Program, function_name: &Identifier) -> Result<Vec<String>> {
+    let function = program
+        .get_function(function_name)
+        .map_err(|e| anyhow!("{}", e))?;
+    Ok(function
+        .inputs()
+        .iter()
+        .map(|input| input.value_type().to_string())
+        .collect())
+}
+
 pub fn program_is_coinbase(program_id: &str, function_name: &str) -> bool {
     (function_name == "mint" || function_name == "genesis") && program_id == "credits.aleo"
 }
@@ -175,12 +350,42 @@ pub fn generate_program(program_string: &str) -> Result<Program> {
     Program::from_str(program_string)
 }
 
-pub fn execution(
+/// lambdavm needs the private key to process the circuit inputs after proving, so executions can't be
+/// split in authorization and proving steps yet. This type exists to preserve the API across backends.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Authorization {
+    pub function_name: Identifier,
+}
+
+pub fn authorize<R: Rng + CryptoRng>(
+    _program: &Program,
+    _function_name: Identifier,
+    _inputs: &[UserInputValueType],
+    _private_key: &PrivateKey,
+    _rng: &mut R,
+) -> Result<Authorization> {
+    bail!("Authorizing executions to be proven separately is not supported by the lambdavm backend")
+}
+
+pub fn prove<R: Rng + CryptoRng>(
+    _program: &Program,
+    _authorization: &Authorization,
+    _proving_key: Option<ProvingKey>,
+    _rng: &mut R,
+) -> Result<Vec<Transition>> {
+    bail!("Proving authorized executions is not supported by the lambdavm backend")
+}
+
+/// Execute a program function locally and return the resulting transitions.
+/// The rng is taken to preserve the API across backends, but lambdavm doesn't support injecting
+/// the randomness of proofs and record nonces yet, so its executions are not reproducible.
+pub fn execution<R: Rng + CryptoRng>(
     program: Program,
     function_name: Identifier,
     inputs: &[UserInputValueType],
     private_key: &PrivateKey,
     _proving_key: Option<ProvingKey>,
+    _rng: &mut R,
 ) -> Result<Vec<Transition>> {
     ensure!(
         !program_is_coinbase(&program.id().to_string(), &function_name.to_string()),
@@ -222,6 +427,122 @@ pub fn execution(
     Ok(vec![transition])
 }
 
+/// Evaluate a program function, returning its output values. lambdavm evaluates functions while
+/// proving them, so this is as expensive as an execution.
+pub fn evaluate<R: Rng + CryptoRng>(
+    program: &Program,
+    function_name: Identifier,
+    inputs: &[UserInputValueType],
+    _private_key: &PrivateKey,
+    _rng: &mut R,
+) -> Result<Vec<String>> {
+    let function = program
+        .get_function(&function_name)
+        .map_err(|e| anyhow!("{}", e))?;
+
+    let (compiled_function_variables, _) =
+        lambdavm::execute_function(program, &function_name.to_string(), inputs)?;
+    let outputs =
+        lambdavm::jaleo::process_circuit_outputs(&function, &compiled_function_variables)?;
+
+    Ok(outputs.into_values().map(output_value).collect())
+}
+
+/// Evaluate a program function, recording the instructions it runs. lambdavm evaluates the whole function
+/// while synthesizing its circuit and only exposes the values of its inputs and outputs, so the values of
+/// intermediate registers are left out of the steps, and a failure is reported for the whole function
+/// instead of the failing instruction. As with `evaluate`, this is as expensive as an execution.
+pub fn trace<R: Rng + CryptoRng>(
+    program: &Program,
+    function_name: Identifier,
+    inputs: &[UserInputValueType],
+    _private_key: &PrivateKey,
+    _rng: &mut R,
+) -> Result<Trace> {
+    let function = program
+        .get_function(&function_name)
+        .map_err(|e| anyhow!("{}", e))?;
+
+    let compiled_function_variables =
+        match lambdavm::execute_function(program, &function_name.to_string(), inputs) {
+            Ok((compiled_function_variables, _)) => compiled_function_variables,
+            Err(error) => {
+                return Ok(Trace {
+                    steps: vec![],
+                    error: Some(TraceError {
+                        index: None,
+                        instruction: None,
+                        message: error.to_string(),
+                    }),
+                })
+            }
+        };
+    let outputs =
+        lambdavm::jaleo::process_circuit_outputs(&function, &compiled_function_variables)?;
+
+    // the known register values, by register
+    let mut values = IndexMap::new();
+    for (input, value) in function.inputs().iter().zip(inputs) {
+        values.insert(input.register().to_string(), value.to_string());
+    }
+    for (output, value) in function.outputs().iter().zip(outputs.into_values()) {
+        values.insert(output.operand().to_string(), output_value(value));
+    }
+    let known_values = |registers: Vec<String>| -> IndexMap<String, String> {
+        registers
+            .into_iter()
+            .filter_map(|register| {
+                let value = values.get(&register)?.clone();
+                Some((register, value))
+            })
+            .collect()
+    };
+
+    let steps = function
+        .instructions()
+        .iter()
+        .enumerate()
+        .map(|(index, instruction)| TraceStep {
+            index,
+            instruction: instruction.to_string(),
+            operands: known_values(
+                instruction
+                    .operands()
+                    .iter()
+                    .map(|operand| operand.to_string())
+                    .collect(),
+            ),
+            destinations: known_values(
+                instruction
+                    .destinations()
+                    .iter()
+                    .map(|register| register.to_string())
+                    .collect(),
+            ),
+        })
+        .collect();
+
+    Ok(Trace { steps, error: None })
+}
+
+/// Format the value of a function output, as the VM prints it.
+fn output_value(output: VariableType) -> String {
+    match output {
+        VariableType::Public(value) | VariableType::Private(value) => value.to_string(),
+        other => format!("{other:?}"),
+    }
+}
+
+/// Sign an arbitrary message with the given account private key.
+pub fn sign(private_key: &PrivateKey, message: &[u8]) -> Result<Signature> {
+    Signature::sign_bytes(private_key, message, &mut rand::thread_rng())
+}
+
+/// Return whether the signature of the message was produced by the given account.
+pub fn verify_signature(address: &Address, message: &[u8], signature: &Signature) -> bool {
+    signature.verify_bytes(address, message)
+}
+
 /// Extract the record gates (the minimal credits unit) as a u64 integer, instead of a snarkvm internal type.
 pub fn gates(record: &Record) -> u64 {
     record.gates
@@ -1,12 +1,21 @@
 /// Library for interfacing with the VM, and generating Transactions
 ///
-use std::{ops::Deref, str::FromStr, sync::Arc};
+use std::{
+    ops::Deref,
+    str::FromStr,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
 
+use crate::cost::CircuitSize;
+use crate::trace::Trace;
 use anyhow::{anyhow, bail, ensure, Result};
 use indexmap::IndexMap;
 use log::debug;
-use parking_lot::{lock_api::RwLock, RawRwLock};
-use rand::{rngs::ThreadRng, SeedableRng};
+use parking_lot::{lock_api::RwLock, Mutex, RawRwLock};
+use rand::{CryptoRng, Rng, SeedableRng};
 use rand_chacha::ChaCha8Rng;
 use serde::{Deserialize, Serialize};
 use snarkvm::prelude::Parser;
@@ -15,12 +24,15 @@ use snarkvm::{
     console::types::string::Integer,
     prelude::{
         Balance, CallStack, Environment, Itertools, Literal, Network, One, Owner, Plaintext,
-        Testnet3, ToBits, ToField, Uniform, I64,
+        Testnet3, ToBits, ToBytes, ToField, Uniform, I64,
     },
 };
 
 mod stack;
 
+/// Name of the vm backend, to tell apart keys and other artifacts that are not compatible across backends.
+pub const BACKEND: &str = "snarkvm";
+
 pub type Address = snarkvm::prelude::Address<Testnet3>;
 pub type Identifier = snarkvm::prelude::Identifier<Testnet3>;
 pub type UserInputValueType = snarkvm::prelude::Value<Testnet3>;
@@ -39,6 +51,13 @@ pub type VerifyingKey = snarkvm::prelude::VerifyingKey<Testnet3>;
 pub type ProvingKey = snarkvm::prelude::ProvingKey<Testnet3>;
 pub type Deployment = snarkvm::prelude::Deployment<Testnet3>;
 pub type Transition = snarkvm::prelude::Transition<Testnet3>;
+pub type Certificate = snarkvm::prelude::Certificate<Testnet3>;
+pub type Signature = snarkvm::prelude::Signature<Testnet3>;
+pub type Request = snarkvm::prelude::Request<Testnet3>;
+
+/// Maximum amount of inputs and outputs of a program function.
+pub const MAX_INPUTS: usize = Testnet3::MAX_INPUTS;
+pub const MAX_OUTPUTS: usize = Testnet3::MAX_OUTPUTS;
 
 /// These structs are nothing more than a wrapper around the actual IndexMap that is used
 /// for the verifying keys map. Why does it exist? The problem comes from the lambdavm backend.
@@ -56,8 +75,20 @@ pub struct ProgramBuild {
     pub map: IndexMap<Identifier, (ProvingKey, VerifyingKey)>,
 }
 
-/// Basic deployment validations
-pub fn verify_deployment(program: &Program, verifying_keys: VerifyingKeyMap) -> Result<()> {
+/// The circuit certificates of each program function, used to prove that the deployed
+/// verifying keys correspond to the program source. Wrapped for the same reasons as `VerifyingKeyMap`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CertificateMap {
+    pub map: IndexMap<Identifier, Certificate>,
+}
+
+/// Basic deployment validations, plus verification of the circuit certificates of each function,
+/// which ensures that the verifying keys were derived from the program source.
+pub fn verify_deployment(
+    program: &Program,
+    verifying_keys: VerifyingKeyMap,
+    certificates: CertificateMap,
+) -> Result<()> {
     // Ensure the deployment contains verifying keys.
     let program_id = program.id();
     ensure!(
@@ -89,7 +120,27 @@ pub fn verify_deployment(program: &Program, verifying_keys: VerifyingKeyMap) ->
             )
         }
     }
-    Ok(())
+
+    // Ensure there's a certificate for each of the verifying keys, in the same order.
+    ensure!(
+        verifying_keys.map.keys().eq(certificates.map.keys()),
+        "The deployment certificates don't match the program functions"
+    );
+
+    // Synthesize each function circuit and check its certificate against the verifying key.
+    let bundle = verifying_keys
+        .map
+        .into_iter()
+        .zip_eq(certificates.map.into_values())
+        .map(|((function_name, verifying_key), certificate)| {
+            (function_name, (verifying_key, certificate))
+        })
+        .collect();
+    let deployment = Deployment::new(Testnet3::EDITION, program.clone(), bundle)?;
+
+    let rng = &mut rand::thread_rng();
+    let stack = stack::new_init(program)?;
+    stack.verify_deployment::<AleoV0, _>(&deployment, rng)
 }
 
 pub fn verify_execution(transition: &Transition, verifying_keys: &VerifyingKeyMap) -> Result<()> {
@@ -113,12 +164,12 @@ pub fn verify_execution(transition: &Transition, verifying_keys: &VerifyingKeyMa
     );
     // Ensure the number of inputs is within the allowed range.
     ensure!(
-        transition.inputs().len() <= Testnet3::MAX_INPUTS,
+        transition.inputs().len() <= MAX_INPUTS,
         "Transition exceeded maximum number of inputs"
     );
     // Ensure the number of outputs is within the allowed range.
     ensure!(
-        transition.outputs().len() <= Testnet3::MAX_INPUTS,
+        transition.outputs().len() <= MAX_OUTPUTS,
         "Transition exceeded maximum number of outputs"
     );
     // Ensure each input is valid.
@@ -186,37 +237,92 @@ pub fn verify_execution(transition: &Transition, verifying_keys: &VerifyingKeyMa
     Ok(())
 }
 
+/// Stack size of the threads that synthesize the function keys, since deep circuits need more than the default.
+const KEY_SYNTHESIS_STACK_SIZE: usize = 64 * 1024 * 1024;
+
 /// Generate proving and verifying keys for each function in the given program,
 /// and return them in a function name -> (proving key, verifying key) map.
 pub fn build_program(program_string: &str) -> Result<(Program, ProgramBuild)> {
-    let (_, program) = Program::parse(program_string).map_err(|e| anyhow!("{}", e))?;
+    build_program_with_rng(program_string, &mut rand::thread_rng())
+}
 
-    let mut verifying_keys = IndexMap::new();
+/// Generate the program keys from the given seed, so the same keys are obtained on every build.
+pub fn build_program_from_seed(program_string: &str, seed: u64) -> Result<(Program, ProgramBuild)> {
+    build_program_with_rng(program_string, &mut ChaCha8Rng::seed_from_u64(seed))
+}
 
+/// Generate the program keys with the given rng.
+pub fn build_program_with_rng<R: Rng + CryptoRng>(
+    program_string: &str,
+    rng: &mut R,
+) -> Result<(Program, ProgramBuild)> {
+    let (_, program) = Program::parse(program_string).map_err(|e| anyhow!("{}", e))?;
+    let stack = stack::new_init(&program)?;
+
+    // derive an rng for each function up front, so seeded builds produce the same keys
+    // regardless of the order in which the threads run
+    let function_rngs: Vec<_> = program
+        .functions()
+        .keys()
+        .map(|function_name| (*function_name, ChaCha8Rng::seed_from_u64(rng.gen())))
+        .collect();
+
+    // synthesize the keys of all functions in parallel, on a single stack so they share the universal SRS.
+    // A bounded amount of workers takes the functions in turns, so big programs don't spawn a thread each.
+    let workers = std::thread::available_parallelism()
+        .map_or(1, |parallelism| parallelism.get())
+        .min(function_rngs.len());
+    let next_function = AtomicUsize::new(0);
+    let function_rngs: Vec<_> = function_rngs.into_iter().map(Mutex::new).collect();
+    std::thread::scope(|scope| {
+        let handles = (0..workers)
+            .map(|_| {
+                std::thread::Builder::new()
+                    .stack_size(KEY_SYNTHESIS_STACK_SIZE)
+                    .spawn_scoped(scope, || -> Result<()> {
+                        while let Some(function_rng) =
+                            function_rngs.get(next_function.fetch_add(1, Ordering::Relaxed))
+                        {
+                            let (function_name, rng) = &mut *function_rng.lock();
+                            stack.synthesize_key::<AleoV0, _>(function_name, rng)?;
+                        }
+                        Ok(())
+                    })
+                    .map_err(|e| anyhow!("couldn't spawn key synthesis thread: {e}"))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        handles.into_iter().try_for_each(|handle| {
+            handle
+                .join()
+                .map_err(|_| anyhow!("key synthesis thread panicked"))?
+        })
+    })?;
+
+    let mut keys = IndexMap::new();
     for function_name in program.functions().keys() {
-        let rng = &mut rand::thread_rng();
-        verifying_keys.insert(
-            *function_name,
-            synthesize_function_keys(&program, rng, function_name)?,
-        );
+        keys.insert(*function_name, function_keys(&stack, function_name)?);
     }
 
-    Ok((
-        program,
-        ProgramBuild {
-            map: verifying_keys,
-        },
-    ))
+    Ok((program, ProgramBuild { map: keys }))
 }
 
 /// Generate proving and verifying keys for the given function.
-pub fn synthesize_function_keys(
+pub fn synthesize_function_keys<R: Rng + CryptoRng>(
     program: &Program,
-    rng: &mut ThreadRng,
+    rng: &mut R,
     function_name: &Identifier,
 ) -> Result<(ProvingKey, VerifyingKey)> {
     let stack = stack::new_init(program)?;
     stack.synthesize_key::<AleoV0, _>(function_name, rng)?;
+    function_keys(&stack, function_name)
+}
+
+/// Get the proving and verifying keys of a function previously synthesized in the given stack.
+fn function_keys(
+    stack: &stack::Stack,
+    function_name: &Identifier,
+) -> Result<(ProvingKey, VerifyingKey)> {
     let proving_key = stack.proving_keys.read().get(function_name).cloned();
     let proving_key = proving_key.ok_or_else(|| anyhow!("proving key not found for identifier"))?;
 
@@ -227,19 +333,85 @@ pub fn synthesize_function_keys(
     Ok((proving_key, verifying_key))
 }
 
+/// Check that the program is valid for this backend without building its keys, by computing
+/// the types of the registers of each function, which type-checks their instructions.
+pub fn check_program(program: &Program) -> Result<()> {
+    stack::new_init(program).map(|_| ())
+}
+
+/// Return the size of the circuit of a function and of its keys.
+pub fn circuit_size(proving_key: &ProvingKey, verifying_key: &VerifyingKey) -> Result<CircuitSize> {
+    let circuit_info = &verifying_key.circuit_info;
+    Ok(CircuitSize {
+        constraints: circuit_info.num_constraints,
+        variables: circuit_info.num_variables,
+        public_inputs: circuit_info.num_public_inputs,
+        proving_key_size: proving_key.to_bytes_le()?.len(),
+        verifying_key_size: verifying_key.to_bytes_le()?.len(),
+    })
+}
+
+/// Return the types of the inputs of a program function, e.g. `u64.private` or `token.record`.
+pub fn function_input_types(program: &Program, function_name: &Identifier) -> Result<Vec<String>> {
+    let function = program.get_function(function_name)?;
+    Ok(function
+        .inputs()
+        .iter()
+        .map(|input| input.value_type().to_string())
+        .collect())
+}
+
+/// Generate the certificates that prove that each function verifying key was derived from
+/// the proving key of the program circuits.
+pub fn certify_program(program_build: &ProgramBuild) -> Result<CertificateMap> {
+    let mut certificates = IndexMap::new();
+
+    for (function_name, (proving_key, verifying_key)) in program_build.map.iter() {
+        let certificate =
+            Certificate::certify(&function_name.to_string(), proving_key, verifying_key)?;
+        certificates.insert(*function_name, certificate);
+    }
+
+    Ok(CertificateMap { map: certificates })
+}
+
 // Generates a program deployment for source transactions
 pub fn generate_program(program_string: &str) -> Result<Program> {
     // Verify program is valid by parsing it and returning it
     Program::from_str(program_string)
 }
 
-pub fn execution(
+/// Execute a program function locally and return the resulting transitions. All the randomness of the
+/// execution (record nonces, proofs and keys if the proving key is not given) is taken from the rng,
+/// so the same inputs and rng state produce the same transitions.
+pub fn execution<R: Rng + CryptoRng>(
     program: Program,
     function_name: Identifier,
     inputs: &[UserInputValueType],
     private_key: &PrivateKey,
     proving_key: Option<ProvingKey>,
+    rng: &mut R,
 ) -> Result<Vec<Transition>> {
+    let authorization = authorize(&program, function_name, inputs, private_key, rng)?;
+    prove(&program, &authorization, proving_key, rng)
+}
+
+/// The signed requests to execute a program function, which can be proven without the private key
+/// of the account that signed them. Wrapped to preserve the API across the two backends.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Authorization {
+    pub requests: Vec<Request>,
+}
+
+/// Sign the requests to execute a program function with the given inputs. This is the only step of
+/// an execution that requires the private key, the resulting authorization can be proven elsewhere.
+pub fn authorize<R: Rng + CryptoRng>(
+    program: &Program,
+    function_name: Identifier,
+    inputs: &[UserInputValueType],
+    private_key: &PrivateKey,
+    rng: &mut R,
+) -> Result<Authorization> {
     ensure!(
         !Program::is_coinbase(program.id(), &function_name),
         "Coinbase functions cannot be called"
@@ -251,21 +423,53 @@ pub fn execution(
     );
 
     debug!(
-        "executing program {} function {} inputs {:?}",
+        "authorizing program {} function {} inputs {:?}",
         program, function_name, inputs
     );
 
-    let rng = &mut rand::thread_rng();
+    let stack = stack::new_init(program)?;
+    let authorization = stack.authorize::<AleoV0, _>(private_key, function_name, inputs, rng)?;
 
-    let stack = stack::new_init(&program)?;
+    Ok(Authorization {
+        requests: authorization.to_vec_deque().into_iter().collect(),
+    })
+}
+
+/// Execute the circuit of an authorized function and return the resulting transitions.
+/// If the proving key is not given, it's synthesized from the program.
+pub fn prove<R: Rng + CryptoRng>(
+    program: &Program,
+    authorization: &Authorization,
+    proving_key: Option<ProvingKey>,
+    rng: &mut R,
+) -> Result<Vec<Transition>> {
+    let request = authorization
+        .requests
+        .first()
+        .ok_or_else(|| anyhow!("The authorization has no requests"))?;
+    let function_name = *request.function_name();
+    ensure!(
+        request.program_id() == program.id(),
+        "The authorization is for program {}, not {}",
+        request.program_id(),
+        program.id()
+    );
+
+    debug!(
+        "proving program {} function {}",
+        program.id(),
+        function_name
+    );
+
+    let stack = stack::new_init(program)?;
 
     let proving_key = match proving_key {
         Some(v) => v,
-        None => synthesize_function_keys(&program, rng, &function_name)?.0,
+        None => synthesize_function_keys(program, rng, &function_name)?.0,
     };
     stack.insert_proving_key(&function_name, proving_key)?;
 
-    let authorization = stack.authorize::<AleoV0, _>(private_key, function_name, inputs, rng)?;
+    let authorization = snarkvm::prelude::Authorization::new(&authorization.requests);
     let execution: Arc<RwLock<RawRwLock, _>> = Arc::new(RwLock::new(Execution::new()));
 
     // Execute the circuit.
@@ -279,6 +483,55 @@ pub fn execution(
     Ok(execution.into_transitions().collect())
 }
 
+/// Evaluate a program function without proving it, returning its output values.
+pub fn evaluate<R: Rng + CryptoRng>(
+    program: &Program,
+    function_name: Identifier,
+    inputs: &[UserInputValueType],
+    private_key: &PrivateKey,
+    rng: &mut R,
+) -> Result<Vec<String>> {
+    let authorization = authorize(program, function_name, inputs, private_key, rng)?;
+    let authorization = snarkvm::prelude::Authorization::new(&authorization.requests);
+
+    let stack = stack::new_init(program)?;
+    let response = stack.evaluate_function::<AleoV0>(CallStack::evaluate(authorization)?)?;
+    Ok(response
+        .outputs()
+        .iter()
+        .map(|output| output.to_string())
+        .collect())
+}
+
+/// Evaluate a program function without proving it, recording the register values read and written by
+/// each instruction, to debug programs on dry runs. A failing instruction is reported in the trace.
+pub fn trace<R: Rng + CryptoRng>(
+    program: &Program,
+    function_name: Identifier,
+    inputs: &[UserInputValueType],
+    private_key: &PrivateKey,
+    rng: &mut R,
+) -> Result<Trace> {
+    let authorization = authorize(program, function_name, inputs, private_key, rng)?;
+    let request = authorization
+        .requests
+        .first()
+        .ok_or_else(|| anyhow!("The authorization has no requests"))?;
+
+    let stack = stack::new_init(program)?;
+    stack::trace_function(&stack, request)
+}
+
+/// Sign an arbitrary message with the given account private key.
+pub fn sign(private_key: &PrivateKey, message: &[u8]) -> Result<Signature> {
+    Signature::sign_bytes(private_key, message, &mut rand::thread_rng())
+}
+
+/// Return whether the signature of the message was produced by the given account.
+pub fn verify_signature(address: &Address, message: &[u8], signature: &Signature) -> bool {
+    signature.verify_bytes(address, message)
+}
+
 /// Extract the record gates (the minimal credits unit) as a u64 integer, instead of a snarkvm internal type.
 pub fn gates(record: &Record) -> u64 {
     *record.gates().deref().deref()
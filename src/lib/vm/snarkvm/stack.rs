@@ -1,6 +1,12 @@
-use super::Program;
+use super::{Program, Request};
+use crate::trace::{Trace, TraceError, TraceStep};
 use anyhow::{ensure, Result};
-use snarkvm::prelude::{RegisterTypes, Testnet3, UniversalSRS};
+use indexmap::IndexMap;
+use snarkvm::circuit::AleoV0;
+use snarkvm::prelude::{
+    Authorization, CallStack, Itertools, Operand, RegisterTypes, Registers, RegistersCaller,
+    RegistersLoad, RegistersStore, Testnet3, UniversalSRS,
+};
 /// This module includes helper functions initially taken from SnarkVM's Stack struct.
 /// The goal is to progressively remove the dependency on that struct.
 use std::sync::Arc;
@@ -51,3 +57,59 @@ pub fn new_init(program: &Program) -> Result<Stack> {
     // Return the stack.
     Ok(stack)
 }
+
+/// Evaluate the function of the given request like `Stack::evaluate_function` does, but recording the
+/// values of the operands and destinations of each instruction. Evaluation stops at the first failing
+/// instruction, which is reported in the trace instead of returned as an error.
+pub fn trace_function(stack: &Stack, request: &Request) -> Result<Trace> {
+    let function = stack.get_function(request.function_name())?;
+
+    let call_stack = CallStack::evaluate(Authorization::new(&[request.clone()]))?;
+    let register_types = stack.get_register_types(function.name())?.clone();
+    let mut registers = Registers::<Testnet3, AleoV0>::new(call_stack, register_types);
+    registers.set_caller(*request.caller());
+    registers.set_tvk(*request.tvk());
+
+    // Store the inputs.
+    function
+        .inputs()
+        .iter()
+        .map(|input| input.register())
+        .zip_eq(request.inputs())
+        .try_for_each(|(register, input)| registers.store(stack, register, input.clone()))?;
+
+    let mut trace = Trace::default();
+    for (index, instruction) in function.instructions().iter().enumerate() {
+        let mut operands = IndexMap::new();
+        for operand in instruction.operands() {
+            if !matches!(operand, Operand::Literal(_)) {
+                let value = registers.load(stack, operand)?;
+                operands.insert(operand.to_string(), value.to_string());
+            }
+        }
+
+        if let Err(error) = instruction.evaluate(stack, &mut registers) {
+            trace.error = Some(TraceError {
+                index: Some(index),
+                instruction: Some(instruction.to_string()),
+                message: error.to_string(),
+            });
+            break;
+        }
+
+        let mut destinations = IndexMap::new();
+        for register in instruction.destinations() {
+            let value = registers.load(stack, &Operand::Register(register.clone()))?;
+            destinations.insert(register.to_string(), value.to_string());
+        }
+
+        trace.steps.push(TraceStep {
+            index,
+            instruction: instruction.to_string(),
+            operands,
+            destinations,
+        });
+    }
+
+    Ok(trace)
+}
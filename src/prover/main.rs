@@ -0,0 +1,194 @@
+//! Process that generates the proofs of execution authorizations sent by clients. It keeps the proving
+//! keys of the programs it has seen in memory, so repeated executions don't need to load or synthesize them again.
+
+use std::collections::HashMap;
+use std::net::{TcpListener, TcpStream};
+use std::str::FromStr;
+use std::sync::mpsc::{channel, sync_channel, Receiver, Sender, SyncSender};
+use std::thread;
+use std::time::Duration;
+
+use anyhow::{anyhow, bail, Result};
+use clap::Parser;
+use lib::deployment;
+use lib::program_file::ProgramFile;
+use lib::prover::{self, ProverRequest, ProverResponse};
+use lib::vm;
+use sha2::{Digest, Sha256};
+use tracing::{debug, error, info};
+use tracing_subscriber::{filter::LevelFilter, util::SubscriberInitExt};
+
+#[derive(Debug, Parser)]
+#[clap(author, version, about)]
+struct Cli {
+    /// Bind the TCP server to this host.
+    #[clap(long, default_value = "127.0.0.1")]
+    host: String,
+
+    /// Bind the TCP server to this port.
+    #[clap(short, long, default_value = "26700")]
+    port: u16,
+
+    /// Increase output logging verbosity to DEBUG level.
+    #[clap(short, long)]
+    verbose: bool,
+}
+
+/// Time to wait for a client to send its request after connecting.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// An authorization waiting in the queue to be proven, along with the channel to send the result to.
+struct Job {
+    request: ProverRequest,
+    reply_to: SyncSender<ProverResponse>,
+}
+
+/// The proving keys of every program seen by the prover, by hash of the program source.
+struct Prover {
+    keys: HashMap<String, vm::ProgramBuild>,
+}
+
+fn main() -> Result<()> {
+    let cli: Cli = Cli::parse();
+    let log_level = if cli.verbose {
+        LevelFilter::DEBUG
+    } else {
+        LevelFilter::INFO
+    };
+
+    tracing_subscriber::fmt()
+        // Use a more compact, abbreviated log format
+        .compact()
+        .with_max_level(log_level)
+        .with_target(false)
+        .finish()
+        .init();
+
+    // authorizations are proven one at a time, since each proof already uses all the cores
+    let mut prover = Prover::new()?;
+    let (job_sender, job_receiver): (Sender<Job>, Receiver<Job>) = channel();
+    thread::spawn(move || {
+        while let Ok(job) = job_receiver.recv() {
+            let response = match prover.prove(&job.request) {
+                Ok(transitions) => ProverResponse::Transitions(transitions),
+                Err(e) => ProverResponse::Error(e.to_string()),
+            };
+            job.reply_to
+                .send(response)
+                .unwrap_or_else(|e| error!("{}", e));
+        }
+    });
+
+    let listener = TcpListener::bind(format!("{}:{}", cli.host, cli.port))?;
+    info!("Prover listening on {}:{}", cli.host, cli.port);
+
+    for stream in listener.incoming() {
+        let job_sender = job_sender.clone();
+        match stream {
+            Ok(stream) => {
+                thread::spawn(move || {
+                    handle_connection(stream, job_sender).unwrap_or_else(|e| error!("{}", e))
+                });
+            }
+            Err(e) => error!("failed to accept connection: {}", e),
+        }
+    }
+    Ok(())
+}
+
+/// Read an authorization from the connection, queue it and write back the proving result.
+fn handle_connection(mut stream: TcpStream, jobs: Sender<Job>) -> Result<()> {
+    stream.set_read_timeout(Some(REQUEST_TIMEOUT))?;
+    stream.set_write_timeout(Some(prover::WRITE_TIMEOUT))?;
+
+    let response = match prover::read_message::<ProverRequest>(&stream) {
+        Ok(request) => {
+            let (reply_to, reply_receiver) = sync_channel(1);
+            jobs.send(Job { request, reply_to })
+                .map_err(|e| anyhow!("prover queue is closed: {e}"))?;
+            reply_receiver.recv()?
+        }
+        Err(e) => ProverResponse::Error(format!("invalid request: {e}")),
+    };
+
+    prover::write_message(&mut stream, &response)
+}
+
+impl Prover {
+    /// Start with the credits keys loaded, since they are needed to prove execution fees.
+    fn new() -> Result<Self> {
+        let (credits, keys) = lib::try_load_credits()?;
+        let mut prover = Self {
+            keys: HashMap::new(),
+        };
+        prover.keys.insert(source_hash(&credits), keys);
+        Ok(prover)
+    }
+
+    fn prove(&mut self, request: &ProverRequest) -> Result<Vec<vm::Transition>> {
+        let authorization = &request.authorization;
+        info!(
+            "Proving {}/{}",
+            authorization.program.id(),
+            authorization.function_name
+        );
+
+        let proving_key = self
+            .proving_key(
+                &authorization.program,
+                &authorization.function_name,
+                request.verifying_key_hash.as_deref(),
+            )?
+            .ok_or_else(|| anyhow!("function {} not found", authorization.function_name))?;
+
+        // the credits keys are checked against the pinned hash when loaded
+        let fee_proving_key = if authorization.fee.is_some() {
+            let credits = vm::generate_program(lib::CREDITS_SOURCE)?;
+            self.proving_key(&credits, &vm::Identifier::from_str("fee")?, None)?
+        } else {
+            None
+        };
+
+        authorization.prove(Some(proving_key), fee_proving_key, &mut rand::thread_rng())
+    }
+
+    /// Return the proving key of the given function, loading or building the program keys
+    /// if the program was not seen before. Keys built here are synthesized from a fresh seed, so they
+    /// need not match the deployed ones: if the deployed verifying key hash is given, keys that don't
+    /// match it are refused, since the blockchain would reject their proofs.
+    fn proving_key(
+        &mut self,
+        program: &vm::Program,
+        function_name: &vm::Identifier,
+        verifying_key_hash: Option<&str>,
+    ) -> Result<Option<vm::ProvingKey>> {
+        let source_hash = source_hash(program);
+        if !self.keys.contains_key(&source_hash) {
+            debug!("loading keys of {}", program.id());
+            let program_file = ProgramFile::load_cached(&program.to_string())?;
+            self.keys.insert(source_hash.clone(), program_file.keys);
+        }
+
+        let (proving_key, verifying_key) = match self.keys[&source_hash].map.get(function_name) {
+            Some(keys) => keys.clone(),
+            None => return Ok(None),
+        };
+        if let Some(expected_hash) = verifying_key_hash {
+            if deployment::verifying_key_hash(&verifying_key)? != expected_hash {
+                // forget the keys, so they are loaded again once the deployed build is cached
+                self.keys.remove(&source_hash);
+                bail!(
+                    "the keys of {}/{function_name} known to the prover don't match the deployed ones, \
+                    copy the .avm file of the deployment to {:?}",
+                    program.id(),
+                    ProgramFile::cache_path(program)
+                );
+            }
+        }
+        Ok(Some(proving_key))
+    }
+}
+
+fn source_hash(program: &vm::Program) -> String {
+    hex::encode(Sha256::digest(program.to_string()))
+}
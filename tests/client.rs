@@ -21,7 +21,7 @@ const CURRENT_ACCOUNT: &str = "%account";
 
 #[test]
 fn basic_program() {
-    let (_tempfile, home_path, _) = &new_account();
+    let (_tempfile, home_path, _) = &new_funded_account();
 
     // deploy a program
     let (_program_file, program_path, _) = load_program(HELLO_PROGRAM);
@@ -53,7 +53,7 @@ fn basic_program() {
 
 #[test]
 fn program_validations() {
-    let (_tempfile, home_path, _) = &new_account();
+    let (_tempfile, home_path, _) = &new_funded_account();
     let (_program_file, program_path, program_id) = load_program(HELLO_PROGRAM);
 
     // fail on execute non deployed command
@@ -70,13 +70,86 @@ fn program_validations() {
     )
     .unwrap();
 
+    // seeded dry runs are reproducible
+    #[cfg(feature = "snarkvm_backend")]
+    {
+        let args = ["1u32", "1u32", "--dry-run", "--seed", "42"];
+        let first = execute_program(home_path, &program_path, HELLO_PROGRAM, &args).unwrap();
+        let second = execute_program(home_path, &program_path, HELLO_PROGRAM, &args).unwrap();
+        assert_eq!(first, second);
+    }
+
+    // traced dry runs point to the failing instruction in the program source
+    #[cfg(feature = "snarkvm_backend")]
+    {
+        let args = ["4294967295u32", "1u32", "--dry-run", "--trace"];
+        let error = execute_program(home_path, &program_path, HELLO_PROGRAM, &args).unwrap_err();
+        assert!(error.contains(&format!(
+            "Failed to evaluate instruction `add r0 r1 into r2;` at {program_path}:7:5"
+        )));
+
+        let args = ["1u32", "1u32", "--dry-run", "--trace"];
+        let output = execute_program(home_path, &program_path, HELLO_PROGRAM, &args).unwrap();
+        assert_eq!(
+            serde_json::json!([format!(
+                "{program_path}:7:5 add r0 r1 into r2; r0 = 1u32, r1 = 1u32 => r2 = 2u32"
+            )]),
+            output["trace"]
+        );
+    }
+
+    // lambdavm only exposes the values of the function inputs and outputs, which are all hello has
+    #[cfg(feature = "lambdavm_backend")]
+    {
+        let args = ["1u32", "1u32", "--dry-run", "--trace"];
+        let output = execute_program(home_path, &program_path, HELLO_PROGRAM, &args).unwrap();
+        assert_eq!(
+            serde_json::json!([format!(
+                "{program_path}:7:5 add r0 r1 into r2; r0 = 1u32, r1 = 1u32 => r2 = 2u32"
+            )]),
+            output["trace"]
+        );
+    }
+
     // deploy a program
-    client_command(home_path, &["program", "deploy", &program_path]).unwrap();
+    let transaction = client_command(home_path, &["program", "deploy", &program_path]).unwrap();
+    let transaction_id = get_transaction_id(&transaction).unwrap();
+    retry_command(home_path, &["get", transaction_id]).unwrap();
+
+    // the deployed keys are cached to be reused by executions
+    let cached = fs::read_dir(format!("{home_path}/cache/programs")).unwrap();
+    assert_eq!(1, cached.count());
+
+    // fail on already deployed by a different account
+    let (_other_file, other_home_path, _) = &new_funded_account();
+    let error = client_command(other_home_path, &["program", "deploy", &program_path]).unwrap_err();
+    assert!(error.contains("Program already exists"));
+
+    // deploying again from the owner account registers a new edition
+    let transaction = client_command(home_path, &["program", "deploy", &program_path]).unwrap();
+    assert_eq!(1, transaction.pointer("/Deployment/edition").unwrap());
+    let transaction_id = get_transaction_id(&transaction).unwrap();
+    retry_command(home_path, &["get", transaction_id]).unwrap();
 
-    // fail on already deployed compiled locally
-    let error = client_command(home_path, &["program", "deploy", &program_path]).unwrap_err();
+    let editions = client_command(home_path, &["program", "editions", &program_id]).unwrap();
+    assert_eq!(2, editions["editions"].as_array().unwrap().len());
 
-    assert!(error.contains("Internal error: tx already exists in cache"));
+    // each edition keeps its deployment metadata
+    let info = client_command(home_path, &["program", "info", &program_id]).unwrap();
+    let info = info["editions"].as_array().unwrap();
+    assert_eq!(2, info.len());
+    assert_eq!(transaction_id, info[1]["transaction_id"].as_str().unwrap());
+    assert_eq!(info[0]["source_hash"], info[1]["source_hash"]);
+    assert_eq!(1, info[1]["functions"].as_array().unwrap().len());
+
+    // the program is listed starting from its id
+    let list = client_command(
+        home_path,
+        &["program", "list", "--from", &program_id, "--limit", "1"],
+    )
+    .unwrap();
+    assert_eq!(program_id, list["programs"][0]["program"].as_str().unwrap());
+    assert_eq!(1, list["programs"][0]["edition"]);
 
     // execute the program, retrieving it from the blockchain, using it's id
     execute_program(home_path, &program_id, "hello", &["1u32", "1u32"]).unwrap();
@@ -103,9 +176,126 @@ fn program_validations() {
     assert!(error.contains("expects 2 inputs"));
 }
 
+#[test]
+fn deploy_built_program() {
+    let (_tempfile, home_path, _) = &new_funded_account();
+    let (_program_file, program_path, program_id) = load_program(HELLO_PROGRAM);
+
+    // the program is checked before building it, hello leaks its private input in the public output
+    let check = client_command(home_path, &["program", "check", &program_path]).unwrap();
+    let diagnostics = check["diagnostics"].as_array().unwrap();
+    let warning = diagnostics
+        .iter()
+        .find(|diagnostic| diagnostic["severity"] == "warning")
+        .unwrap();
+    assert_eq!(8, warning["span"]["line"]);
+    assert!(check["valid"].as_bool().unwrap());
+    assert_eq!(1, diagnostics.len());
+
+    let build = client_command(home_path, &["program", "build", &program_path]).unwrap();
+    let avm_path = build["path"].as_str().unwrap();
+    assert!(build["functions"]["hello"]["constraints"].as_u64().unwrap() > 0);
+
+    // the cost report of the built file is the same except for the measured times
+    let cost = client_command(home_path, &["program", "cost", avm_path]).unwrap();
+    let hello_cost = &cost["functions"]["hello"];
+    assert_eq!(
+        build["functions"]["hello"]["constraints"],
+        hello_cost["constraints"]
+    );
+    assert_eq!(
+        build["functions"]["hello"]["proving_key_size"],
+        hello_cost["proving_key_size"]
+    );
+    assert!(hello_cost["proving_time_ms"].is_u64());
+    let header = client_command(home_path, &["program", "inspect", avm_path]).unwrap();
+    assert!(header["valid"].as_bool().unwrap());
+
+    // the built keys are deployed as is
+    let transaction = client_command(home_path, &["program", "deploy", avm_path]).unwrap();
+    let transaction_id = get_transaction_id(&transaction).unwrap();
+    retry_command(home_path, &["get", transaction_id]).unwrap();
+
+    let info = client_command(home_path, &["program", "info", &program_id]).unwrap();
+    assert_eq!(
+        header["header"]["verifying_keys"],
+        info["editions"][0]["verifying_key_hashes"]
+    );
+    fs::remove_file(avm_path).unwrap();
+}
+
+#[test]
+fn program_tests() {
+    let (_tempfile, home_path, _) = &new_account();
+    let (_program_file, program_path, _) = load_program(TOKEN_PROGRAM);
+
+    let test_file = NamedTempFile::new("token.test.json").unwrap();
+    let tests = serde_json::json!({
+        "program": program_path,
+        "tests": [
+            { "name": "mint", "function": MINT_FUNCTION, "inputs": ["10u64", CURRENT_ACCOUNT], "outputs": [null] },
+            { "name": "mint without address", "function": MINT_FUNCTION, "inputs": ["10u64"], "error": "" },
+            { "name": "mint twice", "function": MINT_FUNCTION, "inputs": ["10u64", CURRENT_ACCOUNT], "outputs": [null, null] },
+        ]
+    });
+    fs::write(test_file.path(), tests.to_string()).unwrap();
+    let test_path = test_file.path().to_str().unwrap();
+
+    // the test runner doesn't need a node
+    let report =
+        client_command(home_path, &["program", "test", test_path, "--skip-proofs"]).unwrap();
+    assert_eq!(2, report["passed"]);
+    assert_eq!(1, report["failed"]);
+    assert_eq!(
+        "expected 2 outputs, found 1",
+        report["tests"][2]["reason"].as_str().unwrap()
+    );
+
+    let report = client_command(home_path, &["program", "test", test_path]).unwrap();
+    assert_eq!(2, report["passed"]);
+}
+
+// lambdavm doesn't support proving authorized executions yet
+#[cfg(feature = "snarkvm_backend")]
+#[test]
+fn authorize_and_prove() {
+    let (_tempfile, home_path, _) = &new_funded_account();
+    let (_program_file, program_path, program_id) = load_program(HELLO_PROGRAM);
+
+    let transaction = client_command(home_path, &["program", "deploy", &program_path]).unwrap();
+    let transaction_id = get_transaction_id(&transaction).unwrap();
+    retry_command(home_path, &["get", transaction_id]).unwrap();
+
+    // sign the execution without proving it
+    let authorization_file = NamedTempFile::new("authorization.json").unwrap();
+    let authorization_path = authorization_file.path().to_string_lossy().to_string();
+    client_command(
+        home_path,
+        &[
+            "program",
+            "authorize",
+            &program_id,
+            HELLO_PROGRAM,
+            "1u32",
+            "1u32",
+            "--output",
+            &authorization_path,
+        ],
+    )
+    .unwrap();
+
+    // prove it from a home without account credentials
+    let prover_home = assert_fs::TempDir::new().unwrap();
+    let prover_home = prover_home.path().to_string_lossy().to_string();
+    let transaction =
+        client_command(&prover_home, &["program", "prove", &authorization_path]).unwrap();
+    let transaction_id = get_transaction_id(&transaction).unwrap();
+    retry_command(home_path, &["get", transaction_id]).unwrap();
+}
+
 #[test]
 fn decrypt_records() {
-    let (_acc_file, home_path, credentials) = &new_account();
+    let (_acc_file, home_path, credentials) = &new_funded_account();
     let (_program_file, program_path, _) = load_program(TOKEN_PROGRAM);
 
     // deploy a program, save txid
@@ -177,7 +367,7 @@ fn decrypt_records() {
 #[test]
 fn token_transaction() {
     // Create two accounts: Alice and Bob
-    let (_tempfile_alice, alice_home, alice_credentials) = &new_account();
+    let (_tempfile_alice, alice_home, alice_credentials) = &new_funded_account();
     let (_tempfile_bob, _bob_home, bob_credentials) = &new_account();
 
     // Load token program with Alice credentials
@@ -261,7 +451,7 @@ fn token_transaction() {
 #[test]
 fn consume_records() {
     // new account41
-    let (_acc_file, home_path, _) = &new_account();
+    let (_acc_file, home_path, _) = &new_funded_account();
 
     // load "records" program
     let (_program_file, program_path, _) = load_program("records");
@@ -319,7 +509,7 @@ fn consume_records() {
 
 #[test]
 fn try_create_credits() {
-    let (_tempfile, home_path, _) = &new_account();
+    let (_tempfile, home_path, _) = &new_funded_account();
 
     let credits_path = "aleo/credits.aleo";
 
@@ -414,40 +604,7 @@ fn transaction_fees() {
     assert!(output.contains("there are not records with enough credits for a 100 gates fee"));
 
     // transfer a known amount of credits to the test account
-    let validator_home = validator_account_path();
-
-    #[cfg(feature = "snarkvm_backend")]
-    let record = client_command(&validator_home, &["account", "records"])
-        .unwrap()
-        .pointer("/1/ciphertext")
-        .unwrap()
-        .as_str()
-        .unwrap()
-        .to_string();
-
-    #[cfg(feature = "lambdavm_backend")]
-    let record = client_command(&validator_home, &["account", "records"])
-        .unwrap()
-        .pointer("/1/ciphertext")
-        .unwrap()
-        .get("ciphertext")
-        .unwrap()
-        .as_str()
-        .unwrap()
-        .to_string();
-
-    client_command(
-        &validator_home,
-        &[
-            "credits",
-            "transfer",
-            &record,
-            credentials.get("address").unwrap(),
-            "10",
-        ],
-    )
-    .unwrap();
-
+    fund_account(credentials.get("address").unwrap(), 10);
     assert_balance(receiver_home, 10).unwrap();
 
     // try to run a deployment with a fee of more credits than available, should fail
@@ -655,9 +812,7 @@ fn staking() {
     // this should be improved to properly handle execution errors internally and showing a clear error message in the CLI
     assert!(error.contains(expected_subtraction_error));
 
-    // TODO add check: try to stake for an unexistent validator, fail
-
-    // stake all available, but fail because this is not the expected aleo account
+    // try to stake for an unexistent validator without signing with its key, fail
     let error = client_command(
         &receiver_home,
         &[
@@ -665,12 +820,58 @@ fn staking() {
             "stake",
             "50",
             &user_record,
-            &tendermint_validator,
+            "TtJ9B7yGXANFIJqH2LJO8JN6M2WOn2w7sRN0HHi14UE=",
         ],
     )
     .unwrap_err();
+    assert!(error.contains("must be signed by the validator key"));
+
+    // stake all available from an account other than the validator's, delegating it
+    client_command(
+        &receiver_home,
+        &[
+            "credits",
+            "stake",
+            "50",
+            &user_record,
+            &tendermint_validator,
+        ],
+    )
+    .unwrap();
+    assert_balance(&receiver_home, 0).unwrap();
 
-    assert!(error.contains("attempted to apply a staking update on a different aleo account"));
+    // the delegator accrues rewards, which it claims without any spendable credits
+    let pending_rewards = || {
+        client_command(&receiver_home, &["account", "rewards"])
+            .unwrap()
+            .pointer("/rewards")
+            .unwrap()
+            .as_u64()
+            .unwrap()
+    };
+    retry::retry(Fixed::from_millis(1000).take(10), || {
+        if pending_rewards() > 0 {
+            Ok(())
+        } else {
+            Err("no rewards accrued")
+        }
+    })
+    .unwrap();
+    client_command(&receiver_home, &["credits", "claim"]).unwrap();
+    retry::retry(Fixed::from_millis(1000).take(10), || {
+        let balance = client_command(&receiver_home, &["account", "balance"])
+            .unwrap()
+            .pointer("/balance")
+            .unwrap()
+            .as_u64()
+            .unwrap();
+        if balance > 0 {
+            Ok(())
+        } else {
+            Err("the claimed rewards weren't paid")
+        }
+    })
+    .unwrap();
 
     #[cfg(feature = "snarkvm_backend")]
     let validator_record = client_command(&validator_home, &["account", "records"])
@@ -718,6 +919,31 @@ fn staking() {
         .unwrap()
         .as_str()
         .unwrap();
+    // the validator is online, so it can't be unjailed
+    let error = client_command(
+        &validator_home,
+        &["credits", "unjail", staked_credits_record],
+    )
+    .unwrap_err();
+    assert!(error.contains("is not jailed"));
+
+    // the validator key needs to be rotated to a different one
+    let validator_key = format!("{validator_home}/config/priv_validator_key.json");
+    let error = client_command(
+        &validator_home,
+        &[
+            "credits",
+            "rotate-key",
+            staked_credits_record,
+            "--validator-key",
+            &validator_key,
+            "--new-validator-key",
+            &validator_key,
+        ],
+    )
+    .unwrap_err();
+    assert!(error.contains("must be signed by the new key"));
+
     // try to unstake more than available, fail
     let error = client_command(
         &validator_home,
@@ -728,15 +954,103 @@ fn staking() {
     assert!(error.contains(expected_subtraction_error));
 
     // unstake all available
-    client_command(
+    let transaction = client_command(
         &validator_home,
         &["credits", "unstake", "5", staked_credits_record],
     )
     .unwrap();
 
+    #[cfg(feature = "snarkvm_backend")]
+    let unstaked_record = transaction
+        .pointer("/Execution/transitions/0/outputs/0/value")
+        .unwrap()
+        .as_str()
+        .unwrap();
+
+    #[cfg(feature = "lambdavm_backend")]
+    let unstaked_record = transaction
+        .pointer("/Execution/transitions/0/outputs/0/EncryptedRecord/1/ciphertext")
+        .unwrap()
+        .as_str()
+        .unwrap();
+
+    // the unstaked credits can't be withdrawn until the unbonding period is over
+    let error =
+        client_command(&validator_home, &["credits", "withdraw", unstaked_record]).unwrap_err();
+    assert!(error.contains("can't be withdrawn until block"));
+
     // TODO: Test to see if the validator_set file actually gets updated with staking updates
 }
 
+#[test]
+fn credits_supply() {
+    let validator_home = validator_account_path();
+    let supply = client_command(&validator_home, &["credits", "supply"]).unwrap();
+    let gates = |field| supply.pointer(field).unwrap().as_u64().unwrap();
+
+    // the validators are rewarded with new credits on every block
+    assert!(gates("/genesis") > 0);
+    assert!(gates("/minted") > 0);
+    assert_eq!(
+        gates("/genesis") + gates("/minted") - gates("/burned"),
+        gates("/total")
+    );
+}
+
+#[test]
+fn spend_treasury() {
+    let validator_home = validator_account_path();
+    let treasury = client_command(&validator_home, &["credits", "treasury"]).unwrap();
+    assert_eq!(0, treasury.pointer("/spends").unwrap().as_u64().unwrap());
+
+    // the testnet genesis doesn't set treasury keys, so its credits can't be spent
+    let (_tempfile, _, credentials) = new_account();
+    let validator_key = format!("{validator_home}/config/priv_validator_key.json");
+    let error = client_command(
+        &validator_home,
+        &[
+            "credits",
+            "spend-treasury",
+            credentials.get("address").unwrap(),
+            "10",
+            "--treasury-key",
+            &validator_key,
+        ],
+    )
+    .unwrap_err();
+    assert!(error.contains("the treasury has no keys to authorize spends"));
+
+    // the spend can be signed separately, without account credentials, and the signature passed along
+    let signer_home = assert_fs::TempDir::new().unwrap();
+    let signer_home = signer_home.path().to_string_lossy().to_string();
+    let signature = client_command(
+        &signer_home,
+        &[
+            "credits",
+            "sign-treasury-spend",
+            credentials.get("address").unwrap(),
+            "10",
+            "--treasury-key",
+            &validator_key,
+        ],
+    )
+    .unwrap();
+    assert!(signature["signature"].is_string());
+    let error = client_command(
+        &validator_home,
+        &[
+            "credits",
+            "spend-treasury",
+            credentials.get("address").unwrap(),
+            "10",
+            "--signature",
+            &signature.to_string(),
+        ],
+    )
+    .unwrap_err();
+    assert!(error.contains("the treasury has no keys to authorize spends"));
+}
+
 // HELPERS
 
 /// Retries iteratively to get a transaction until something returns
@@ -788,6 +1102,41 @@ fn new_account() -> (NamedTempFile, String, HashMap<String, String>) {
     (tempfile, aleo_path, credentials)
 }
 
+/// Same as `new_account`, but funding the new account with enough credits to pay for deployments.
+fn new_funded_account() -> (NamedTempFile, String, HashMap<String, String>) {
+    let (tempfile, home_path, credentials) = new_account();
+    fund_account(credentials.get("address").unwrap(), 10);
+    (tempfile, home_path, credentials)
+}
+
+/// Transfer credits from a validator record to the given address and wait until the transfer is committed.
+/// Tests run concurrently and may spend the same validator record, in which case the transfer is retried
+/// with another one.
+fn fund_account(address: &str, gates: u64) {
+    let validator_home = validator_account_path();
+    retry::retry(Fixed::from_millis(1000).take(5), || {
+        let records = client_command(&validator_home, &["account", "records"])?;
+        let records = records.as_array().unwrap();
+        let record = &records[rand::thread_rng().gen_range(0..records.len())]["ciphertext"];
+        #[cfg(feature = "lambdavm_backend")]
+        let record = &record["ciphertext"];
+
+        let transaction = client_command(
+            &validator_home,
+            &[
+                "credits",
+                "transfer",
+                record.as_str().unwrap(),
+                address,
+                &gates.to_string(),
+            ],
+        )?;
+        let transaction_id = get_transaction_id(&transaction).unwrap();
+        retry_command(&validator_home, &["get", transaction_id]).map_err(|e| e.to_string())
+    })
+    .unwrap();
+}
+
 /// Load the source code from the given example file, randomize it's name, and return a tempfile
 /// with the same source code but with the new name, along with its path and the new id.
 /// The file will be removed when it goes out of scope.